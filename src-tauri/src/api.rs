@@ -0,0 +1,653 @@
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use crate::auth::{self, generate_token, Claims};
+use crate::database::ControlCard;
+use crate::deadlines;
+use crate::error::{AppError, AppResult};
+use crate::models::{self, CardFilter, CardInput, ControlCardPatch, ExecutorSummary, User};
+use crate::operations;
+use crate::profiles::json_store;
+use crate::state::AppState;
+
+/// Версия API входит в путь: /api/v1/...
+pub const API_VERSION: &str = "v1";
+
+/// Сколько ждать завершения текущих запросов при остановке сервера
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Сколько неудачных входов подряд допускается без паузы
+const FREE_LOGIN_ATTEMPTS: u32 = 5;
+
+/// Пауза после каждой следующей неудачи удваивается, но не превышает этого предела
+const MAX_LOGIN_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Через сколько после последней неудачи счётчик неудачных входов забывается
+const LOGIN_FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Где слушает сервер: только на этом компьютере или во всей локальной сети
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiBind {
+    #[default]
+    Localhost,
+    /// Сервер работает по HTTP без шифрования: пароли и токены идут по сети открытым текстом.
+    /// Для доступа из недоверенной сети нужен внешний обратный прокси с HTTPS.
+    Lan,
+}
+
+/// Настройки HTTP API рабочего места. Хранятся в JSON-файле рядом с профилями.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    #[serde(default)]
+    pub bind: ApiBind,
+    pub port: u16,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self { enabled: false, bind: ApiBind::Localhost, port: 8745 }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiStatus {
+    pub settings: ApiSettings,
+    /// Адрес, на котором сервер принимает запросы; None — сервер не запущен
+    pub address: Option<String>,
+    pub version: &'static str,
+}
+
+/// Откуда обработчики берут состояние приложения: из Tauri или напрямую
+pub trait StateProvider: Clone + Send + Sync + 'static {
+    fn app_state(&self) -> &AppState;
+}
+
+impl StateProvider for Arc<AppState> {
+    fn app_state(&self) -> &AppState {
+        self
+    }
+}
+
+struct RunningServer {
+    address: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Встроенный HTTP-сервер. Выключен, пока администратор не включит его в настройках.
+#[derive(Default)]
+pub struct ApiServer {
+    file: Option<PathBuf>,
+    settings: RwLock<ApiSettings>,
+    running: Mutex<Option<RunningServer>>,
+}
+
+impl ApiServer {
+    /// Повреждённый или отсутствующий файл даёт настройки по умолчанию (сервер выключен)
+    pub fn load(file: PathBuf) -> Self {
+        let settings = json_store::load(&file);
+        Self { file: Some(file), settings: RwLock::new(settings), running: Mutex::new(None) }
+    }
+
+    pub async fn status(&self) -> ApiStatus {
+        ApiStatus {
+            settings: self.settings.read().await.clone(),
+            address: self.running.lock().await.as_ref().map(|r| r.address.to_string()),
+            version: API_VERSION,
+        }
+    }
+
+    /// Запускает сервер по текущим настройкам, остановив предыдущий
+    pub async fn start<S: StateProvider>(&self, provider: S) -> AppResult<ApiStatus> {
+        let settings = self.settings.read().await.clone();
+        check_bind(&settings)?;
+        let mut running = self.running.lock().await;
+        if let Some(previous) = running.take() {
+            stop_server(previous).await;
+        }
+
+        if settings.enabled {
+            let ip = match settings.bind {
+                ApiBind::Localhost => Ipv4Addr::LOCALHOST,
+                ApiBind::Lan => Ipv4Addr::UNSPECIFIED,
+            };
+            let listener = tokio::net::TcpListener::bind((ip, settings.port))
+                .await
+                .map_err(|e| AppError::conflict(format!("Cannot listen on port {}: {}", settings.port, e)))?;
+            let address = listener
+                .local_addr()
+                .map_err(|e| AppError::db(format!("Failed to get API server address: {}", e)))?;
+
+            let (shutdown, signal) = oneshot::channel();
+            let task = tokio::spawn(async move {
+                let server = axum::serve(listener, router(provider))
+                    .with_graceful_shutdown(async { signal.await.ok(); });
+                if let Err(e) = server.await {
+                    eprintln!("API server stopped: {}", e);
+                }
+            });
+            *running = Some(RunningServer { address, shutdown, task });
+        }
+        drop(running);
+
+        Ok(self.status().await)
+    }
+
+    pub async fn update<S: StateProvider>(&self, settings: ApiSettings, provider: S) -> AppResult<ApiStatus> {
+        if settings.port == 0 {
+            return Err(AppError::validation("port", "Port must be between 1 and 65535"));
+        }
+        check_bind(&settings)?;
+
+        self.save(&settings)?;
+        *self.settings.write().await = settings;
+        self.start(provider).await
+    }
+
+    fn save(&self, settings: &ApiSettings) -> AppResult<()> {
+        json_store::save(self.file.as_deref(), settings, "API settings")
+    }
+}
+
+/// Доступ из сети открывается только при собственном секрете подписи токенов
+fn check_bind(settings: &ApiSettings) -> AppResult<()> {
+    if settings.enabled && settings.bind == ApiBind::Lan && !auth::has_token_secret() {
+        return Err(AppError::validation("bind", "LAN access is unavailable: token signing secret is not initialized"));
+    }
+    Ok(())
+}
+
+async fn stop_server(server: RunningServer) {
+    let _ = server.shutdown.send(());
+    let mut task = server.task;
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut task).await.is_err() {
+        task.abort();
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LoginFailures {
+    count: u32,
+    last: Instant,
+}
+
+/// Неудачные входы через API по именам пользователей. После нескольких ошибок
+/// следующая попытка возможна только после паузы, растущей с каждой ошибкой.
+#[derive(Default)]
+struct LoginThrottle {
+    failures: std::sync::Mutex<HashMap<String, LoginFailures>>,
+}
+
+impl LoginThrottle {
+    fn delay(count: u32) -> Duration {
+        match count.checked_sub(FREE_LOGIN_ATTEMPTS) {
+            None => Duration::ZERO,
+            Some(extra) => Duration::from_secs(1 << extra.min(16)).min(MAX_LOGIN_DELAY),
+        }
+    }
+
+    /// Сколько осталось ждать до следующей попытки входа
+    fn wait(&self, username: &str, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = failures.get(username)?;
+        let ready = entry.last + Self::delay(entry.count);
+        (ready > now).then(|| ready - now)
+    }
+
+    fn failed(&self, username: &str, now: Instant) {
+        let mut failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+        failures.retain(|_, f| now.duration_since(f.last) < LOGIN_FAILURE_WINDOW);
+        let entry = failures.entry(username.to_string()).or_insert(LoginFailures { count: 0, last: now });
+        entry.count += 1;
+        entry.last = now;
+    }
+
+    fn succeeded(&self, username: &str) {
+        self.failures.lock().unwrap_or_else(PoisonError::into_inner).remove(username);
+    }
+}
+
+/// Маршруты API. Ответы об ошибках имеют тот же вид, что и у команд: `{ code, message, details }`.
+pub fn router<S: StateProvider>(provider: S) -> Router {
+    Router::new()
+        .route("/api/v1/health", get(health::<S>))
+        .route("/api/v1/auth/login", post(login::<S>))
+        .route("/api/v1/cards", get(list_cards::<S>).post(create_card::<S>))
+        .route("/api/v1/cards/{id}", get(get_card::<S>).patch(patch_card::<S>))
+        .route("/api/v1/users", get(list_users::<S>))
+        .route("/api/v1/users/me", get(current_user::<S>))
+        .route("/api/v1/users/executors", get(list_executors::<S>))
+        .route("/api/v1/reports/executors", get(executor_report::<S>))
+        .layer(Extension(Arc::new(LoginThrottle::default())))
+        .with_state(provider)
+}
+
+struct ApiError(AppError);
+
+impl From<AppError> for ApiError {
+    fn from(error: AppError) -> Self {
+        ApiError(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            AppError::NotConnected => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::ReadOnly => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self.0)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Токен передаётся в заголовке `Authorization: Bearer <token>` — тот же, что выдаёт вход в приложении.
+/// Действует только для подключённой сейчас БД. Роль берётся из БД, а не из токена:
+/// понижение или удаление пользователя действует сразу, а не по истечении токена.
+async fn claims(state: &AppState, headers: &HeaderMap) -> AppResult<Claims> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::unauthorized("Missing bearer token"))?;
+    let mut claims = state.verify_token(token.trim()).await?;
+
+    let db = state.db().await?;
+    let user = match operations::current_user(&db, &claims).await {
+        Err(AppError::NotFound(_)) => return Err(AppError::unauthorized("User no longer exists")),
+        result => result?,
+    };
+    claims.role = user.role;
+    Ok(claims)
+}
+
+fn json_body<T>(payload: Result<Json<T>, JsonRejection>) -> AppResult<T> {
+    payload
+        .map(|Json(value)| value)
+        .map_err(|e| AppError::validation("body", e.body_text()))
+}
+
+fn query<T>(params: Result<Query<T>, QueryRejection>) -> AppResult<T> {
+    params
+        .map(|Query(value)| value)
+        .map_err(|e| AppError::validation("query", e.body_text()))
+}
+
+#[derive(Serialize)]
+struct Health {
+    version: &'static str,
+    connected: bool,
+    read_only: bool,
+}
+
+async fn health<S: StateProvider>(State(provider): State<S>) -> Json<Health> {
+    let db = provider.app_state().db().await.ok();
+    Json(Health {
+        version: API_VERSION,
+        connected: db.is_some(),
+        read_only: db.is_some_and(|db| db.is_read_only()),
+    })
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+struct LoginResponse {
+    token: String,
+    user: User,
+}
+
+async fn login<S: StateProvider>(
+    State(provider): State<S>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    payload: Result<Json<LoginRequest>, JsonRejection>,
+) -> ApiResult<Json<LoginResponse>> {
+    let request = json_body(payload)?;
+    let key = request.username.trim().to_lowercase();
+    if let Some(wait) = throttle.wait(&key, Instant::now()) {
+        return Err(AppError::unauthorized(format!(
+            "Too many failed login attempts; try again in {} s",
+            wait.as_secs() + 1
+        ))
+        .into());
+    }
+
+    let db = provider.app_state().db().await?;
+    let user = match operations::authenticate(&db, &request.username, &request.password).await {
+        Err(e @ AppError::Unauthorized(_)) => {
+            throttle.failed(&key, Instant::now());
+            return Err(e.into());
+        }
+        result => result?,
+    };
+    throttle.succeeded(&key);
+    let token = generate_token(user.id, &user.role, db.get_path())?;
+    Ok(Json(LoginResponse { token, user }))
+}
+
+async fn list_cards<S: StateProvider>(
+    State(provider): State<S>,
+    headers: HeaderMap,
+    filter: Result<Query<CardFilter>, QueryRejection>,
+) -> ApiResult<Json<Vec<ControlCard>>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let filter = query(filter)?;
+    let db = provider.app_state().db().await?;
+    Ok(Json(operations::visible_cards(&db, &claims, &filter).await?))
+}
+
+async fn get_card<S: StateProvider>(
+    State(provider): State<S>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<ControlCard>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let db = provider.app_state().db().await?;
+    Ok(Json(operations::get_card(&db, &claims, id).await?))
+}
+
+async fn create_card<S: StateProvider>(
+    State(provider): State<S>,
+    headers: HeaderMap,
+    payload: Result<Json<CardInput>, JsonRejection>,
+) -> ApiResult<(StatusCode, Json<ControlCard>)> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let input = json_body(payload)?;
+    let state = provider.app_state();
+    let id = operations::create_card(state, &claims, &input).await?;
+    let card = state.db().await?.get_control_card(id).await?;
+    Ok((StatusCode::CREATED, Json(card)))
+}
+
+/// Тело — частичное обновление с expected_version, как у команды patch_control_card
+async fn patch_card<S: StateProvider>(
+    State(provider): State<S>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    payload: Result<Json<ControlCardPatch>, JsonRejection>,
+) -> ApiResult<Json<ControlCard>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let patch = json_body(payload)?;
+    let state = provider.app_state();
+    operations::patch_card(state, &claims, id, &patch).await?;
+    let card = state.db().await?.get_control_card(id).await?;
+    Ok(Json(card))
+}
+
+async fn list_users<S: StateProvider>(State(provider): State<S>, headers: HeaderMap) -> ApiResult<Json<Vec<User>>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let db = provider.app_state().db().await?;
+    Ok(Json(operations::list_users(&db, &claims).await?))
+}
+
+async fn current_user<S: StateProvider>(State(provider): State<S>, headers: HeaderMap) -> ApiResult<Json<User>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let db = provider.app_state().db().await?;
+    Ok(Json(operations::current_user(&db, &claims).await?))
+}
+
+async fn list_executors<S: StateProvider>(State(provider): State<S>, headers: HeaderMap) -> ApiResult<Json<Vec<User>>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let db = provider.app_state().db().await?;
+    Ok(Json(operations::list_executors(&db, &claims).await?))
+}
+
+/// Свод по исполнителям по тем же карточкам, что видит пользователь в списке
+async fn executor_report<S: StateProvider>(
+    State(provider): State<S>,
+    headers: HeaderMap,
+    filter: Result<Query<CardFilter>, QueryRejection>,
+) -> ApiResult<Json<Vec<ExecutorSummary>>> {
+    let claims = claims(provider.app_state(), &headers).await?;
+    let filter = query(filter)?;
+    let db = provider.app_state().db().await?;
+    let cards = operations::visible_cards(&db, &claims, &filter).await?;
+    Ok(Json(models::summarize_by_executor(&cards, deadlines::today())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use chrono::Datelike;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+    use crate::database::Database;
+
+    struct TestApi {
+        db: Database,
+        /// Один роутер на все запросы теста, как у запущенного сервера
+        router: Router,
+        admin: String,
+        first_user: (i64, String),
+        second_user: (i64, String),
+    }
+
+    async fn setup(read_only: bool) -> TestApi {
+        auth::init_test_token_secret();
+        let db = Database::connect_in_memory(read_only).await.unwrap();
+        let create = |username: &'static str, role: &'static str| {
+            let db = db.clone();
+            async move {
+                let id = db.create_user(username, None, None, "not-a-hash", role).await.unwrap();
+                (id, generate_token(id, role, db.get_path()).unwrap())
+            }
+        };
+        let (_, admin) = create("admin", "admin").await;
+        let first_user = create("first", "user").await;
+        let second_user = create("second", "user").await;
+
+        let router = router(Arc::new(AppState::with_database(db.clone())));
+        TestApi { db, router, admin, first_user, second_user }
+    }
+
+    impl TestApi {
+        async fn call(&self, method: &str, uri: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            let body = match body {
+                Some(body) => {
+                    request = request.header(header::CONTENT_TYPE, "application/json");
+                    Body::from(body.to_string())
+                }
+                None => Body::empty(),
+            };
+
+            let response = self.router.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        }
+
+        async fn create_card(&self, executor_user_id: i64) -> Value {
+            let card = json!({
+                "year": deadlines::today().year(),
+                "executor_user_id": executor_user_id,
+                "reporter": "Руководитель",
+                "summary": "Подготовить ответ",
+                "document_reference": "Вх. 1",
+            });
+            let (status, body) = self.call("POST", "/api/v1/cards", Some(&self.admin), Some(card)).await;
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+            body
+        }
+    }
+
+    #[tokio::test]
+    async fn request_without_token_is_unauthorized() {
+        let api = setup(false).await;
+        let (status, body) = api.call("GET", "/api/v1/cards", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+
+        let (status, _) = api.call("GET", "/api/v1/cards", Some("garbage"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn token_from_another_database_is_unauthorized() {
+        let api = setup(false).await;
+        let foreign = generate_token(1, "admin", "other.db").unwrap();
+        let (status, body) = api.call("GET", "/api/v1/users", Some(&foreign), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    #[tokio::test]
+    async fn user_cannot_create_cards_or_list_users() {
+        let api = setup(false).await;
+        let (user_id, token) = &api.first_user;
+        let card = json!({
+            "year": deadlines::today().year(),
+            "executor_user_id": user_id,
+            "reporter": "Руководитель",
+            "summary": "Подготовить ответ",
+            "document_reference": "Вх. 1",
+        });
+
+        let (status, _) = api.call("POST", "/api/v1/cards", Some(token), Some(card)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = api.call("GET", "/api/v1/users", Some(token), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, users) = api.call("GET", "/api/v1/users", Some(&api.admin), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(users.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn user_sees_only_own_cards() {
+        let api = setup(false).await;
+        let own = api.create_card(api.first_user.0).await;
+        let other = api.create_card(api.second_user.0).await;
+
+        let (status, cards) = api.call("GET", "/api/v1/cards", Some(&api.first_user.1), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<_> = cards.as_array().unwrap().iter().map(|c| c["id"].clone()).collect();
+        assert_eq!(ids, vec![own["id"].clone()]);
+
+        let uri = format!("/api/v1/cards/{}", other["id"]);
+        let (status, _) = api.call("GET", &uri, Some(&api.first_user.1), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (_, cards) = api.call("GET", "/api/v1/cards", Some(&api.admin), None).await;
+        assert_eq!(cards.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn patch_with_stale_version_conflicts() {
+        let api = setup(false).await;
+        let card = api.create_card(api.first_user.0).await;
+        let uri = format!("/api/v1/cards/{}", card["id"]);
+        let version = card["version"].as_i64().unwrap();
+
+        let (status, patched) = api
+            .call("PATCH", &uri, Some(&api.admin), Some(json!({ "expected_version": version, "summary": "Новое" })))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", patched);
+        assert_eq!(patched["summary"], "Новое");
+        assert_eq!(patched["version"], version + 1);
+
+        let (status, body) = api
+            .call("PATCH", &uri, Some(&api.admin), Some(json!({ "expected_version": version, "summary": "Старое" })))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+    }
+
+    #[tokio::test]
+    async fn read_only_database_rejects_changes() {
+        let api = setup(true).await;
+        let card = json!({
+            "year": deadlines::today().year(),
+            "executor_user_id": api.first_user.0,
+            "reporter": "Руководитель",
+            "summary": "Подготовить ответ",
+            "document_reference": "Вх. 1",
+        });
+
+        let (status, _) = api.call("POST", "/api/v1/cards", Some(&api.admin), Some(card)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = api.call("GET", "/api/v1/cards", Some(&api.admin), None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn demoted_or_deleted_user_loses_access_immediately() {
+        let api = setup(false).await;
+        let admin = api.db.get_user_by_username("admin").await.unwrap().unwrap();
+        let (status, _) = api.call("GET", "/api/v1/users", Some(&api.admin), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        api.db.update_user(admin.id, "admin", None, None, "user").await.unwrap();
+        let (status, _) = api.call("GET", "/api/v1/users", Some(&api.admin), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (user_id, token) = &api.second_user;
+        api.db.delete_user(*user_id).await.unwrap();
+        let (status, body) = api.call("GET", "/api/v1/cards", Some(token), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    #[tokio::test]
+    async fn repeated_failed_logins_are_throttled() {
+        let api = setup(false).await;
+        let hash = auth::hash_password("correct-password").unwrap();
+        api.db.create_user("operator", None, None, &hash, "controller").await.unwrap();
+        let login = |password: &str| json!({ "username": "operator", "password": password });
+
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            let (status, body) = api.call("POST", "/api/v1/auth/login", None, Some(login("wrong"))).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["message"], "Invalid username or password");
+        }
+
+        // Пока идёт пауза, не принимается даже верный пароль; имя сравнивается без учёта регистра
+        let blocked = json!({ "username": "OPERATOR", "password": "correct-password" });
+        let (status, body) = api.call("POST", "/api/v1/auth/login", None, Some(blocked)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["message"].as_str().unwrap().starts_with("Too many failed login attempts"), "{}", body);
+
+        // Пауза растёт с каждой неудачей, не касается других имён и снимается успешным входом
+        let throttle = LoginThrottle::default();
+        let now = Instant::now();
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            assert_eq!(throttle.wait("operator", now), None);
+            throttle.failed("operator", now);
+        }
+        assert_eq!(throttle.wait("operator", now), Some(Duration::from_secs(1)));
+        assert_eq!(throttle.wait("operator", now + Duration::from_secs(1)), None);
+        assert_eq!(throttle.wait("admin", now), None);
+        throttle.failed("operator", now);
+        assert_eq!(throttle.wait("operator", now), Some(Duration::from_secs(2)));
+        throttle.succeeded("operator");
+        assert_eq!(throttle.wait("operator", now), None);
+    }
+}
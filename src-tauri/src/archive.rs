@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool}, Connection, FromRow};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::database::ControlCard;
+use crate::encryption;
+use crate::error::{AppError, AppResult};
+
+/// Запись об архиве года в основной БД
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct ArchiveInfo {
+    pub year: i32,
+    /// Имя файла архива; файл лежит рядом с основной БД
+    pub file_name: String,
+    /// SHA-256 файла архива, записывается после его формирования
+    pub checksum: Option<String>,
+    pub card_count: i64,
+    /// Сколько незакрытых карточек года осталось в основной БД
+    pub carried_over: i64,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+    /// Подключён ли архив в этом клиенте
+    #[sqlx(default)]
+    #[serde(default)]
+    pub attached: bool,
+}
+
+/// Имя файла архива: calendar.db -> calendar_archive_2025.db
+pub fn archive_file_name(db_path: &str, year: i32) -> String {
+    let stem = Path::new(db_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("calendar");
+    format!("{}_archive_{}.db", stem, year)
+}
+
+/// Архивы хранятся рядом с основной БД: путь к общей папке на разных рабочих местах может отличаться
+pub fn archive_path(db_path: &str, file_name: &str) -> PathBuf {
+    Path::new(db_path)
+        .parent()
+        .map(|dir| dir.join(file_name))
+        .unwrap_or_else(|| PathBuf::from(file_name))
+}
+
+pub fn file_checksum(path: &Path) -> AppResult<String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| AppError::db(format!("Failed to open archive file: {}", e)))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| AppError::db(format!("Failed to read archive file: {}", e)))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Перешифровывает архив во временный файл рядом с ним.
+/// Возвращает путь к временному файлу и его контрольную сумму.
+pub async fn export_with_key(path: &Path, key: Option<&str>, new_key: Option<&str>) -> AppResult<(PathBuf, String)> {
+    let temp = path.with_extension("rekey.tmp");
+    let _ = std::fs::remove_file(&temp);
+
+    // ATTACH наследует флаги соединения: без create_if_missing временный файл не создать
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    let options = encryption::with_key(options, key);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to open archive", e))?;
+
+    let result = encryption::export_with_key(&mut conn, &temp, new_key).await;
+    let _ = conn.close().await;
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(AppError::from_sqlx("Failed to re-encrypt archive", e));
+    }
+
+    let checksum = file_checksum(&temp)?;
+    Ok((temp, checksum))
+}
+
+/// Записывает контрольные суммы перешифрованных архивов в файл БД
+pub async fn record_checksums(db_file: &Path, key: Option<&str>, checksums: &[(i32, String)]) -> AppResult<()> {
+    let options = encryption::with_key(SqliteConnectOptions::new().filename(db_file), key);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to open database", e))?;
+
+    let mut result = Ok(());
+    for (year, checksum) in checksums {
+        result = sqlx::query("UPDATE archives SET checksum = ?1 WHERE year = ?2")
+            .bind(checksum)
+            .bind(year)
+            .execute(&mut conn)
+            .await
+            .map(|_| ());
+        if result.is_err() {
+            break;
+        }
+    }
+    let _ = conn.close().await;
+    result.map_err(|e| AppError::from_sqlx("Failed to record archive checksums", e))
+}
+
+/// Архив, подключённый только для чтения
+#[derive(Clone)]
+pub struct ArchiveDb {
+    pub year: i32,
+    pool: SqlitePool,
+}
+
+impl ArchiveDb {
+    /// Открывает архив, предварительно сверив контрольную сумму файла.
+    /// Архив зашифрован тем же ключом, что и основная БД.
+    pub async fn open(info: &ArchiveInfo, path: &Path, key: Option<&str>) -> AppResult<Self> {
+        let Some(expected) = info.checksum.as_deref() else {
+            return Err(AppError::validation("year", format!("Archive of {} has no recorded checksum", info.year)));
+        };
+
+        if !path.exists() {
+            return Err(AppError::not_found(format!("Archive file '{}' not found", path.display())));
+        }
+
+        let actual = file_checksum(path)?;
+        if actual != expected {
+            return Err(AppError::conflict(format!(
+                "Archive file '{}' has been modified: checksum mismatch",
+                path.display()
+            )));
+        }
+
+        // immutable: файл архива не меняется, блокировки на общем диске не нужны
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .read_only(true)
+            .immutable(true);
+        let options = encryption::with_key(options, key);
+
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| AppError::from_sqlx("Failed to open archive", e))?;
+
+        Ok(Self { year: info.year, pool })
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Поиск по номеру и содержанию. executor_user_id ограничивает выборку карточками исполнителя.
+    pub async fn search_cards(&self, query: &str, executor_user_id: Option<i64>) -> AppResult<Vec<ControlCard>> {
+        let pattern = format!("%{}%", query.trim());
+
+        sqlx::query_as::<_, ControlCard>(
+            r#"
+            SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+            FROM control_cards
+            WHERE (formatted_number LIKE ?1
+                   OR CAST(card_number AS TEXT) || '/' || CAST(year AS TEXT) LIKE ?1
+                   OR summary LIKE ?1
+                   OR document_reference LIKE ?1)
+              AND (?2 IS NULL OR executor_user_id = ?2)
+            ORDER BY card_number DESC
+            "#,
+        )
+        .bind(&pattern)
+        .bind(executor_user_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to search archive", e))
+    }
+}
@@ -1,31 +1,101 @@
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use bcrypt::{hash, verify, DEFAULT_COST};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::error::{AppError, AppResult};
+use crate::profiles::json_store;
 
-const JWT_SECRET: &str = "your-secret-key-change-in-production";
 const JWT_EXPIRATION_HOURS: u64 = 24;
 
+const TOKEN_SECRET_LEN: usize = 32;
+
+/// Секрет подписи токенов, свой у каждой установки. Задаётся при запуске приложения.
+static TOKEN_SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Default)]
+struct StoredSecret {
+    secret: Option<String>,
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// Читает секрет из файла в каталоге настроек, а если его нет — создаёт случайный.
+/// Несохранённый секрет действует до перезапуска, после него сохранённые сессии не восстановятся.
+pub fn init_token_secret(file: &Path) -> AppResult<()> {
+    let stored: StoredSecret = json_store::load(file);
+    if let Some(secret) = stored.secret.as_deref().and_then(decode_hex).filter(|s| s.len() >= TOKEN_SECRET_LEN) {
+        let _ = TOKEN_SECRET.set(secret);
+        return Ok(());
+    }
+
+    let mut secret = vec![0u8; TOKEN_SECRET_LEN];
+    getrandom::getrandom(&mut secret)
+        .map_err(|e| AppError::db(format!("Failed to generate token secret: {}", e)))?;
+    let stored = StoredSecret { secret: Some(encode_hex(&secret)) };
+    let _ = TOKEN_SECRET.set(secret);
+    json_store::save(Some(file), &stored, "token secret")
+}
+
+/// Постоянный секрет для тестов, без файла
+#[cfg(test)]
+pub fn init_test_token_secret() {
+    let _ = TOKEN_SECRET.set(vec![7; TOKEN_SECRET_LEN]);
+}
+
+pub fn has_token_secret() -> bool {
+    TOKEN_SECRET.get().is_some()
+}
+
+fn token_secret() -> AppResult<&'static [u8]> {
+    TOKEN_SECRET
+        .get()
+        .map(Vec::as_slice)
+        .ok_or_else(|| AppError::db("Token secret is not initialized"))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i64,
     pub role: String,
+    /// БД, в которой выдан токен: вход в одном профиле не действует в другом
+    pub db: String,
     pub exp: usize,
 }
 
-pub fn hash_password(password: &str) -> Result<String, String> {
+/// Идентификатор БД в токене — хэш пути, чтобы не раскрывать сам путь
+fn database_id(db_path: &str) -> String {
+    encode_hex(&Sha256::digest(db_path.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> AppResult<String> {
     if password.len() < 6 {
-        return Err("Password must be at least 6 characters long".to_string());
+        return Err(AppError::validation("password", "Password must be at least 6 characters long"));
     }
     hash(password, DEFAULT_COST)
-        .map_err(|e| format!("Failed to hash password: {}", e))
+        .map_err(|e| AppError::db(format!("Failed to hash password: {}", e)))
 }
 
-pub fn verify_password(password: &str, hash: &str) -> Result<bool, String> {
+pub fn verify_password(password: &str, hash: &str) -> AppResult<bool> {
+    // Ошибка bcrypt означает повреждённый хэш, а не неверный пароль
     verify(password, hash)
-        .map_err(|e| format!("Failed to verify password: {}", e))
+        .map_err(|e| AppError::db(format!("Failed to verify password: {}", e)))
 }
 
-pub fn generate_token(user_id: i64, role: &str) -> Result<String, String> {
+pub fn generate_token(user_id: i64, role: &str, db_path: &str) -> AppResult<String> {
     let expiration = chrono::Utc::now()
         .checked_add_signed(chrono::Duration::hours(JWT_EXPIRATION_HOURS as i64))
         .expect("valid timestamp")
@@ -34,25 +104,30 @@ pub fn generate_token(user_id: i64, role: &str) -> Result<String, String> {
     let claims = Claims {
         sub: user_id,
         role: role.to_string(),
+        db: database_id(db_path),
         exp: expiration,
     };
 
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
+        &EncodingKey::from_secret(token_secret()?),
     )
-    .map_err(|e| format!("Failed to generate token: {}", e))
+    .map_err(|e| AppError::db(format!("Failed to generate token: {}", e)))
 }
 
-pub fn verify_token(token: &str) -> Result<Claims, String> {
+/// Проверяет подпись и срок токена и то, что он выдан для БД db_path
+pub fn verify_token(token: &str, db_path: &str) -> AppResult<Claims> {
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
+        &DecodingKey::from_secret(token_secret()?),
         &Validation::default(),
     )
-    .map_err(|e| format!("Invalid token: {}", e))?;
+    .map_err(|e| AppError::unauthorized(format!("Invalid token: {}", e)))?;
 
+    if token_data.claims.db != database_id(db_path) {
+        return Err(AppError::unauthorized("Token was issued for another database"));
+    }
     Ok(token_data.claims)
 }
 
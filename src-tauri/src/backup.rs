@@ -0,0 +1,251 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, FromRow};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use crate::database::{Database, SCHEMA_VERSION};
+use crate::encryption;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+/// Как часто проверяется, не пора ли сделать автоматическую копию
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Таблицы, без которых файл не считается базой календаря
+const REQUIRED_TABLES: &[&str] = &["users", "control_cards"];
+
+/// Настройки автоматического резервного копирования, общие для всех рабочих мест
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub interval_hours: i64,
+    /// Сколько последних автоматических копий хранить
+    pub retention: i64,
+    /// Каталог для копий; по умолчанию backups рядом с файлом БД
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub last_backup_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+impl BackupInfo {
+    pub fn from_path(path: &Path) -> AppResult<Self> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| AppError::db(format!("Failed to read backup file: {}", e)))?;
+        let created_at = metadata
+            .modified()
+            .map(|time| chrono::DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+
+        Ok(Self {
+            path: path.to_string_lossy().to_string(),
+            size_bytes: metadata.len(),
+            created_at,
+        })
+    }
+}
+
+pub fn backup_directory(db_path: &str, settings: &BackupSettings) -> PathBuf {
+    match settings.directory.as_deref().map(str::trim) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => default_backup_directory(db_path),
+    }
+}
+
+pub fn default_backup_directory(db_path: &str) -> PathBuf {
+    Path::new(db_path)
+        .parent()
+        .map(|dir| dir.join("backups"))
+        .unwrap_or_else(|| PathBuf::from("backups"))
+}
+
+fn db_stem(db_path: &str) -> &str {
+    Path::new(db_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("calendar")
+}
+
+/// calendar.db -> calendar_auto_20250131_235959.db; по имени копии сортируются по времени
+fn auto_backup_prefix(db_path: &str) -> String {
+    format!("{}_auto_", db_stem(db_path))
+}
+
+pub fn auto_backup_file_name(db_path: &str) -> String {
+    format!("{}{}.db", auto_backup_prefix(db_path), Local::now().format("%Y%m%d_%H%M%S"))
+}
+
+/// Копия, которая снимается перед восстановлением, чтобы его можно было отменить
+pub fn pre_restore_file_name(db_path: &str) -> String {
+    format!("{}_before_restore_{}.db", db_stem(db_path), Local::now().format("%Y%m%d_%H%M%S"))
+}
+
+pub fn pre_repair_file_name(db_path: &str) -> String {
+    format!("{}_before_repair_{}.db", db_stem(db_path), Local::now().format("%Y%m%d_%H%M%S"))
+}
+
+/// Автоматические копии, от новых к старым
+pub fn list_auto_backups(dir: &Path, db_path: &str) -> AppResult<Vec<BackupInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = auto_backup_prefix(db_path);
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| AppError::db(format!("Failed to read backup directory: {}", e)))?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".db"))
+        })
+        .collect();
+    paths.sort();
+    paths.reverse();
+
+    paths.iter().map(|path| BackupInfo::from_path(path)).collect()
+}
+
+/// Удаляет автоматические копии сверх retention, начиная со старых
+pub fn prune_auto_backups(dir: &Path, db_path: &str, retention: i64) -> AppResult<usize> {
+    let backups = list_auto_backups(dir, db_path)?;
+    let mut removed = 0;
+
+    for backup in backups.iter().skip(retention.max(1) as usize) {
+        std::fs::remove_file(&backup.path)
+            .map_err(|e| AppError::db(format!("Failed to remove old backup: {}", e)))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Проверяет файл перед восстановлением: целостность, наличие таблиц и версию схемы.
+/// Зашифрованная копия проверяется с ключом key. Возвращает версию схемы копии.
+pub async fn validate_backup(path: &Path, key: Option<&str>) -> AppResult<i64> {
+    if !path.is_file() {
+        return Err(AppError::not_found(format!("Backup file '{}' not found", path.display())));
+    }
+
+    let options = encryption::with_key(SqliteConnectOptions::new().filename(path).read_only(true), key);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .map_err(|e| open_error(path, "Failed to open backup", e))?;
+
+    let result = check_backup(&pool, path).await;
+    pool.close().await;
+    result
+}
+
+/// Неверный ключ SQLite сообщает как «file is not a database»
+fn open_error(path: &Path, context: &str, error: sqlx::Error) -> AppError {
+    if encryption::is_wrong_key_error(&error) && encryption::is_encrypted_file(path) {
+        AppError::validation("src_path", "Backup is encrypted with a different key or is not a database")
+    } else {
+        AppError::from_sqlx(context, error)
+    }
+}
+
+async fn check_backup(pool: &SqlitePool, path: &Path) -> AppResult<i64> {
+    let problems: Vec<(String,)> = sqlx::query_as("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| open_error(path, "Backup is not a valid database", e))?;
+
+    if problems.first().map(|(row,)| row.as_str()) != Some("ok") {
+        let problems: Vec<String> = problems.into_iter().map(|(row,)| row).collect();
+        return Err(AppError::conflict_with("Backup failed integrity check", &problems));
+    }
+
+    for table in REQUIRED_TABLES {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        )
+        .bind(table)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to read backup schema", e))?;
+
+        if !exists {
+            return Err(AppError::validation("src_path", format!("Backup has no '{}' table", table)));
+        }
+    }
+
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to read backup schema version", e))?;
+
+    // Более старые схемы обновятся при подключении, более новые эта версия не поймёт
+    if version > SCHEMA_VERSION {
+        return Err(AppError::validation(
+            "src_path",
+            format!("Backup schema version {} is newer than supported {}", version, SCHEMA_VERSION),
+        ));
+    }
+
+    Ok(version)
+}
+
+/// Заменяет файл БД копией. Подключение к БД должно быть закрыто.
+pub fn replace_database_file(src: &Path, db_path: &Path) -> AppResult<()> {
+    // Копируем рядом с БД и переименовываем: файл не окажется записанным наполовину
+    let temp = db_path.with_extension("restore.tmp");
+    std::fs::copy(src, &temp)
+        .map_err(|e| AppError::db(format!("Failed to copy backup: {}", e)))?;
+
+    // Журнал старого файла не должен примениться к восстановленному
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let mut journal = db_path.as_os_str().to_owned();
+        journal.push(suffix);
+        let _ = std::fs::remove_file(PathBuf::from(journal));
+    }
+
+    std::fs::rename(&temp, db_path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        AppError::db(format!("Failed to replace database file: {}", e))
+    })
+}
+
+/// Делает автоматическую копию, если она включена и подошло время.
+/// Время отмечается в БД, поэтому из нескольких рабочих мест копию делает одно.
+pub async fn run_scheduled_backup(db: &Database) -> AppResult<Option<BackupInfo>> {
+    // Отметку о копии в БД только для чтения не записать; копии делают клиенты с правом записи
+    if db.is_read_only() {
+        return Ok(None);
+    }
+    let Some(settings) = db.claim_scheduled_backup().await? else {
+        return Ok(None);
+    };
+
+    let dir = backup_directory(db.get_path(), &settings);
+    let dest = dir.join(auto_backup_file_name(db.get_path()));
+    db.backup_to(&dest).await?;
+    prune_auto_backups(&dir, db.get_path(), settings.retention)?;
+
+    BackupInfo::from_path(&dest).map(Some)
+}
+
+pub fn spawn_auto_backup<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.db().await else {
+                continue;
+            };
+            if let Err(e) = run_scheduled_backup(&db).await {
+                eprintln!("Scheduled backup failed: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,683 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::database::{Database, NewControlCard};
+use crate::deadlines::{self, CardDates};
+use crate::error::{AppError, AppResult};
+use crate::models::User;
+
+/// Форматы дат по умолчанию: ISO и принятый в реестрах ДД.ММ.ГГГГ
+const DEFAULT_DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d.%m.%Y"];
+
+/// Сопоставление полей карточки колонкам CSV по заголовкам (без учёта регистра)
+#[derive(Debug, Deserialize, Clone)]
+pub struct CsvMapping {
+    pub card_number: Option<String>,
+    pub year: Option<String>,
+    /// Имя пользователя или ФИО исполнителя
+    pub executor: String,
+    pub reporter: String,
+    pub summary: String,
+    pub document_reference: String,
+    pub return_to: Option<String>,
+    pub execution_deadline: Option<String>,
+    pub execution_period_type: Option<String>,
+    pub extended_deadline: Option<String>,
+    pub resolution: Option<String>,
+    pub department: Option<String>,
+    pub controller: Option<String>,
+    /// Дата исполнения: карточки с ней импортируются исполненными
+    pub completed_at: Option<String>,
+    /// Форматы дат chrono в порядке проверки; по умолчанию YYYY-MM-DD и DD.MM.YYYY
+    #[serde(default)]
+    pub date_formats: Vec<String>,
+    /// Разделитель; по умолчанию определяется по строке заголовков (';' или ',')
+    pub delimiter: Option<char>,
+    /// Год карточек без колонки года; по умолчанию текущий
+    pub default_year: Option<i32>,
+    pub numbering_scheme_id: Option<i64>,
+    /// Сохранить строки без ошибок, даже если в других строках ошибки есть
+    #[serde(default)]
+    pub partial: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportRowError {
+    /// Номер строки в файле, заголовок — строка 1
+    pub row: u64,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    /// Строки, прошедшие все проверки
+    pub valid_rows: usize,
+    /// Были ли карточки сохранены
+    pub committed: bool,
+    pub card_ids: Vec<i64>,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Строка CSV, прошедшая разбор и проверки полей
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    pub row: u64,
+    pub card_number: Option<i32>,
+    pub year: i32,
+    pub executor: String,
+    pub executor_user_id: i64,
+    pub reporter: String,
+    pub summary: String,
+    pub document_reference: String,
+    pub return_to: Option<String>,
+    pub execution_deadline: Option<NaiveDate>,
+    pub execution_period_type: Option<String>,
+    pub extended_deadline: Option<NaiveDate>,
+    pub resolution: Option<String>,
+    pub department: Option<String>,
+    pub controller: Option<String>,
+    pub completed_at: Option<NaiveDate>,
+}
+
+impl ImportRow {
+    pub fn as_new_card(&self, user_id: i64, numbering_scheme_id: Option<i64>) -> NewControlCard<'_> {
+        NewControlCard {
+            card_number: self.card_number,
+            year: self.year,
+            executor: &self.executor,
+            reporter: &self.reporter,
+            summary: &self.summary,
+            document_reference: &self.document_reference,
+            user_id: Some(user_id),
+            executor_user_id: Some(self.executor_user_id),
+            return_to: self.return_to.as_deref(),
+            execution_deadline: self.execution_deadline,
+            execution_period_type: self.execution_period_type.as_deref(),
+            extended_deadline: self.extended_deadline,
+            resolution: self.resolution.as_deref(),
+            department: self.department.as_deref(),
+            controller: self.controller.as_deref(),
+            controller_user_id: None,
+            numbering_scheme_id,
+            completed_at: self.completed_at,
+        }
+    }
+}
+
+pub struct ParsedCsv {
+    pub total_rows: usize,
+    pub rows: Vec<ImportRow>,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Читает и проверяет файл. Ошибки отдельных строк собираются в errors,
+/// ошибка всего файла (нет файла, нет колонки) возвращается как Err.
+pub fn read_csv(path: &Path, mapping: &CsvMapping, users: &[User], today: NaiveDate) -> AppResult<ParsedCsv> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| AppError::not_found(format!("Failed to read '{}': {}", path.display(), e)))?;
+    let content = String::from_utf8(bytes)
+        .map_err(|_| AppError::validation("path", "CSV file must be saved in UTF-8"))?;
+    let content = content.trim_start_matches('\u{feff}');
+
+    let delimiter = mapping.delimiter.unwrap_or_else(|| detect_delimiter(content));
+    if !delimiter.is_ascii() {
+        return Err(AppError::validation("delimiter", "Delimiter must be an ASCII character"));
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::validation("path", format!("Failed to read CSV header: {}", e)))?
+        .clone();
+    let columns = Columns::resolve(&headers, mapping)?;
+    let formats: Vec<&str> = if mapping.date_formats.is_empty() {
+        DEFAULT_DATE_FORMATS.to_vec()
+    } else {
+        mapping.date_formats.iter().map(String::as_str).collect()
+    };
+    let context = RowContext { mapping, columns: &columns, formats: &formats, users, today };
+
+    let mut parsed = ParsedCsv { total_rows: 0, rows: Vec::new(), errors: Vec::new() };
+    for record in reader.records() {
+        parsed.total_rows += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let row = e.position().map(|p| p.line()).unwrap_or_default();
+                parsed.errors.push(ImportRowError { row, field: None, message: e.to_string() });
+                continue;
+            }
+        };
+
+        let row = record.position().map(|p| p.line()).unwrap_or_default();
+        match context.parse(row, &record) {
+            Ok(parsed_row) => parsed.rows.push(parsed_row),
+            Err(errors) => parsed.errors.extend(errors),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Импортирует файл в БД. Карточки сохраняются, только если все строки прошли проверки
+/// или импорт явно частичный; пробный импорт ничего не сохраняет.
+/// В БД только для чтения возможен лишь пробный импорт: номера проверяются запросами, без вставки.
+pub async fn import_file(
+    db: &Database,
+    path: &Path,
+    mapping: &CsvMapping,
+    user_id: i64,
+    dry_run: bool,
+    today: NaiveDate,
+) -> AppResult<ImportReport> {
+    let users = db.get_all_users().await?;
+    let parsed = read_csv(path, mapping, &users, today)?;
+
+    let commit = !dry_run && (mapping.partial || parsed.errors.is_empty());
+    let results: Vec<AppResult<Option<i64>>> = {
+        let cards: Vec<_> = parsed
+            .rows
+            .iter()
+            .map(|row| row.as_new_card(user_id, mapping.numbering_scheme_id))
+            .collect();
+        if db.is_read_only() {
+            if !dry_run {
+                return Err(AppError::ReadOnly);
+            }
+            db.check_import(&cards).await?.into_iter().map(|r| r.map(|()| None)).collect()
+        } else {
+            db.import_control_cards(&cards, commit, mapping.partial).await?.into_iter().map(|r| r.map(Some)).collect()
+        }
+    };
+
+    Ok(build_report(parsed, results, dry_run, commit, mapping.partial))
+}
+
+/// Сводит ошибки разбора и результаты вставки в отчёт. Ok(None) — строка проверена без вставки.
+/// commit — запрошено ли сохранение; оно выполняется, если нет ошибок или импорт частичный.
+pub fn build_report(parsed: ParsedCsv, results: Vec<AppResult<Option<i64>>>, dry_run: bool, commit: bool, partial: bool) -> ImportReport {
+    let mut errors = parsed.errors;
+    let mut valid_rows = 0;
+    let mut card_ids = Vec::new();
+    for (row, result) in parsed.rows.iter().zip(results) {
+        match result {
+            Ok(id) => {
+                valid_rows += 1;
+                card_ids.extend(id);
+            }
+            Err(e) => errors.push(row_error(row.row, e)),
+        }
+    }
+    errors.sort_by_key(|e| e.row);
+
+    let committed = commit && !card_ids.is_empty() && (partial || errors.is_empty());
+    ImportReport {
+        dry_run,
+        total_rows: parsed.total_rows,
+        valid_rows,
+        committed,
+        card_ids: if committed { card_ids } else { Vec::new() },
+        errors,
+    }
+}
+
+fn row_error(row: u64, error: AppError) -> ImportRowError {
+    let field = match &error {
+        AppError::Validation { field, .. } => Some(field.clone()),
+        _ => None,
+    };
+    ImportRowError { row, field, message: error.message().to_string() }
+}
+
+/// Разделитель, которого в строке заголовков больше: Excel с русской локалью сохраняет CSV через ';'
+fn detect_delimiter(content: &str) -> char {
+    let header = content.lines().next().unwrap_or_default();
+    if header.matches(';').count() > header.matches(',').count() {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Номера колонок для полей карточки
+struct Columns {
+    card_number: Option<usize>,
+    year: Option<usize>,
+    executor: usize,
+    reporter: usize,
+    summary: usize,
+    document_reference: usize,
+    return_to: Option<usize>,
+    execution_deadline: Option<usize>,
+    execution_period_type: Option<usize>,
+    extended_deadline: Option<usize>,
+    resolution: Option<usize>,
+    department: Option<usize>,
+    controller: Option<usize>,
+    completed_at: Option<usize>,
+}
+
+impl Columns {
+    fn resolve(headers: &csv::StringRecord, mapping: &CsvMapping) -> AppResult<Self> {
+        let find = |field: &str, header: &str| -> AppResult<usize> {
+            let wanted = normalize(header);
+            headers
+                .iter()
+                .position(|h| normalize(h) == wanted)
+                .ok_or_else(|| AppError::validation(field, format!("Column '{}' not found in CSV header", header)))
+        };
+        let find_optional = |field: &str, header: &Option<String>| -> AppResult<Option<usize>> {
+            match header.as_deref().map(str::trim) {
+                None | Some("") => Ok(None),
+                Some(header) => find(field, header).map(Some),
+            }
+        };
+
+        Ok(Self {
+            card_number: find_optional("card_number", &mapping.card_number)?,
+            year: find_optional("year", &mapping.year)?,
+            executor: find("executor", &mapping.executor)?,
+            reporter: find("reporter", &mapping.reporter)?,
+            summary: find("summary", &mapping.summary)?,
+            document_reference: find("document_reference", &mapping.document_reference)?,
+            return_to: find_optional("return_to", &mapping.return_to)?,
+            execution_deadline: find_optional("execution_deadline", &mapping.execution_deadline)?,
+            execution_period_type: find_optional("execution_period_type", &mapping.execution_period_type)?,
+            extended_deadline: find_optional("extended_deadline", &mapping.extended_deadline)?,
+            resolution: find_optional("resolution", &mapping.resolution)?,
+            department: find_optional("department", &mapping.department)?,
+            controller: find_optional("controller", &mapping.controller)?,
+            completed_at: find_optional("completed_at", &mapping.completed_at)?,
+        })
+    }
+}
+
+struct RowContext<'a> {
+    mapping: &'a CsvMapping,
+    columns: &'a Columns,
+    formats: &'a [&'a str],
+    users: &'a [User],
+    today: NaiveDate,
+}
+
+impl RowContext<'_> {
+    /// Проверяет все поля строки, чтобы сообщить обо всех ошибках сразу
+    fn parse(&self, row: u64, record: &csv::StringRecord) -> Result<ImportRow, Vec<ImportRowError>> {
+        let mut errors = Vec::new();
+        let mut check = |result: AppResult<()>| {
+            if let Err(e) = result {
+                errors.push(row_error(row, e));
+            }
+        };
+
+        let text = |column: usize| record.get(column).unwrap_or_default().to_string();
+        let optional = |column: Option<usize>| {
+            column.map(text).filter(|value| !value.is_empty())
+        };
+
+        let mut card_number = None;
+        if let Some(value) = optional(self.columns.card_number) {
+            check(parse_number("card_number", &value).map(|n| card_number = Some(n)));
+        }
+
+        let mut year = self.mapping.default_year.unwrap_or_else(|| self.today.year());
+        if let Some(value) = optional(self.columns.year) {
+            check(parse_number("year", &value).map(|y| year = y));
+        }
+
+        let mut execution_deadline = None;
+        if let Some(value) = optional(self.columns.execution_deadline) {
+            check(self.parse_date("execution_deadline", &value).map(|d| execution_deadline = Some(d)));
+        }
+        let mut extended_deadline = None;
+        if let Some(value) = optional(self.columns.extended_deadline) {
+            check(self.parse_date("extended_deadline", &value).map(|d| extended_deadline = Some(d)));
+        }
+        let mut completed_at = None;
+        if let Some(value) = optional(self.columns.completed_at) {
+            check(self.parse_date("completed_at", &value).map(|d| completed_at = Some(d)));
+        }
+
+        let mut executor = None;
+        check(match_executor(self.users, &text(self.columns.executor)).map(|user| executor = Some(user)));
+
+        // Те же проверки дат, что и при создании карточки вручную, кроме запрета сроков в прошлом:
+        // в существующем реестре есть просроченные и исполненные карточки
+        check(deadlines::validate_imported_card_dates(
+            &CardDates { year, execution_deadline, extended_deadline, completed_at },
+            self.today,
+        ));
+
+        match executor {
+            Some(executor) if errors.is_empty() => Ok(ImportRow {
+                row,
+                card_number,
+                year,
+                executor: executor.username.clone(),
+                executor_user_id: executor.id,
+                reporter: text(self.columns.reporter),
+                summary: text(self.columns.summary),
+                document_reference: text(self.columns.document_reference),
+                return_to: optional(self.columns.return_to),
+                execution_deadline,
+                execution_period_type: optional(self.columns.execution_period_type),
+                extended_deadline,
+                resolution: optional(self.columns.resolution),
+                department: optional(self.columns.department),
+                controller: optional(self.columns.controller),
+                completed_at,
+            }),
+            _ => Err(errors),
+        }
+    }
+
+    fn parse_date(&self, field: &str, value: &str) -> AppResult<NaiveDate> {
+        self.formats
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+            .ok_or_else(|| {
+                AppError::validation(field, format!("Invalid date '{}': expected {}", value, self.formats.join(" or ")))
+            })
+    }
+}
+
+fn parse_number(field: &str, value: &str) -> AppResult<i32> {
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| AppError::validation(field, format!("Invalid number '{}'", value)))
+}
+
+/// Исполнитель ищется по имени пользователя, затем по ФИО.
+/// Как и при создании вручную, исполнителем может быть только пользователь с ролью user.
+fn match_executor<'a>(users: &'a [User], value: &str) -> AppResult<&'a User> {
+    let wanted = normalize(value);
+    if wanted.is_empty() {
+        return Err(AppError::validation("executor", "Executor is required"));
+    }
+
+    let by_username = users.iter().find(|u| normalize(&u.username) == wanted);
+    let user = match by_username {
+        Some(user) => user,
+        None => {
+            let mut by_name = users
+                .iter()
+                .filter(|u| u.full_name.as_deref().is_some_and(|name| normalize(name) == wanted));
+            match (by_name.next(), by_name.next()) {
+                (Some(user), None) => user,
+                (Some(_), Some(_)) => {
+                    return Err(AppError::validation("executor", format!("Executor '{}' matches several users", value)));
+                }
+                (None, _) => {
+                    return Err(AppError::validation("executor", format!("Executor '{}' not found", value)));
+                }
+            }
+        }
+    };
+
+    if user.role != "user" {
+        return Err(AppError::validation("executor", "Executor must be a user with role 'user'"));
+    }
+    Ok(user)
+}
+
+/// Сравнение без учёта регистра, лишних пробелов и различия е/ё
+fn normalize(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .replace('ё', "е")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 2).unwrap()
+    }
+
+    fn user(id: i64, username: &str, full_name: Option<&str>, role: &str) -> User {
+        User {
+            id,
+            username: username.to_string(),
+            full_name: full_name.map(str::to_string),
+            email: None,
+            password_hash: String::new(),
+            role: role.to_string(),
+            created_at: String::new(),
+        }
+    }
+
+    fn mapping() -> CsvMapping {
+        CsvMapping {
+            card_number: Some("Номер".to_string()),
+            year: None,
+            executor: "Исполнитель".to_string(),
+            reporter: "Автор".to_string(),
+            summary: "Содержание".to_string(),
+            document_reference: "Документ".to_string(),
+            return_to: None,
+            execution_deadline: Some("Срок".to_string()),
+            execution_period_type: None,
+            extended_deadline: None,
+            resolution: None,
+            department: None,
+            controller: None,
+            completed_at: None,
+            date_formats: Vec::new(),
+            delimiter: None,
+            default_year: Some(2026),
+            numbering_scheme_id: None,
+            partial: false,
+        }
+    }
+
+    fn write_csv(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("csv-import-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn import_row(row: u64) -> ImportRow {
+        ImportRow {
+            row,
+            card_number: None,
+            year: 2026,
+            executor: "ivanov".to_string(),
+            executor_user_id: 1,
+            reporter: "r".to_string(),
+            summary: "s".to_string(),
+            document_reference: "d".to_string(),
+            return_to: None,
+            execution_deadline: None,
+            execution_period_type: None,
+            extended_deadline: None,
+            resolution: None,
+            department: None,
+            controller: None,
+            completed_at: None,
+        }
+    }
+
+    fn parsed(rows: u64, errors: &[u64]) -> ParsedCsv {
+        ParsedCsv {
+            total_rows: rows as usize + errors.len(),
+            rows: (2..rows + 2).map(import_row).collect(),
+            errors: errors
+                .iter()
+                .map(|&row| ImportRowError { row, field: None, message: "bad".to_string() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn read_csv_detects_delimiter_and_falls_back_between_date_formats() {
+        let users = [user(1, "ivanov", None, "user")];
+        let path = write_csv(
+            "semicolon",
+            "\u{feff}Номер;Исполнитель;Автор;Содержание;Документ;Срок\n\
+             1;ivanov;Руководитель;Ответ, срочно;Вх. 1;2026-03-10\n\
+             2;ivanov;Руководитель;Ответ;Вх. 2;10.03.2026\n\
+             3;ivanov;Руководитель;Ответ;Вх. 3;10/03/2026\n",
+        );
+
+        let parsed = read_csv(&path, &mapping(), &users, today()).unwrap();
+        assert_eq!(parsed.total_rows, 3);
+        let deadline = NaiveDate::from_ymd_opt(2026, 3, 10);
+        assert_eq!(parsed.rows.iter().map(|r| r.execution_deadline).collect::<Vec<_>>(), vec![deadline, deadline]);
+        assert_eq!(parsed.rows[0].summary, "Ответ, срочно");
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!((parsed.errors[0].row, parsed.errors[0].field.as_deref()), (4, Some("execution_deadline")));
+
+        // Заданные форматы заменяют форматы по умолчанию
+        let path = write_csv(
+            "comma",
+            "Номер,Исполнитель,Автор,Содержание,Документ,Срок\n\
+             1,ivanov,Руководитель,Ответ,Вх. 1,10/03/2026\n\
+             2,ivanov,Руководитель,Ответ,Вх. 2,2026-03-10\n",
+        );
+        let custom = CsvMapping { date_formats: vec!["%d/%m/%Y".to_string()], ..mapping() };
+        let parsed = read_csv(&path, &custom, &users, today()).unwrap();
+        assert_eq!(parsed.rows.len(), 1);
+        assert_eq!(parsed.rows[0].execution_deadline, deadline);
+        assert_eq!(parsed.errors[0].row, 3);
+
+        // Без обязательной колонки файл не читается целиком
+        let missing = CsvMapping { reporter: "Докладчик".to_string(), ..mapping() };
+        let error = read_csv(&path, &missing, &users, today()).err().unwrap();
+        assert_eq!(error.code(), "validation");
+    }
+
+    #[test]
+    fn match_executor_by_username_then_full_name() {
+        let users = [
+            user(1, "ivanov", Some("Иванов Иван"), "user"),
+            user(2, "fedorov", Some("Фёдоров Пётр"), "user"),
+            user(3, "petrov1", Some("Петров Пётр"), "user"),
+            user(4, "petrov2", Some("Петров Петр"), "user"),
+            user(5, "boss", Some("Начальник"), "admin"),
+        ];
+        let matched = |value: &str| match_executor(&users, value).map(|u| u.id);
+
+        assert_eq!(matched("IVANOV").unwrap(), 1);
+        assert_eq!(matched("  иванов   иван ").unwrap(), 1);
+        assert_eq!(matched("Федоров Петр").unwrap(), 2);
+        assert!(matched("Петров Петр").unwrap_err().message().contains("several users"));
+        assert!(matched("Сидоров").unwrap_err().message().contains("not found"));
+        assert!(matched("").unwrap_err().message().contains("required"));
+        assert!(matched("boss").unwrap_err().message().contains("role 'user'"));
+    }
+
+    #[test]
+    fn build_report_commits_only_clean_or_partial_imports() {
+        let conflict = || AppError::conflict("Card number is already used");
+
+        // Пробный импорт ничего не сохраняет, но считает прошедшие проверки строки
+        let report = build_report(parsed(2, &[]), vec![Ok(Some(1)), Ok(Some(2))], true, false, false);
+        assert!(!report.committed);
+        assert_eq!(report.valid_rows, 2);
+        assert!(report.card_ids.is_empty());
+
+        // Ошибка вставки в полном импорте отменяет всё
+        let report = build_report(parsed(2, &[]), vec![Ok(Some(1)), Err(conflict())], false, true, false);
+        assert!(!report.committed);
+        assert!(report.card_ids.is_empty());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].row, 3);
+
+        // Частичный импорт сохраняет строки без ошибок, ошибки отсортированы по строкам
+        let report = build_report(parsed(2, &[5]), vec![Err(conflict()), Ok(Some(7))], false, true, true);
+        assert!(report.committed);
+        assert_eq!(report.card_ids, vec![7]);
+        assert_eq!(report.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![2, 5]);
+
+        let report = build_report(parsed(2, &[]), vec![Ok(Some(1)), Ok(Some(2))], false, true, false);
+        assert!(report.committed);
+        assert_eq!(report.card_ids, vec![1, 2]);
+
+        // Проверка без вставки даёт строки без идентификаторов
+        let report = build_report(parsed(1, &[]), vec![Ok(None)], true, false, false);
+        assert_eq!((report.valid_rows, report.committed), (1, false));
+    }
+
+    async fn database(read_only: bool) -> (Database, i64) {
+        let db = Database::connect_in_memory(read_only).await.unwrap();
+        db.create_user("ivanov", Some("Иванов Иван"), None, "not-a-hash", "user").await.unwrap();
+        let admin = db.create_user("admin", None, None, "not-a-hash", "admin").await.unwrap();
+        (db, admin)
+    }
+
+    async fn card_count(db: &Database) -> usize {
+        db.get_all_control_cards().await.unwrap().len()
+    }
+
+    const HEADER: &str = "Номер;Исполнитель;Автор;Содержание;Документ;Срок\n";
+
+    #[tokio::test]
+    async fn import_is_all_or_nothing_unless_partial() {
+        let (db, admin) = database(false).await;
+        let valid = write_csv("valid", &format!("{}1;ivanov;Р;С;Д;2026-03-10\n2;Иванов Иван;Р;С;Д;\n", HEADER));
+        let invalid = write_csv("invalid", &format!("{}3;ivanov;Р;С;Д;\n4;nobody;Р;С;Д;\n", HEADER));
+        let repeated = write_csv("repeated", &format!("{}5;ivanov;Р;С;Д;\n5;ivanov;Р;С;Д;\n", HEADER));
+
+        let report = import_file(&db, &valid, &mapping(), admin, true, today()).await.unwrap();
+        assert_eq!((report.valid_rows, report.committed), (2, false));
+        assert_eq!(card_count(&db).await, 0);
+
+        // Ошибка разбора одной строки не даёт сохранить остальные
+        let report = import_file(&db, &invalid, &mapping(), admin, false, today()).await.unwrap();
+        assert!(!report.committed);
+        assert_eq!(report.errors[0].field.as_deref(), Some("executor"));
+        assert_eq!(card_count(&db).await, 0);
+
+        // Ошибка вставки откатывает уже вставленные строки
+        let report = import_file(&db, &repeated, &mapping(), admin, false, today()).await.unwrap();
+        assert!(!report.committed);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(card_count(&db).await, 0);
+
+        let partial = CsvMapping { partial: true, ..mapping() };
+        let report = import_file(&db, &invalid, &partial, admin, false, today()).await.unwrap();
+        assert!(report.committed);
+        assert_eq!(report.card_ids.len(), 1);
+        assert_eq!(card_count(&db).await, 1);
+
+        let report = import_file(&db, &valid, &mapping(), admin, false, today()).await.unwrap();
+        assert!(report.committed);
+        assert_eq!(card_count(&db).await, 3);
+    }
+
+    #[tokio::test]
+    async fn read_only_database_allows_only_dry_run() {
+        let (db, admin) = database(true).await;
+        db.create_control_card(Some(7), 2026, "ivanov", "r", "s", "d", None, None, None, None, None, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        let path = write_csv("read-only", &format!("{}7;ivanov;Р;С;Д;\n8;ivanov;Р;С;Д;\n8;ivanov;Р;С;Д;\n", HEADER));
+
+        let report = import_file(&db, &path, &mapping(), admin, true, today()).await.unwrap();
+        assert_eq!(report.valid_rows, 1);
+        assert!(!report.committed);
+        let messages: Vec<_> = report.errors.iter().map(|e| (e.row, e.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![(2, "Card number 7/2026 is already used"), (4, "Card number 8/2026 is repeated in the file")]
+        );
+        assert_eq!(card_count(&db).await, 1);
+
+        let error = import_file(&db, &path, &mapping(), admin, false, today()).await.unwrap_err();
+        assert_eq!(error.code(), "read_only");
+    }
+}
@@ -1,9 +1,19 @@
-use sqlx::{sqlite::{SqlitePool, SqliteConnectOptions}, FromRow};
+use sqlx::{ConnectOptions, Connection, sqlite::{Sqlite, SqlitePool, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqliteLockingMode, SqliteSynchronous}, FromRow, QueryBuilder};
 use serde::{Deserialize, Serialize};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashSet;
 use std::path::Path;
 use std::str::FromStr;
-use tokio::runtime::Runtime;
-use crate::models::User;
+use crate::archive::{self, ArchiveInfo};
+use crate::backup::BackupSettings;
+use crate::deadlines::{self, CardDates};
+use crate::encryption;
+use crate::error::{AppError, AppResult};
+use crate::models::{PatchValue, User};
+use crate::reminders::{self, SmtpSettings};
+use crate::numbering::{self, NumberContext, NumberingScheme, NumberingSchemeInput};
+use crate::storage::{self, ConnectionMode, StorageInfo};
+use crate::webhooks::{self, NewDelivery, Webhook, WebhookDelivery};
 
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
 pub struct ControlCard {
@@ -15,15 +25,15 @@ pub struct ControlCard {
     pub summary: String,
     pub document_reference: String,
     pub executor_user_id: Option<i64>,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     #[serde(default)]
     pub return_to: Option<String>,
     #[serde(default)]
-    pub execution_deadline: Option<String>,
+    pub execution_deadline: Option<NaiveDate>,
     #[serde(default)]
     pub execution_period_type: Option<String>,
     #[serde(default)]
-    pub extended_deadline: Option<String>,
+    pub extended_deadline: Option<NaiveDate>,
     #[serde(default)]
     pub resolution: Option<String>,
     #[serde(default)]
@@ -32,97 +42,694 @@ pub struct ControlCard {
     pub controller: Option<String>,
     #[serde(default)]
     pub controller_user_id: Option<i64>,
+    #[serde(default)]
+    pub formatted_number: Option<String>,
+    #[serde(default)]
+    pub numbering_scheme_id: Option<i64>,
+    /// Увеличивается при каждом изменении; обновление должно передать текущее значение
+    #[serde(default = "default_card_version")]
+    pub version: i64,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Отметка исполнителя о выполнении
+    #[serde(default)]
+    pub execution_report: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<NaiveDate>,
 }
 
-pub struct Database {
-    pool: Option<SqlitePool>,
-    path: Option<String>,
-    runtime: Option<Runtime>,
+impl ControlCard {
+    /// Текущее значение колонки в виде строки — для журнала изменений
+    fn history_value(&self, column: &str) -> Option<String> {
+        match column {
+            "card_number" => Some(self.card_number.to_string()),
+            "year" => Some(self.year.to_string()),
+            "executor" => Some(self.executor.clone()),
+            "reporter" => Some(self.reporter.clone()),
+            "summary" => Some(self.summary.clone()),
+            "document_reference" => Some(self.document_reference.clone()),
+            "executor_user_id" => self.executor_user_id.map(|v| v.to_string()),
+            "return_to" => self.return_to.clone(),
+            "execution_deadline" => self.execution_deadline.map(deadlines::format_date),
+            "execution_period_type" => self.execution_period_type.clone(),
+            "extended_deadline" => self.extended_deadline.map(deadlines::format_date),
+            "resolution" => self.resolution.clone(),
+            "department" => self.department.clone(),
+            "controller" => self.controller.clone(),
+            "controller_user_id" => self.controller_user_id.map(|v| v.to_string()),
+            "execution_report" => self.execution_report.clone(),
+            "completed_at" => self.completed_at.map(deadlines::format_date),
+            _ => None,
+        }
+    }
+
+    pub fn dates(&self) -> CardDates {
+        CardDates {
+            year: self.year,
+            execution_deadline: self.execution_deadline,
+            extended_deadline: self.extended_deadline,
+            completed_at: self.completed_at,
+        }
+    }
 }
 
-impl Database {
-    pub fn new() -> Self {
-        Self {
-            pool: None,
-            path: None,
-            runtime: None,
+/// Дата, которую не удалось разобрать при миграции; исходное значение сохранено здесь
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct DateMigrationIssue {
+    pub id: i64,
+    pub card_id: i64,
+    pub field: String,
+    pub raw_value: String,
+    pub detected_at: String,
+}
+
+/// Карточка в корзине
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct DeletedCard {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub card: ControlCard,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<i64>,
+    pub deleted_by_username: Option<String>,
+    pub delete_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct CardHistoryEntry {
+    pub id: i64,
+    pub card_id: i64,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub user_id: Option<i64>,
+    pub changed_at: String,
+}
+
+fn default_card_version() -> i64 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct CardTemplate {
+    pub id: i64,
+    pub name: String,
+    pub reporter: Option<String>,
+    pub department: Option<String>,
+    pub controller: Option<String>,
+    pub controller_user_id: Option<i64>,
+    pub execution_period_type: Option<String>,
+    pub deadline_offset_days: Option<i32>,
+    pub created_at: String,
+}
+
+/// Поля шаблона, которые задаёт пользователь при создании/редактировании
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CardTemplateInput {
+    pub name: String,
+    #[serde(default)]
+    pub reporter: Option<String>,
+    #[serde(default)]
+    pub department: Option<String>,
+    #[serde(default)]
+    pub controller: Option<String>,
+    #[serde(default)]
+    pub controller_user_id: Option<i64>,
+    #[serde(default)]
+    pub execution_period_type: Option<String>,
+    #[serde(default)]
+    pub deadline_offset_days: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct CardNumberReservation {
+    pub year: i32,
+    pub card_number: i32,
+    pub user_id: i64,
+    pub expires_at: String,
+}
+
+/// Рабочее место, подключённое к БД в многопользовательском режиме
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct ClientSession {
+    pub client_id: String,
+    pub hostname: String,
+    pub os_username: String,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub app_version: String,
+    pub connected_at: String,
+    pub last_seen: String,
+    /// Клиент присылал отметку недавно и, скорее всего, ещё работает
+    pub active: bool,
+}
+
+/// Параметры текущего подключения
+#[derive(Debug, Serialize, Clone)]
+pub struct ConnectionInfo {
+    pub path: String,
+    pub mode: ConnectionMode,
+    pub journal_mode: String,
+    pub storage: StorageInfo,
+    /// Идентификатор записи в client_sessions (у всех подключений с правом записи)
+    pub client_id: Option<String>,
+    /// Файл зашифрован (SQLCipher)
+    pub encrypted: bool,
+    /// Открыта только для чтения: схема не обновляется, изменения запрещены
+    pub read_only: bool,
+    pub warnings: Vec<String>,
+}
+
+/// Запись журнала изменений. Журнал заполняется триггерами, поэтому в нём видны
+/// изменения, сделанные любым клиентом, работающим с этим файлом.
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct ChangeLogEntry {
+    pub seq: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub action: String,
+    pub changed_at: String,
+}
+
+/// Версия схемы, записывается в PRAGMA user_version. Увеличивается при изменениях схемы,
+/// которые не понимают предыдущие версии приложения, и при новых колонках: БД только
+/// для чтения не мигрируется и открывается лишь с текущей версией.
+/// 2 — users.full_name
+/// 3 — webhooks, webhook_deliveries
+/// 4 — users.email, smtp_settings, sent_notifications
+pub const SCHEMA_VERSION: i64 = 4;
+
+/// Клиент без отметки дольше этого времени считается отключившимся
+pub const SESSION_TIMEOUT_SECONDS: i64 = 90;
+
+/// Сколько минут держится зарезервированный номер карточки
+const RESERVATION_TTL_MINUTES: i64 = 30;
+
+/// Следующий свободный номер за год с учётом действующих резервов.
+/// Карточки в корзине, окончательно удалённые и архивные тоже занимают свои номера.
+/// Вызывать внутри транзакции, открытой через BEGIN IMMEDIATE.
+async fn next_free_card_number(conn: &mut SqliteConnection, year: i32) -> Result<i32, sqlx::Error> {
+    let (next,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(MAX(card_number), 0) + 1
+        FROM (
+            SELECT card_number FROM control_cards WHERE year = ?1
+            UNION ALL
+            SELECT card_number FROM purged_card_numbers WHERE year = ?1
+            UNION ALL
+            SELECT card_number FROM card_number_reservations
+            WHERE year = ?1 AND expires_at > datetime('now')
+        )
+        "#,
+    )
+    .bind(year)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(next as i32)
+}
+
+async fn purge_expired_reservations(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM card_number_reservations WHERE expires_at <= datetime('now')")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Проверяет, что номер можно присвоить карточке: он не принадлежал удалённой или архивной
+/// карточке и не зарезервирован другим пользователем. Резерв самого пользователя снимается.
+async fn take_card_number(
+    conn: &mut SqliteConnection,
+    number: i32,
+    year: i32,
+    user_id: Option<i64>,
+) -> Result<AppResult<()>, sqlx::Error> {
+    let purged: Option<(i64,)> = sqlx::query_as(
+        "SELECT 1 FROM purged_card_numbers WHERE year = ?1 AND card_number = ?2",
+    )
+    .bind(year)
+    .bind(number)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if purged.is_some() {
+        return Ok(Err(AppError::conflict(format!(
+            "Card number {}/{} belonged to a deleted or archived card and cannot be reused",
+            number, year
+        ))));
+    }
+
+    let reserved_by: Option<(i64,)> = sqlx::query_as(
+        "SELECT user_id FROM card_number_reservations WHERE year = ?1 AND card_number = ?2",
+    )
+    .bind(year)
+    .bind(number)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match reserved_by {
+        Some((owner,)) if Some(owner) != user_id => Ok(Err(AppError::conflict(format!(
+            "Card number {}/{} is reserved by another user",
+            number, year
+        )))),
+        Some(_) => {
+            sqlx::query("DELETE FROM card_number_reservations WHERE year = ?1 AND card_number = ?2")
+                .bind(year)
+                .bind(number)
+                .execute(&mut *conn)
+                .await?;
+            Ok(Ok(()))
         }
+        None => Ok(Ok(())),
+    }
+}
+
+async fn find_numbering_scheme(conn: &mut SqliteConnection, scheme_id: i64) -> Result<Option<NumberingScheme>, sqlx::Error> {
+    sqlx::query_as::<_, NumberingScheme>(
+        r#"
+        SELECT id, name, template, prefix, department_code, reset_policy, created_at
+        FROM numbering_schemes
+        WHERE id = ?1
+        "#,
+    )
+    .bind(scheme_id)
+    .fetch_optional(&mut *conn)
+    .await
+}
+
+/// Следующий номер по схеме: значение берётся из счётчика схемы за период года.
+/// Совпадение с номером другой карточки той же схемы (например, после сброса счётчика)
+/// считается конфликтом. Вызывать внутри транзакции, открытой через BEGIN IMMEDIATE.
+async fn next_formatted_number(
+    conn: &mut SqliteConnection,
+    scheme: &NumberingScheme,
+    year: i32,
+    card_id: Option<i64>,
+) -> Result<AppResult<String>, sqlx::Error> {
+    let (sequence,): (i64,) = sqlx::query_as(
+        r#"
+        INSERT INTO numbering_counters (scheme_id, period, last_value)
+        VALUES (?1, ?2, 1)
+        ON CONFLICT(scheme_id, period) DO UPDATE SET last_value = last_value + 1
+        RETURNING last_value
+        "#,
+    )
+    .bind(scheme.id)
+    .bind(numbering::counter_period(&scheme.reset_policy, year))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let formatted = match numbering::format_number(
+        &scheme.template,
+        &NumberContext {
+            sequence,
+            year,
+            prefix: scheme.prefix.as_deref(),
+            department_code: scheme.department_code.as_deref(),
+        },
+    ) {
+        Ok(formatted) => formatted,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let taken: Option<(i64,)> = sqlx::query_as(
+        r#"
+        SELECT id FROM control_cards
+        WHERE numbering_scheme_id = ?1 AND formatted_number = ?2 AND id IS NOT ?3
+        LIMIT 1
+        "#,
+    )
+    .bind(scheme.id)
+    .bind(&formatted)
+    .bind(card_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if taken.is_some() {
+        return Ok(Err(AppError::conflict(format!("Number {} is already used by another card", formatted))));
     }
 
-    pub fn connect(&mut self, db_path: &str) -> Result<(), String> {
-        // Отключаемся от предыдущего подключения если есть
-        if self.pool.is_some() {
-            self.disconnect();
+    Ok(Ok(formatted))
+}
+
+/// Номер по схеме после смены года карточки: берётся следующее значение счётчика
+/// за новый период. При смене только номера, без схемы или если схему удалили,
+/// остаётся прежний номер по схеме.
+async fn renumber_formatted(
+    conn: &mut SqliteConnection,
+    card_id: i64,
+    scheme_id: Option<i64>,
+    formatted_number: Option<String>,
+    year_changed: Option<i32>,
+) -> Result<AppResult<Option<String>>, sqlx::Error> {
+    let (Some(scheme_id), Some(year)) = (scheme_id, year_changed) else {
+        return Ok(Ok(formatted_number));
+    };
+    let Some(scheme) = find_numbering_scheme(&mut *conn, scheme_id).await? else {
+        return Ok(Ok(formatted_number));
+    };
+
+    Ok(next_formatted_number(&mut *conn, &scheme, year, Some(card_id)).await?.map(Some))
+}
+
+/// Проверка новой карточки запросами на чтение, без вставки.
+/// seen — номера из предыдущих строк того же импорта.
+async fn check_new_card(
+    conn: &mut SqliteConnection,
+    card: &NewControlCard<'_>,
+    seen: &mut HashSet<(i32, i32)>,
+) -> Result<AppResult<()>, sqlx::Error> {
+    if let Some(number) = card.card_number {
+        if !seen.insert((card.year, number)) {
+            return Ok(Err(AppError::conflict(format!("Card number {}/{} is repeated in the file", number, card.year))));
         }
 
-        let path = Path::new(db_path);
-        
-        // Создаем директорию если нужно
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        let taken: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT 'card' FROM control_cards WHERE year = ?1 AND card_number = ?2
+            UNION ALL
+            SELECT 'purged' FROM purged_card_numbers WHERE year = ?1 AND card_number = ?2
+            UNION ALL
+            SELECT 'reserved' FROM card_number_reservations
+            WHERE year = ?1 AND card_number = ?2 AND expires_at > datetime('now') AND user_id IS NOT ?3
+            LIMIT 1
+            "#,
+        )
+        .bind(card.year)
+        .bind(number)
+        .bind(card.user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some((reason,)) = taken {
+            let message = match reason.as_str() {
+                "card" => "is already used",
+                "purged" => "belonged to a deleted or archived card and cannot be reused",
+                _ => "is reserved by another user",
+            };
+            return Ok(Err(AppError::conflict(format!("Card number {}/{} {}", number, card.year, message))));
         }
+    }
+
+    if let Some(scheme_id) = card.numbering_scheme_id {
+        if find_numbering_scheme(&mut *conn, scheme_id).await?.is_none() {
+            return Ok(Err(AppError::not_found("Numbering scheme not found")));
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Поля новой карточки
+#[derive(Debug, Clone)]
+pub struct NewControlCard<'a> {
+    pub card_number: Option<i32>,
+    pub year: i32,
+    pub executor: &'a str,
+    pub reporter: &'a str,
+    pub summary: &'a str,
+    pub document_reference: &'a str,
+    pub user_id: Option<i64>,
+    pub executor_user_id: Option<i64>,
+    pub return_to: Option<&'a str>,
+    pub execution_deadline: Option<NaiveDate>,
+    pub execution_period_type: Option<&'a str>,
+    pub extended_deadline: Option<NaiveDate>,
+    pub resolution: Option<&'a str>,
+    pub department: Option<&'a str>,
+    pub controller: Option<&'a str>,
+    pub controller_user_id: Option<i64>,
+    pub numbering_scheme_id: Option<i64>,
+    /// Дата исполнения, если карточка переносится из реестра уже исполненной
+    pub completed_at: Option<NaiveDate>,
+}
+
+/// Вставляет карточку. Если номер не передан, он выделяется из свободных.
+/// Переданный номер должен быть свободен или зарезервирован этим же пользователем.
+/// Вызывать внутри транзакции, открытой через BEGIN IMMEDIATE.
+async fn insert_control_card(conn: &mut SqliteConnection, card: &NewControlCard<'_>) -> Result<AppResult<i64>, sqlx::Error> {
+    let year = card.year;
+    let card_number = match card.card_number {
+        Some(number) => {
+            if let Err(e) = take_card_number(&mut *conn, number, year, card.user_id).await? {
+                return Ok(Err(e));
+            }
+            number
+        }
+        None => next_free_card_number(&mut *conn, card.year).await?,
+    };
+
+    // Номер по схеме нумерации берётся из счётчика схемы в той же транзакции
+    let formatted_number = match card.numbering_scheme_id {
+        Some(scheme_id) => {
+            let Some(scheme) = find_numbering_scheme(&mut *conn, scheme_id).await? else {
+                return Ok(Err(AppError::not_found("Numbering scheme not found")));
+            };
+
+            match next_formatted_number(&mut *conn, &scheme, year, None).await? {
+                Ok(formatted) => Some(formatted),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        None => None,
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO control_cards (card_number, year, executor, reporter, summary, document_reference, user_id, executor_user_id, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, completed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+        "#,
+    )
+    .bind(card_number)
+    .bind(year)
+    .bind(card.executor)
+    .bind(card.reporter)
+    .bind(card.summary)
+    .bind(card.document_reference)
+    .bind(card.user_id)
+    .bind(card.executor_user_id)
+    .bind(card.return_to)
+    .bind(card.execution_deadline)
+    .bind(card.execution_period_type)
+    .bind(card.extended_deadline)
+    .bind(card.resolution)
+    .bind(card.department)
+    .bind(card.controller)
+    .bind(card.controller_user_id)
+    .bind(&formatted_number)
+    .bind(card.numbering_scheme_id)
+    .bind(card.completed_at)
+    .execute(&mut *conn)
+    .await?;
+
+
+    Ok(Ok(result.last_insert_rowid()))
+}
+
+/// Подключение к БД. Клонирование дешёвое: пул соединений разделяется между копиями,
+/// поэтому команды могут работать с БД параллельно.
+#[derive(Clone)]
+pub struct Database {
+    pool: SqlitePool,
+    info: ConnectionInfo,
+    /// Ключ шифрования; нужен, чтобы открывать копии и архивы этой БД
+    key: Option<String>,
+}
 
-        // Создаем новый runtime
-        let runtime = Runtime::new()
-            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+impl Database {
+    pub async fn connect(db_path: &str, mode: ConnectionMode, key: Option<&str>, read_only: bool) -> AppResult<Self> {
+        let path = Path::new(db_path);
+
+        if read_only {
+            // Только для чтения открывается лишь существующая БД
+            if !path.is_file() {
+                return Err(AppError::not_found(format!("Database file '{}' not found", db_path)));
+            }
+        } else {
+            // Создаем директорию если нужно
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| AppError::db(format!("Failed to create directory: {}", e)))?;
+            }
 
-        // Проверяем, существует ли файл, и создаем пустой файл если нужно
-        let db_file_path = Path::new(db_path);
-        if !db_file_path.exists() {
-            std::fs::File::create(db_path)
-                .map_err(|e| format!("Failed to create database file: {}", e))?;
+            // Проверяем, существует ли файл, и создаем пустой файл если нужно
+            if !path.exists() {
+                std::fs::File::create(db_path)
+                    .map_err(|e| AppError::db(format!("Failed to create database file: {}", e)))?;
+            }
         }
         
         // Подключаемся к базе данных
-        let pool = runtime.block_on(async {
-            let normalized_path = db_path.replace('\\', "/");
-            
-            // Используем SqliteConnectOptions для настройки подключения
-            let options = SqliteConnectOptions::from_str(&format!("sqlite:///{}", normalized_path))
-                .map_err(|e| sqlx::Error::Configuration(format!("Invalid database path: {}", e).into()))?
-                .create_if_missing(true)
-                .busy_timeout(std::time::Duration::from_secs(5));
-            
-            SqlitePool::connect_with(options).await
-        })
-        .map_err(|e| format!("Failed to connect to database: {}", e))?;
+        let normalized_path = db_path.replace('\\', "/");
+        let storage = storage::detect_storage(db_path);
+        let mut warnings = Vec::new();
+
+        // Используем SqliteConnectOptions для настройки подключения
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:///{}", normalized_path))
+            .map_err(|e| AppError::validation("db_path", format!("Invalid database path: {}", e)))?
+            .create_if_missing(!read_only);
+        let options = encryption::with_key(options, key);
 
-        // Инициализируем схему
-        runtime.block_on(async {
-            self.init_schema(&pool).await
+        let options = match mode {
+            // mode=ro: режим журнала задаёт тот, кто пишет в файл, здесь его менять нельзя
+            _ if read_only => options
+                .read_only(true)
+                .locking_mode(SqliteLockingMode::Normal)
+                .busy_timeout(std::time::Duration::from_secs(15)),
+            ConnectionMode::Single => {
+                if storage.network {
+                    warnings.push("Database is located on a network share; enable multi-user mode if several workstations use it".to_string());
+                }
+                options.busy_timeout(std::time::Duration::from_secs(5))
+            }
+            // WAL требует общей памяти между процессами и не работает на сетевых дисках,
+            // там используется классический журнал с полной синхронизацией
+            ConnectionMode::MultiUser if storage.network => {
+                warnings.push("Database is located on a network share: WAL is unavailable, rollback journal is used. File locking over the network may be unreliable, keep regular backups".to_string());
+                options
+                    .locking_mode(SqliteLockingMode::Normal)
+                    .journal_mode(SqliteJournalMode::Delete)
+                    .synchronous(SqliteSynchronous::Full)
+                    .busy_timeout(std::time::Duration::from_secs(15))
+            }
+            ConnectionMode::MultiUser => options
+                .locking_mode(SqliteLockingMode::Normal)
+                .journal_mode(SqliteJournalMode::Wal)
+                .synchronous(SqliteSynchronous::Normal)
+                .busy_timeout(std::time::Duration::from_secs(15)),
+        };
+
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .map_err(|e| Self::connect_error(path, key, "Failed to connect to database", e))?;
+
+        // Инициализируем схему. Без права записи миграции невозможны,
+        // поэтому версия схемы должна совпадать с поддерживаемой.
+        if read_only {
+            Self::check_schema_version(&pool)
+                .await
+                .map_err(|e| Self::connect_error(path, key, "Failed to read schema version", e))??;
+        } else {
+            Self::init_schema(&pool)
+                .await
+                .map_err(|e| Self::connect_error(path, key, "Failed to initialize schema", e))?;
+        }
+
+        let (journal_mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| AppError::from_sqlx("Failed to read journal mode", e))?;
+
+        let mut db = Self {
+            pool,
+            info: ConnectionInfo {
+                path: db_path.to_string(),
+                mode,
+                journal_mode,
+                storage,
+                client_id: None,
+                encrypted: key.is_some(),
+                read_only,
+                warnings,
+            },
+            key: key.map(str::to_string),
+        };
+
+        // Клиент только для чтения не может записать себя в client_sessions;
+        // его видно только по блокировке файла во время чтения
+        if !read_only {
+            db.info.client_id = Some(new_client_id());
+            db.touch_client_session().await?;
+        }
+
+        Ok(db)
+    }
+
+    /// БД в памяти для тестов. read_only только помечает подключение, как при открытии с mode=ro.
+    #[cfg(test)]
+    pub async fn connect_in_memory(read_only: bool) -> AppResult<Self> {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .map_err(|e| AppError::db(format!("Invalid database path: {}", e)))?;
+        // У каждого соединения своя БД в памяти, поэтому соединение одно и не закрывается
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await
+            .map_err(|e| AppError::from_sqlx("Failed to connect to database", e))?;
+        Self::init_schema(&pool)
+            .await
+            .map_err(|e| AppError::from_sqlx("Failed to initialize schema", e))?;
+
+        Ok(Self {
+            pool,
+            info: ConnectionInfo {
+                path: ":memory:".to_string(),
+                mode: ConnectionMode::Single,
+                journal_mode: "memory".to_string(),
+                storage: StorageInfo { network: false, filesystem: None },
+                client_id: None,
+                encrypted: false,
+                read_only,
+                warnings: Vec::new(),
+            },
+            key: None,
         })
-        .map_err(|e| format!("Failed to initialize schema: {}", e))?;
+    }
 
-        self.pool = Some(pool);
-        self.path = Some(db_path.to_string());
-        self.runtime = Some(runtime);
-        
-        Ok(())
+    pub async fn close(&self) {
+        // Ошибка здесь не важна: запись всё равно устареет по таймауту
+        let _ = self.remove_client_session().await;
+        self.pool.close().await;
     }
 
-    pub fn disconnect(&mut self) {
-        if let Some(runtime) = &self.runtime {
-            if let Some(pool) = &self.pool {
-                runtime.block_on(async {
-                    pool.close().await;
-                });
-            }
+    /// Неверный ключ SQLite сообщает как «file is not a database»; поясняем, в чём дело
+    async fn check_schema_version(pool: &SqlitePool) -> Result<AppResult<()>, sqlx::Error> {
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version").fetch_one(pool).await?;
+        if version == SCHEMA_VERSION {
+            return Ok(Ok(()));
+        }
+
+        let message = if version > SCHEMA_VERSION {
+            format!("Database schema version {} is newer than supported {}", version, SCHEMA_VERSION)
+        } else {
+            format!(
+                "Database schema version {} is outdated (supported {}); open it once with write access to upgrade",
+                version, SCHEMA_VERSION
+            )
+        };
+        Ok(Err(AppError::validation("read_only", message)))
+    }
+
+    fn connect_error(path: &Path, key: Option<&str>, context: &str, error: sqlx::Error) -> AppError {
+        if !encryption::is_wrong_key_error(&error) {
+            return AppError::from_sqlx(context, error);
+        }
+
+        if encryption::is_encrypted_file(path) {
+            let message = if key.is_some() { "Wrong encryption key" } else { "Database is encrypted, a key is required" };
+            AppError::validation("key", message)
+        } else {
+            AppError::validation("key", "Database is not encrypted, connect without a key")
         }
-        self.pool = None;
-        self.path = None;
-        self.runtime = None;
     }
 
-    pub fn is_connected(&self) -> bool {
-        self.pool.is_some()
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.info.path
+    }
+
+    pub fn connection_info(&self) -> &ConnectionInfo {
+        &self.info
     }
 
-    pub fn get_path(&self) -> Option<&String> {
-        self.path.as_ref()
+    pub fn is_read_only(&self) -> bool {
+        self.info.read_only
     }
 
-    async fn init_schema(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    async fn init_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         sqlx::query("DROP TABLE IF EXISTS events")
             .execute(pool)
             .await?;
@@ -141,6 +748,14 @@ impl Database {
         .execute(pool)
         .await?;
 
+        // Миграция: ошибка означает, что колонка уже есть
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN full_name TEXT")
+            .execute(pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE users ADD COLUMN email TEXT")
+            .execute(pool)
+            .await;
+
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS control_cards (
@@ -163,499 +778,2439 @@ impl Database {
         .execute(pool)
         .await?;
 
-        // Проверяем, существует ли таблица control_cards перед миграциями
-        let table_exists: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='control_cards'"
-        )
-        .fetch_one(pool)
-        .await?;
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS card_number_reservations (
+                year INTEGER NOT NULL,
+                card_number INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                expires_at TEXT NOT NULL,
+                PRIMARY KEY (year, card_number),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Номера карточек, удалённых окончательно или перенесённых в архив, чтобы они не выдавались повторно
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS purged_card_numbers (
+                year INTEGER NOT NULL,
+                card_number INTEGER NOT NULL,
+                PRIMARY KEY (year, card_number)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS archives (
+                year INTEGER PRIMARY KEY,
+                file_name TEXT NOT NULL,
+                checksum TEXT,
+                card_count INTEGER NOT NULL,
+                carried_over INTEGER NOT NULL DEFAULT 0,
+                created_by INTEGER,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS backup_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                enabled INTEGER NOT NULL DEFAULT 0,
+                interval_hours INTEGER NOT NULL DEFAULT 24,
+                retention INTEGER NOT NULL DEFAULT 7,
+                directory TEXT,
+                last_backup_at TEXT
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query("INSERT OR IGNORE INTO backup_settings (id) VALUES (1)")
+            .execute(pool)
+            .await?;
+
+        // Почтовый сервер для напоминаний о сроках, общий для всех рабочих мест
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS smtp_settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                enabled INTEGER NOT NULL DEFAULT 0,
+                host TEXT NOT NULL DEFAULT '',
+                port INTEGER NOT NULL DEFAULT 587,
+                security TEXT NOT NULL DEFAULT 'starttls' CHECK (security IN ('none', 'starttls', 'tls')),
+                username TEXT,
+                password TEXT,
+                from_address TEXT NOT NULL DEFAULT '',
+                remind_days_before INTEGER NOT NULL DEFAULT 3
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query("INSERT OR IGNORE INTO smtp_settings (id) VALUES (1)")
+            .execute(pool)
+            .await?;
+
+        // Отправленные напоминания: одно письмо на карточку, адресата, вид и срок
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sent_notifications (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                card_id INTEGER NOT NULL,
+                recipient TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                deadline TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'sending' CHECK (status IN ('sending', 'sent', 'failed')),
+                attempts INTEGER NOT NULL DEFAULT 1,
+                last_error TEXT,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE (card_id, recipient, kind, deadline)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS numbering_schemes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                template TEXT NOT NULL,
+                prefix TEXT,
+                department_code TEXT,
+                reset_policy TEXT NOT NULL DEFAULT 'yearly' CHECK(reset_policy IN ('yearly', 'never')),
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Счётчики схем нумерации: period = год при ежегодном сбросе, 0 при сквозной нумерации
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS numbering_counters (
+                scheme_id INTEGER NOT NULL,
+                period INTEGER NOT NULL,
+                last_value INTEGER NOT NULL,
+                PRIMARY KEY (scheme_id, period),
+                FOREIGN KEY (scheme_id) REFERENCES numbering_schemes(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS card_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                card_id INTEGER NOT NULL,
+                field TEXT NOT NULL,
+                old_value TEXT,
+                new_value TEXT,
+                user_id INTEGER,
+                changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+                FOREIGN KEY (user_id) REFERENCES users(id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS card_templates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                reporter TEXT,
+                department TEXT,
+                controller TEXT,
+                controller_user_id INTEGER,
+                execution_period_type TEXT,
+                deadline_offset_days INTEGER,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                FOREIGN KEY (controller_user_id) REFERENCES users(id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Рабочие места, подключённые в многопользовательском режиме
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS client_sessions (
+                client_id TEXT PRIMARY KEY,
+                hostname TEXT NOT NULL,
+                os_username TEXT NOT NULL,
+                user_id INTEGER,
+                app_version TEXT NOT NULL,
+                connected_at TEXT NOT NULL DEFAULT (datetime('now')),
+                last_seen TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Исходящие вебхуки и очередь их доставки; события перечислены через запятую
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhooks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                url TEXT NOT NULL,
+                secret TEXT NOT NULL,
+                events TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                webhook_id INTEGER NOT NULL,
+                event TEXT NOT NULL,
+                card_id INTEGER NOT NULL,
+                event_key TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending'
+                    CHECK (status IN ('pending', 'sending', 'delivered', 'failed')),
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+                response_status INTEGER,
+                last_error TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                delivered_at TEXT,
+                UNIQUE (webhook_id, event_key)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at)",
+        )
+        .execute(pool)
+        .await?;
+
+        // Проверяем, существует ли таблица control_cards перед миграциями
+        let table_exists: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='control_cards'"
+        )
+        .fetch_one(pool)
+        .await?;
+
+        // Выполняем миграции только если таблица существует
+        if table_exists.0 > 0 {
+            // Вспомогательная функция для безопасного добавления колонки
+            // Просто пытаемся добавить и игнорируем ошибку, если колонка уже существует
+            async fn try_add_column(pool: &SqlitePool, column_name: &str, column_type: &str) {
+                let alter_query = format!(
+                    "ALTER TABLE control_cards ADD COLUMN {} {}",
+                    column_name, column_type
+                );
+                let _ = sqlx::query(&alter_query)
+                    .execute(pool)
+                    .await;
+            }
+
+            // Миграции: добавляем поля если их нет (игнорируем ошибки если уже существуют)
+            try_add_column(pool, "user_id", "INTEGER").await;
+            try_add_column(pool, "executor_user_id", "INTEGER").await;
+            try_add_column(pool, "return_to", "TEXT").await;
+            try_add_column(pool, "execution_deadline", "TEXT").await;
+            try_add_column(pool, "execution_period_type", "TEXT").await;
+            try_add_column(pool, "extended_deadline", "TEXT").await;
+            try_add_column(pool, "resolution", "TEXT").await;
+            try_add_column(pool, "department", "TEXT").await;
+            try_add_column(pool, "controller", "TEXT").await;
+            try_add_column(pool, "controller_user_id", "INTEGER").await;
+            try_add_column(pool, "formatted_number", "TEXT").await;
+            try_add_column(pool, "numbering_scheme_id", "INTEGER").await;
+            try_add_column(pool, "version", "INTEGER NOT NULL DEFAULT 1").await;
+            try_add_column(pool, "updated_at", "TEXT").await;
+            try_add_column(pool, "execution_report", "TEXT").await;
+            try_add_column(pool, "completed_at", "TEXT").await;
+            try_add_column(pool, "deleted_at", "TEXT").await;
+            try_add_column(pool, "deleted_by", "INTEGER").await;
+            try_add_column(pool, "delete_reason", "TEXT").await;
+
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_control_cards_formatted_number ON control_cards(formatted_number)")
+                .execute(pool)
+                .await?;
+
+            Self::normalize_card_dates(pool).await?;
+        }
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS change_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity TEXT NOT NULL,
+                entity_id INTEGER NOT NULL,
+                action TEXT NOT NULL CHECK(action IN ('created', 'updated', 'deleted')),
+                changed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Перенос в корзину и восстановление — тоже UPDATE, но для окон это удаление и появление карточки.
+        // Триггеры пересоздаются, чтобы в старых БД их определение обновилось;
+        // транзакция не даёт одновременно подключающимся клиентам помешать друг другу.
+        let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+        for (trigger, event, row, action) in [
+            ("trg_control_cards_created", "INSERT", "NEW", "'created'"),
+            (
+                "trg_control_cards_updated",
+                "UPDATE",
+                "NEW",
+                "CASE WHEN NEW.deleted_at IS NOT NULL AND OLD.deleted_at IS NULL THEN 'deleted' \
+                      WHEN NEW.deleted_at IS NULL AND OLD.deleted_at IS NOT NULL THEN 'created' \
+                      ELSE 'updated' END",
+            ),
+            ("trg_control_cards_deleted", "DELETE", "OLD", "'deleted'"),
+        ] {
+            sqlx::query(&format!("DROP TRIGGER IF EXISTS {trigger}"))
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(&format!(
+                r#"
+                CREATE TRIGGER {trigger} AFTER {event} ON control_cards
+                BEGIN
+                    INSERT INTO change_log (entity, entity_id, action) VALUES ('control_card', {row}.id, {action});
+                END
+                "#
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        // Клиенты читают журнал с момента подключения, старые записи не нужны
+        sqlx::query("DELETE FROM change_log WHERE changed_at < datetime('now', '-7 days')")
+            .execute(pool)
+            .await?;
+
+        // Журнал доставки вебхуков хранится месяц; ожидающие доставки не трогаем
+        sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE status IN ('delivered', 'failed') AND created_at < datetime('now', '-30 days')",
+        )
+        .execute(pool)
+        .await?;
+
+        // Напоминания по давно прошедшим срокам больше не понадобятся
+        sqlx::query("DELETE FROM sent_notifications WHERE deadline < date('now', '-60 days')")
+            .execute(pool)
+            .await?;
+
+        sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Приводит даты карточек к формату YYYY-MM-DD. Значения, которые не удалось разобрать,
+    /// переносятся в date_migration_issues и очищаются, чтобы карточка оставалась читаемой.
+    async fn normalize_card_dates(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS date_migration_issues (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                card_id INTEGER NOT NULL,
+                field TEXT NOT NULL,
+                raw_value TEXT NOT NULL,
+                detected_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        for column in ["execution_deadline", "extended_deadline", "completed_at"] {
+            let rows: Vec<(i64, String)> = sqlx::query_as(&format!(
+                "SELECT id, {column} FROM control_cards WHERE {column} IS NOT NULL"
+            ))
+            .fetch_all(pool)
+            .await?;
+
+            for (id, raw) in rows {
+                if NaiveDate::parse_from_str(&raw, deadlines::DATE_FORMAT).is_ok() {
+                    continue;
+                }
+
+                let normalized = if raw.trim().is_empty() {
+                    None
+                } else {
+                    match deadlines::parse_legacy_date(&raw) {
+                        Some(date) => Some(deadlines::format_date(date)),
+                        None => {
+                            sqlx::query(
+                                "INSERT INTO date_migration_issues (card_id, field, raw_value) VALUES (?1, ?2, ?3)",
+                            )
+                            .bind(id)
+                            .bind(column)
+                            .bind(&raw)
+                            .execute(pool)
+                            .await?;
+                            None
+                        }
+                    }
+                };
+
+                sqlx::query(&format!("UPDATE control_cards SET {column} = ?1 WHERE id = ?2"))
+                    .bind(normalized)
+                    .bind(id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Выполняет операцию, повторяя её с нарастающей задержкой, если БД занята другим клиентом.
+    /// Операция должна быть атомарной: при ошибке транзакция откатывается и повтор безопасен.
+    async fn execute_async<F, Fut, T>(&self, operation: F, error_msg: &str) -> AppResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(e) if storage::is_busy_error(&e) && attempt + 1 < storage::BUSY_RETRY_ATTEMPTS => {
+                    tokio::time::sleep(storage::busy_retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result.map_err(|e| AppError::from_sqlx(error_msg, e)),
+            }
+        }
+    }
+
+    /// Предварительный номер для отображения. Окончательный номер выделяется
+    /// при создании карточки или через reserve_card_number.
+    pub async fn get_next_card_number(&self, year: i32) -> AppResult<i32> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut conn = pool.acquire().await?;
+                next_free_card_number(&mut conn, year).await
+            },
+            "Failed to get next card number",
+        ).await
+    }
+
+    pub async fn reserve_card_number(&self, year: i32, user_id: i64) -> AppResult<CardNumberReservation> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                // BEGIN IMMEDIATE сразу берёт блокировку на запись, поэтому два клиента
+                // на общей БД не смогут выделить один и тот же номер
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+                purge_expired_reservations(&mut tx).await?;
+                let card_number = next_free_card_number(&mut tx, year).await?;
+
+                let reservation = sqlx::query_as::<_, CardNumberReservation>(
+                    r#"
+                    INSERT INTO card_number_reservations (year, card_number, user_id, expires_at)
+                    VALUES (?1, ?2, ?3, datetime('now', ?4))
+                    RETURNING year, card_number, user_id, expires_at
+                    "#,
+                )
+                .bind(year)
+                .bind(card_number)
+                .bind(user_id)
+                .bind(format!("+{} minutes", RESERVATION_TTL_MINUTES))
+                .fetch_one(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+                Ok(reservation)
+            },
+            "Failed to reserve card number",
+        ).await
+    }
+
+    pub async fn release_card_number(&self, year: i32, card_number: i32, user_id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    DELETE FROM card_number_reservations
+                    WHERE year = ?1 AND card_number = ?2 AND user_id = ?3
+                    "#,
+                )
+                .bind(year)
+                .bind(card_number)
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to release card number",
+        ).await
+    }
+
+    /// Создаёт карточку. Если номер не передан, он выделяется внутри той же транзакции.
+    /// Переданный номер должен быть свободен или зарезервирован этим же пользователем.
+    pub async fn create_control_card(
+        &self,
+        card_number: Option<i32>,
+        year: i32,
+        executor: &str,
+        reporter: &str,
+        summary: &str,
+        document_reference: &str,
+        user_id: Option<i64>,
+        executor_user_id: Option<i64>,
+        return_to: Option<&str>,
+        execution_deadline: Option<NaiveDate>,
+        execution_period_type: Option<&str>,
+        extended_deadline: Option<NaiveDate>,
+        resolution: Option<&str>,
+        department: Option<&str>,
+        controller: Option<&str>,
+        controller_user_id: Option<i64>,
+        numbering_scheme_id: Option<i64>,
+    ) -> AppResult<i64> {
+        let pool = &self.pool;
+        let card = &NewControlCard {
+            card_number,
+            year,
+            executor,
+            reporter,
+            summary,
+            document_reference,
+            user_id,
+            executor_user_id,
+            return_to,
+            execution_deadline,
+            execution_period_type,
+            extended_deadline,
+            resolution,
+            department,
+            controller,
+            controller_user_id,
+            numbering_scheme_id,
+            completed_at: None,
+        };
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+                purge_expired_reservations(&mut tx).await?;
+
+                let id = insert_control_card(&mut tx, card).await?;
+                if id.is_ok() {
+                    tx.commit().await?;
+                }
+                Ok(id)
+            },
+            "Failed to create control card",
+        ).await?
+    }
+
+    /// Импорт карточек одной транзакцией. Каждая карточка вставляется в своей точке
+    /// сохранения, поэтому ошибка в одной строке не затрагивает остальные.
+    /// Изменения сохраняются, только если commit и все строки прошли (или partial),
+    /// иначе откатываются: так выполняется пробный прогон.
+    pub async fn import_control_cards(
+        &self,
+        cards: &[NewControlCard<'_>],
+        commit: bool,
+        partial: bool,
+    ) -> AppResult<Vec<AppResult<i64>>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+                purge_expired_reservations(&mut tx).await?;
+
+                let mut results = Vec::with_capacity(cards.len());
+                for card in cards {
+                    let mut savepoint = tx.begin().await?;
+                    match insert_control_card(&mut savepoint, card).await {
+                        Ok(Ok(id)) => {
+                            savepoint.commit().await?;
+                            results.push(Ok(id));
+                        }
+                        Ok(Err(e)) => {
+                            savepoint.rollback().await?;
+                            results.push(Err(e));
+                        }
+                        Err(e) if storage::is_busy_error(&e) => return Err(e),
+                        Err(e) => {
+                            savepoint.rollback().await?;
+                            results.push(Err(AppError::from_sqlx("Failed to import control card", e)));
+                        }
+                    }
+                }
+
+                if commit && (partial || results.iter().all(Result::is_ok)) {
+                    tx.commit().await?;
+                } else {
+                    tx.rollback().await?;
+                }
+                Ok(results)
+            },
+            "Failed to import control cards",
+        ).await
+    }
+
+    /// Проверки импорта без записи — для пробного импорта в БД только для чтения.
+    /// Номер не должен быть занят карточкой, удалённой карточкой или чужим резервом
+    /// и не должен повторяться в файле; схема нумерации должна существовать.
+    pub async fn check_import(&self, cards: &[NewControlCard<'_>]) -> AppResult<Vec<AppResult<()>>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut conn = pool.acquire().await?;
+                let mut seen = HashSet::new();
+                let mut results = Vec::with_capacity(cards.len());
+                for card in cards {
+                    results.push(check_new_card(&mut conn, card, &mut seen).await?);
+                }
+                Ok(results)
+            },
+            "Failed to check control cards",
+        ).await
+    }
+
+    pub async fn get_control_card(&self, id: i64) -> AppResult<ControlCard> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(id)
+                .fetch_one(pool)
+                .await
+            },
+            "Failed to get control card",
+        ).await
+    }
+
+    pub async fn find_control_card(&self, id: i64) -> AppResult<Option<ControlCard>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(id)
+                .fetch_optional(pool)
+                .await
+            },
+            "Failed to get control card",
+        ).await
+    }
+
+    pub async fn get_all_control_cards(&self) -> AppResult<Vec<ControlCard>> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE deleted_at IS NULL
+                    ORDER BY year DESC, card_number DESC
+                    "#,
+                )
+                .fetch_all(pool)
+                .await;
+                result
+            },
+            "Failed to get control cards",
+        ).await
+    }
+
+    pub async fn get_control_cards_by_executor_id(&self, executor_user_id: i64) -> AppResult<Vec<ControlCard>> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE executor_user_id = ?1 AND deleted_at IS NULL
+                    ORDER BY year DESC, card_number DESC
+                    "#,
+                )
+                .bind(executor_user_id)
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get control cards by executor_user_id",
+        ).await
+    }
+
+    /// Поиск по отформатированному номеру (подстрока). executor_user_id ограничивает выборку
+    /// карточками конкретного исполнителя.
+    pub async fn search_control_cards_by_number(&self, query: &str, executor_user_id: Option<i64>) -> AppResult<Vec<ControlCard>> {
+        let pool = &self.pool;
+        let pattern = &format!("%{}%", query.trim());
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE (formatted_number LIKE ?1 OR CAST(card_number AS TEXT) || '/' || CAST(year AS TEXT) LIKE ?1)
+                      AND (?2 IS NULL OR executor_user_id = ?2)
+                      AND deleted_at IS NULL
+                    ORDER BY year DESC, card_number DESC
+                    "#,
+                )
+                .bind(pattern)
+                .bind(executor_user_id)
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to search control cards",
+        ).await
+    }
+
+
+    pub async fn update_control_card(
+        &self,
+        id: i64,
+        card_number: i32,
+        year: i32,
+        executor: &str,
+        reporter: &str,
+        summary: &str,
+        document_reference: &str,
+        user_id: Option<i64>,
+        executor_user_id: Option<i64>,
+        return_to: Option<&str>,
+        execution_deadline: Option<NaiveDate>,
+        execution_period_type: Option<&str>,
+        extended_deadline: Option<NaiveDate>,
+        resolution: Option<&str>,
+        department: Option<&str>,
+        controller: Option<&str>,
+        controller_user_id: Option<i64>,
+        expected_version: i64,
+    ) -> AppResult<usize> {
+        let pool = &self.pool;
+        
+        let updated = self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+                let current: Option<(i32, i32, Option<i64>, Option<String>)> = sqlx::query_as(
+                    r#"
+                    SELECT card_number, year, numbering_scheme_id, formatted_number
+                    FROM control_cards
+                    WHERE id = ?1 AND version = ?2 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(id)
+                .bind(expected_version)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                // Новый номер проверяется так же, как при создании карточки;
+                // со сменой года номер по схеме берётся из счётчика нового периода
+                let mut formatted_number = current.as_ref().and_then(|(_, _, _, formatted)| formatted.clone());
+                if let Some((current_number, current_year, scheme_id, current_formatted)) = current {
+                    if (current_number, current_year) != (card_number, year) {
+                        purge_expired_reservations(&mut tx).await?;
+                        if let Err(e) = take_card_number(&mut tx, card_number, year, user_id).await? {
+                            return Ok(Err(e));
+                        }
+                        let year_changed = (current_year != year).then_some(year);
+                        formatted_number = match renumber_formatted(&mut tx, id, scheme_id, current_formatted, year_changed).await? {
+                            Ok(formatted) => formatted,
+                            Err(e) => return Ok(Err(e)),
+                        };
+                    }
+                }
+
+                let result = sqlx::query(
+                    r#"
+                    UPDATE control_cards
+                    SET card_number = ?1, year = ?2, executor = ?3, reporter = ?4, summary = ?5, document_reference = ?6, user_id = ?7, executor_user_id = ?8, return_to = ?9, execution_deadline = ?10, execution_period_type = ?11, extended_deadline = ?12, resolution = ?13, department = ?14, controller = ?15, controller_user_id = ?16,
+                        formatted_number = ?19, version = version + 1, updated_at = datetime('now')
+                    WHERE id = ?17 AND version = ?18 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(card_number)
+                .bind(year)
+                .bind(executor)
+                .bind(reporter)
+                .bind(summary)
+                .bind(document_reference)
+                .bind(user_id)
+                .bind(executor_user_id)
+                .bind(return_to)
+                .bind(execution_deadline)
+                .bind(execution_period_type)
+                .bind(extended_deadline)
+                .bind(resolution)
+                .bind(department)
+                .bind(controller)
+                .bind(controller_user_id)
+                .bind(id)
+                .bind(expected_version)
+                .bind(formatted_number)
+                .execute(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+                Ok(Ok(result.rows_affected() as usize))
+            },
+            "Failed to update control card",
+        ).await??;
+
+        if updated > 0 {
+            return Ok(updated);
+        }
+
+        // Ни одна строка не обновлена: карточки нет или её уже изменил кто-то другой
+        let current = self.find_control_card(id).await?
+            .ok_or_else(|| AppError::not_found("Control card not found"))?;
+
+        Err(AppError::conflict_with(
+            format!(
+                "Control card was modified by another user (version {}, expected {})",
+                current.version, expected_version
+            ),
+            &current,
+        ))
+    }
+
+    /// Обновляет только переданные колонки и записывает в историю фактически изменённые поля.
+    /// Имена колонок берутся из ControlCardPatch::changes, а не из пользовательского ввода.
+    /// Возвращает количество изменённых полей.
+    pub async fn patch_control_card(
+        &self,
+        id: i64,
+        changes: &[(&'static str, PatchValue)],
+        expected_version: i64,
+        user_id: i64,
+    ) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+                let current = sqlx::query_as::<_, ControlCard>(
+                    r#"
+                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id, formatted_number, numbering_scheme_id, version, updated_at, execution_report, completed_at
+                    FROM control_cards
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let Some(current) = current else {
+                    return Ok(Err(AppError::not_found("Control card not found")));
+                };
+
+                if current.version != expected_version {
+                    return Ok(Err(AppError::conflict_with(
+                        format!(
+                            "Control card was modified by another user (version {}, expected {})",
+                            current.version, expected_version
+                        ),
+                        &current,
+                    )));
+                }
+
+                // Поля, значение которых совпадает с текущим, не считаются изменёнными
+                let changed: Vec<_> = changes
+                    .iter()
+                    .filter(|(column, value)| current.history_value(column) != value.to_history_value())
+                    .collect();
+
+                if changed.is_empty() {
+                    return Ok(Ok(0));
+                }
+
+                let changed_integer = |name: &str| {
+                    changed.iter().find_map(|(column, value)| match value {
+                        PatchValue::Integer(Some(v)) if *column == name => i32::try_from(*v).ok(),
+                        _ => None,
+                    })
+                };
+                let card_number = changed_integer("card_number").unwrap_or(current.card_number);
+                let year = changed_integer("year").unwrap_or(current.year);
+                let renumbered = (card_number, year) != (current.card_number, current.year);
+                let mut formatted_number = current.formatted_number.clone();
+                if renumbered {
+                    purge_expired_reservations(&mut tx).await?;
+                    if let Err(e) = take_card_number(&mut tx, card_number, year, Some(user_id)).await? {
+                        return Ok(Err(e));
+                    }
+                    let year_changed = (current.year != year).then_some(year);
+                    formatted_number = match renumber_formatted(&mut tx, id, current.numbering_scheme_id, formatted_number, year_changed).await? {
+                        Ok(formatted) => formatted,
+                        Err(e) => return Ok(Err(e)),
+                    };
+                }
+
+                let mut query = QueryBuilder::<Sqlite>::new("UPDATE control_cards SET ");
+                for (column, value) in &changed {
+                    query.push(*column).push(" = ");
+                    match value {
+                        PatchValue::Text(v) => query.push_bind(v.clone()),
+                        PatchValue::Integer(v) => query.push_bind(*v),
+                        PatchValue::Date(v) => query.push_bind(*v),
+                    };
+                    query.push(", ");
+                }
+                if renumbered {
+                    query.push("formatted_number = ").push_bind(formatted_number).push(", ");
+                }
+                query.push("version = version + 1, updated_at = datetime('now') WHERE id = ");
+                query.push_bind(id);
+                query.build().execute(&mut *tx).await?;
+
+                for (column, value) in &changed {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO card_history (card_id, field, old_value, new_value, user_id)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                        "#,
+                    )
+                    .bind(id)
+                    .bind(*column)
+                    .bind(current.history_value(column))
+                    .bind(value.to_history_value())
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+                Ok(Ok(changed.len()))
+            },
+            "Failed to patch control card",
+        ).await?
+    }
+
+    pub async fn get_date_migration_issues(&self) -> AppResult<Vec<DateMigrationIssue>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, DateMigrationIssue>(
+                    r#"
+                    SELECT id, card_id, field, raw_value, detected_at
+                    FROM date_migration_issues
+                    ORDER BY id
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get date migration issues",
+        ).await
+    }
+
+    pub async fn get_card_history(&self, card_id: i64) -> AppResult<Vec<CardHistoryEntry>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, CardHistoryEntry>(
+                    r#"
+                    SELECT id, card_id, field, old_value, new_value, user_id, changed_at
+                    FROM card_history
+                    WHERE card_id = ?1
+                    ORDER BY id DESC
+                    "#,
+                )
+                .bind(card_id)
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get card history",
+        ).await
+    }
+
+    /// Переносит карточку в корзину. Номер остаётся занятым, карточку можно восстановить.
+    pub async fn delete_control_card(&self, id: i64, user_id: i64, reason: Option<&str>) -> AppResult<usize> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+                let result = sqlx::query(
+                    r#"
+                    UPDATE control_cards
+                    SET deleted_at = datetime('now'), deleted_by = ?2, delete_reason = ?3,
+                        version = version + 1, updated_at = datetime('now')
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(id)
+                .bind(user_id)
+                .bind(reason)
+                .execute(&mut *tx)
+                .await?;
+
+                if result.rows_affected() > 0 {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO card_history (card_id, field, old_value, new_value, user_id)
+                        SELECT id, 'deleted_at', NULL, deleted_at, ?2 FROM control_cards WHERE id = ?1
+                        "#,
+                    )
+                    .bind(id)
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+
+                tx.commit().await?;
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to delete control card",
+        ).await
+    }
+
+    pub async fn get_deleted_control_cards(&self) -> AppResult<Vec<DeletedCard>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, DeletedCard>(
+                    r#"
+                    SELECT c.id, c.card_number, c.year, c.executor, c.reporter, c.summary, c.document_reference, c.executor_user_id, c.created_at, c.return_to, c.execution_deadline, c.execution_period_type, c.extended_deadline, c.resolution, c.department, c.controller, c.controller_user_id, c.formatted_number, c.numbering_scheme_id, c.version, c.updated_at, c.execution_report, c.completed_at,
+                           c.deleted_at, c.deleted_by, u.username AS deleted_by_username, c.delete_reason
+                    FROM control_cards c
+                    LEFT JOIN users u ON u.id = c.deleted_by
+                    WHERE c.deleted_at IS NOT NULL
+                    ORDER BY c.deleted_at DESC
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get deleted control cards",
+        ).await
+    }
+
+    pub async fn restore_control_card(&self, id: i64, user_id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO card_history (card_id, field, old_value, new_value, user_id)
+                    SELECT id, 'deleted_at', deleted_at, NULL, ?2 FROM control_cards
+                    WHERE id = ?1 AND deleted_at IS NOT NULL
+                    "#,
+                )
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+                let result = sqlx::query(
+                    r#"
+                    UPDATE control_cards
+                    SET deleted_at = NULL, deleted_by = NULL, delete_reason = NULL,
+                        version = version + 1, updated_at = datetime('now')
+                    WHERE id = ?1 AND deleted_at IS NOT NULL
+                    "#,
+                )
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to restore control card",
+        ).await
+    }
+
+    /// Окончательно удаляет карточки, попавшие в корзину раньше указанной даты.
+    /// Их номера запоминаются в purged_card_numbers и повторно не выдаются.
+    pub async fn purge_deleted_cards(&self, older_than: NaiveDate) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+
+                sqlx::query(
+                    r#"
+                    INSERT OR IGNORE INTO purged_card_numbers (year, card_number)
+                    SELECT year, card_number FROM control_cards
+                    WHERE deleted_at IS NOT NULL AND deleted_at < ?1
+                    "#,
+                )
+                .bind(older_than)
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    DELETE FROM card_history
+                    WHERE card_id IN (SELECT id FROM control_cards WHERE deleted_at IS NOT NULL AND deleted_at < ?1)
+                    "#,
+                )
+                .bind(older_than)
+                .execute(&mut *tx)
+                .await?;
+
+                let result = sqlx::query("DELETE FROM control_cards WHERE deleted_at IS NOT NULL AND deleted_at < ?1")
+                    .bind(older_than)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to purge deleted control cards",
+        ).await
+    }
+
+    pub async fn create_user(
+        &self,
+        username: &str,
+        full_name: Option<&str>,
+        email: Option<&str>,
+        password_hash: &str,
+        role: &str,
+    ) -> AppResult<i64> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO users (username, full_name, email, password_hash, role)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                )
+                .bind(username)
+                .bind(full_name)
+                .bind(email)
+                .bind(password_hash)
+                .bind(role)
+                .execute(pool)
+                .await?;
+
+                Ok(result.last_insert_rowid())
+            },
+            "Failed to create user",
+        ).await
+    }
+
+    pub async fn get_user_by_username(&self, username: &str) -> AppResult<Option<User>> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, User>(
+                    r#"
+                    SELECT id, username, full_name, email, password_hash, role, created_at
+                    FROM users
+                    WHERE username = ?1
+                    "#,
+                )
+                .bind(username)
+                .fetch_optional(pool)
+                .await
+            },
+            "Failed to get user by username",
+        ).await
+    }
+
+    pub async fn get_user_by_id(&self, id: i64) -> AppResult<Option<User>> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query_as::<_, User>(
+                    r#"
+                    SELECT id, username, full_name, email, password_hash, role, created_at
+                    FROM users
+                    WHERE id = ?1
+                    "#,
+                )
+                .bind(id)
+                .fetch_optional(pool)
+                .await;
+                result
+            },
+            "Failed to get user by id",
+        ).await
+    }
+
+    pub async fn has_any_users(&self) -> AppResult<bool> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result: (i64,) = sqlx::query_as(
+                    r#"
+                    SELECT COUNT(*) as count
+                    FROM users
+                    "#,
+                )
+                .fetch_one(pool)
+                .await?;
+
+                Ok(result.0 > 0)
+            },
+            "Failed to check if users exist",
+        ).await
+    }
+
+    pub async fn get_all_users(&self) -> AppResult<Vec<User>> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, User>(
+                    r#"
+                    SELECT id, username, full_name, email, password_hash, role, created_at
+                    FROM users
+                    ORDER BY created_at DESC
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get all users",
+        ).await
+    }
+
+    pub async fn update_user(
+        &self,
+        id: i64,
+        username: &str,
+        full_name: Option<&str>,
+        email: Option<&str>,
+        role: &str,
+    ) -> AppResult<usize> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    UPDATE users
+                    SET username = ?1, full_name = ?2, email = ?3, role = ?4
+                    WHERE id = ?5
+                    "#,
+                )
+                .bind(username)
+                .bind(full_name)
+                .bind(email)
+                .bind(role)
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to update user",
+        ).await
+    }
+
+    pub async fn update_user_password(
+        &self,
+        id: i64,
+        password_hash: &str,
+    ) -> AppResult<usize> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    UPDATE users
+                    SET password_hash = ?1
+                    WHERE id = ?2
+                    "#,
+                )
+                .bind(password_hash)
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to update user password",
+        ).await
+    }
+
+    pub async fn delete_user(&self, id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+        
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    DELETE FROM users
+                    WHERE id = ?1
+                    "#,
+                )
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to delete user",
+        ).await
+    }
+
+    pub async fn create_card_template(&self, template: &CardTemplateInput) -> AppResult<i64> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO card_templates (name, reporter, department, controller, controller_user_id, execution_period_type, deadline_offset_days)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    "#,
+                )
+                .bind(&template.name)
+                .bind(&template.reporter)
+                .bind(&template.department)
+                .bind(&template.controller)
+                .bind(template.controller_user_id)
+                .bind(&template.execution_period_type)
+                .bind(template.deadline_offset_days)
+                .execute(pool)
+                .await?;
+
+                Ok(result.last_insert_rowid())
+            },
+            "Failed to create card template",
+        ).await
+    }
+
+    pub async fn get_card_template(&self, id: i64) -> AppResult<Option<CardTemplate>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, CardTemplate>(
+                    r#"
+                    SELECT id, name, reporter, department, controller, controller_user_id, execution_period_type, deadline_offset_days, created_at
+                    FROM card_templates
+                    WHERE id = ?1
+                    "#,
+                )
+                .bind(id)
+                .fetch_optional(pool)
+                .await
+            },
+            "Failed to get card template",
+        ).await
+    }
+
+    pub async fn get_all_card_templates(&self) -> AppResult<Vec<CardTemplate>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, CardTemplate>(
+                    r#"
+                    SELECT id, name, reporter, department, controller, controller_user_id, execution_period_type, deadline_offset_days, created_at
+                    FROM card_templates
+                    ORDER BY name
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get card templates",
+        ).await
+    }
+
+    pub async fn update_card_template(&self, id: i64, template: &CardTemplateInput) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    UPDATE card_templates
+                    SET name = ?1, reporter = ?2, department = ?3, controller = ?4, controller_user_id = ?5, execution_period_type = ?6, deadline_offset_days = ?7
+                    WHERE id = ?8
+                    "#,
+                )
+                .bind(&template.name)
+                .bind(&template.reporter)
+                .bind(&template.department)
+                .bind(&template.controller)
+                .bind(template.controller_user_id)
+                .bind(&template.execution_period_type)
+                .bind(template.deadline_offset_days)
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to update card template",
+        ).await
+    }
+
+    pub async fn delete_card_template(&self, id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    DELETE FROM card_templates
+                    WHERE id = ?1
+                    "#,
+                )
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to delete card template",
+        ).await
+    }
+
+    pub async fn get_all_numbering_schemes(&self) -> AppResult<Vec<NumberingScheme>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, NumberingScheme>(
+                    r#"
+                    SELECT id, name, template, prefix, department_code, reset_policy, created_at
+                    FROM numbering_schemes
+                    ORDER BY name
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get numbering schemes",
+        ).await
+    }
+
+    pub async fn create_numbering_scheme(&self, scheme: &NumberingSchemeInput) -> AppResult<i64> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO numbering_schemes (name, template, prefix, department_code, reset_policy)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                )
+                .bind(&scheme.name)
+                .bind(&scheme.template)
+                .bind(&scheme.prefix)
+                .bind(&scheme.department_code)
+                .bind(&scheme.reset_policy)
+                .execute(pool)
+                .await?;
+
+                Ok(result.last_insert_rowid())
+            },
+            "Failed to create numbering scheme",
+        ).await
+    }
+
+    pub async fn update_numbering_scheme(&self, id: i64, scheme: &NumberingSchemeInput) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let result = sqlx::query(
+                    r#"
+                    UPDATE numbering_schemes
+                    SET name = ?1, template = ?2, prefix = ?3, department_code = ?4, reset_policy = ?5
+                    WHERE id = ?6
+                    "#,
+                )
+                .bind(&scheme.name)
+                .bind(&scheme.template)
+                .bind(&scheme.prefix)
+                .bind(&scheme.department_code)
+                .bind(&scheme.reset_policy)
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to update numbering scheme",
+        ).await
+    }
+
+    /// Удаляет схему вместе с её счётчиками. Уже выданные номера на карточках сохраняются.
+    pub async fn delete_numbering_scheme(&self, id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin().await?;
+
+                sqlx::query("DELETE FROM numbering_counters WHERE scheme_id = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                let result = sqlx::query("DELETE FROM numbering_schemes WHERE id = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to delete numbering scheme",
+        ).await
+    }
+
+    /// Отметка «клиент на связи». Если запись успели удалить как устаревшую, она создаётся заново.
+    pub async fn touch_client_session(&self) -> AppResult<()> {
+        let Some(client_id) = self.info.client_id.as_deref() else {
+            return Ok(());
+        };
+        let pool = &self.pool;
+        let hostname = whoami::fallible::hostname().unwrap_or_else(|_| whoami::devicename());
+        let os_username = whoami::username();
+        let hostname = hostname.as_str();
+        let os_username = os_username.as_str();
+
+        self.execute_async(
+            || async move {
+                sqlx::query(
+                    r#"
+                    INSERT INTO client_sessions (client_id, hostname, os_username, app_version)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT(client_id) DO UPDATE SET last_seen = datetime('now')
+                    "#,
+                )
+                .bind(client_id)
+                .bind(hostname)
+                .bind(os_username)
+                .bind(env!("CARGO_PKG_VERSION"))
+                .execute(pool)
+                .await?;
+
+                // Заодно убираем записи клиентов, которые не закрылись штатно
+                sqlx::query("DELETE FROM client_sessions WHERE last_seen < datetime('now', '-1 day')")
+                    .execute(pool)
+                    .await?;
+
+                Ok(())
+            },
+            "Failed to update client session",
+        ).await
+    }
+
+    /// Привязывает текущую сессию к вошедшему пользователю
+    pub async fn set_session_user(&self, user_id: Option<i64>) -> AppResult<()> {
+        let Some(client_id) = self.info.client_id.as_deref() else {
+            return Ok(());
+        };
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query("UPDATE client_sessions SET user_id = ?1 WHERE client_id = ?2")
+                    .bind(user_id)
+                    .bind(client_id)
+                    .execute(pool)
+                    .await?;
+                Ok(())
+            },
+            "Failed to update client session",
+        ).await
+    }
+
+    pub async fn remove_client_session(&self) -> AppResult<()> {
+        let Some(client_id) = self.info.client_id.as_deref() else {
+            return Ok(());
+        };
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query("DELETE FROM client_sessions WHERE client_id = ?1")
+                    .bind(client_id)
+                    .execute(pool)
+                    .await?;
+                Ok(())
+            },
+            "Failed to remove client session",
+        ).await
+    }
+
+    pub async fn latest_change_seq(&self) -> AppResult<i64> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let (seq,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(seq), 0) FROM change_log")
+                    .fetch_one(pool)
+                    .await?;
+                Ok(seq)
+            },
+            "Failed to read change log",
+        ).await
+    }
+
+    pub async fn get_changes_since(&self, seq: i64) -> AppResult<Vec<ChangeLogEntry>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ChangeLogEntry>(
+                    r#"
+                    SELECT seq, entity, entity_id, action, changed_at
+                    FROM change_log
+                    WHERE seq > ?1
+                    ORDER BY seq
+                    "#,
+                )
+                .bind(seq)
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to read change log",
+        ).await
+    }
+
+    pub async fn get_client_sessions(&self) -> AppResult<Vec<ClientSession>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ClientSession>(
+                    r#"
+                    SELECT s.client_id, s.hostname, s.os_username, s.user_id, u.username, s.app_version,
+                           s.connected_at, s.last_seen, s.last_seen >= datetime('now', ?1) AS active
+                    FROM client_sessions s
+                    LEFT JOIN users u ON u.id = s.user_id
+                    ORDER BY active DESC, s.last_seen DESC
+                    "#,
+                )
+                .bind(format!("-{} seconds", SESSION_TIMEOUT_SECONDS))
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get client sessions",
+        ).await
+    }
+
+    pub async fn get_archives(&self) -> AppResult<Vec<ArchiveInfo>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ArchiveInfo>(
+                    r#"
+                    SELECT year, file_name, checksum, card_count, carried_over, created_by, created_at
+                    FROM archives
+                    ORDER BY year DESC
+                    "#,
+                )
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get archives",
+        ).await
+    }
+
+    pub async fn get_archive(&self, year: i32) -> AppResult<Option<ArchiveInfo>> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                sqlx::query_as::<_, ArchiveInfo>(
+                    r#"
+                    SELECT year, file_name, checksum, card_count, carried_over, created_by, created_at
+                    FROM archives
+                    WHERE year = ?1
+                    "#,
+                )
+                .bind(year)
+                .fetch_optional(pool)
+                .await
+            },
+            "Failed to get archive",
+        ).await
+    }
+
+    /// Переносит закрытые карточки года (с датой исполнения) вместе с их историей в отдельный файл.
+    /// Незакрытые карточки блокируют архивацию, если их явно не оставили в основной БД через carry_over.
+    /// Карточки из корзины не архивируются.
+    pub async fn archive_year(&self, year: i32, carry_over: &[i64], user_id: i64) -> AppResult<ArchiveInfo> {
+        if self.get_archive(year).await?.is_some() {
+            return Err(AppError::conflict(format!("Year {} is already archived", year)));
+        }
+
+        let file_name = archive::archive_file_name(&self.info.path, year);
+        let path = archive::archive_path(&self.info.path, &file_name);
+        if path.exists() {
+            return Err(AppError::conflict(format!("Archive file '{}' already exists", path.display())));
+        }
+
+        let result = self.write_archive(year, carry_over, user_id, &file_name, &path).await;
+        if !matches!(result, Ok(Ok(_))) {
+            // Файл создаётся при ATTACH ещё до начала транзакции
+            let _ = std::fs::remove_file(&path);
+        }
+        result??;
+
+        let checksum = archive::file_checksum(&path)?;
+        let pool = &self.pool;
+        let checksum = checksum.as_str();
+        self.execute_async(
+            || async move {
+                sqlx::query("UPDATE archives SET checksum = ?1 WHERE year = ?2")
+                    .bind(checksum)
+                    .bind(year)
+                    .execute(pool)
+                    .await
+            },
+            "Failed to record archive checksum",
+        ).await?;
+
+        self.get_archive(year).await?
+            .ok_or_else(|| AppError::not_found("Archive not found"))
+    }
+
+    async fn write_archive(
+        &self,
+        year: i32,
+        carry_over: &[i64],
+        user_id: i64,
+        file_name: &str,
+        path: &Path,
+    ) -> AppResult<AppResult<()>> {
+        let carry_over = serde_json::to_string(carry_over).map_err(|e| AppError::db(e.to_string()))?;
+        let carry_over = carry_over.as_str();
+        let path = path.to_string_lossy();
+        let path = path.as_ref();
+        let options = self.pool.connect_options();
+        let options = options.as_ref();
+
+        self.execute_async(
+            || async move {
+                // Отдельное соединение: ATTACH действует на соединение, в пул его возвращать нельзя
+                let mut conn = options.connect().await?;
+                sqlx::query("ATTACH DATABASE ?1 AS archive")
+                    .bind(path)
+                    .execute(&mut conn)
+                    .await?;
+
+                let mut tx = sqlx::Connection::begin_with(&mut conn, "BEGIN IMMEDIATE").await?;
+
+                let blocking: Vec<(i64,)> = sqlx::query_as(
+                    r#"
+                    SELECT id FROM main.control_cards
+                    WHERE year = ?1 AND completed_at IS NULL AND deleted_at IS NULL
+                      AND id NOT IN (SELECT value FROM json_each(?2))
+                    ORDER BY card_number
+                    "#,
+                )
+                .bind(year)
+                .bind(carry_over)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                if !blocking.is_empty() {
+                    let ids: Vec<i64> = blocking.into_iter().map(|(id,)| id).collect();
+                    return Ok(Err(AppError::conflict_with(
+                        format!("Year {} has {} open cards; close them or carry them over", year, ids.len()),
+                        &ids,
+                    )));
+                }
+
+                for statement in [
+                    "CREATE TABLE archive.control_cards AS SELECT * FROM main.control_cards WHERE 0",
+                    "CREATE TABLE archive.card_history AS SELECT * FROM main.card_history WHERE 0",
+                    "CREATE TABLE archive.archive_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+                ] {
+                    sqlx::query(statement).execute(&mut *tx).await?;
+                }
+
+                let copied = sqlx::query(
+                    r#"
+                    INSERT INTO archive.control_cards
+                    SELECT * FROM main.control_cards
+                    WHERE year = ?1 AND completed_at IS NOT NULL AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(year)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() as i64;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO archive.card_history
+                    SELECT * FROM main.card_history WHERE card_id IN (SELECT id FROM archive.control_cards)
+                    "#,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                let (carried_over,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM main.control_cards WHERE year = ?1 AND completed_at IS NULL AND deleted_at IS NULL",
+                )
+                .bind(year)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO archive.archive_meta (key, value) VALUES
+                        ('year', ?1), ('card_count', ?2), ('carried_over', ?3),
+                        ('created_at', datetime('now')), ('app_version', ?4)
+                    "#,
+                )
+                .bind(year.to_string())
+                .bind(copied.to_string())
+                .bind(carried_over.to_string())
+                .bind(env!("CARGO_PKG_VERSION"))
+                .execute(&mut *tx)
+                .await?;
+
+                for statement in [
+                    "INSERT OR IGNORE INTO main.purged_card_numbers (year, card_number) SELECT year, card_number FROM archive.control_cards",
+                    "DELETE FROM main.card_history WHERE card_id IN (SELECT id FROM archive.control_cards)",
+                    "DELETE FROM main.control_cards WHERE id IN (SELECT id FROM archive.control_cards)",
+                ] {
+                    sqlx::query(statement).execute(&mut *tx).await?;
+                }
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO main.archives (year, file_name, card_count, carried_over, created_by)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                )
+                .bind(year)
+                .bind(file_name)
+                .bind(copied)
+                .bind(carried_over)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+                tx.commit().await?;
+                sqlx::query("DETACH DATABASE archive").execute(&mut conn).await?;
+                sqlx::Connection::close(conn).await?;
+                Ok(Ok(()))
+            },
+            "Failed to archive year",
+        ).await
+    }
+
+    /// Согласованная копия БД через VACUUM INTO: другие клиенты могут продолжать писать
+    pub async fn backup_to(&self, dest: &Path) -> AppResult<()> {
+        if dest.exists() {
+            return Err(AppError::conflict(format!("File '{}' already exists", dest.display())));
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AppError::db(format!("Failed to create backup directory: {}", e)))?;
+        }
+
+        let pool = &self.pool;
+        let dest_str = &dest.to_string_lossy().to_string();
 
-        // Выполняем миграции только если таблица существует
-        if table_exists.0 > 0 {
-            // Вспомогательная функция для безопасного добавления колонки
-            // Просто пытаемся добавить и игнорируем ошибку, если колонка уже существует
-            async fn try_add_column(pool: &SqlitePool, column_name: &str, column_type: &str) {
-                let alter_query = format!(
-                    "ALTER TABLE control_cards ADD COLUMN {} {}",
-                    column_name, column_type
-                );
-                let _ = sqlx::query(&alter_query)
+        let result = self.execute_async(
+            || async move {
+                sqlx::query("VACUUM INTO ?1")
+                    .bind(dest_str)
                     .execute(pool)
-                    .await;
-            }
+                    .await
+            },
+            "Failed to back up database",
+        ).await;
 
-            // Миграции: добавляем поля если их нет (игнорируем ошибки если уже существуют)
-            try_add_column(pool, "user_id", "INTEGER").await;
-            try_add_column(pool, "executor_user_id", "INTEGER").await;
-            try_add_column(pool, "return_to", "TEXT").await;
-            try_add_column(pool, "execution_deadline", "TEXT").await;
-            try_add_column(pool, "execution_period_type", "TEXT").await;
-            try_add_column(pool, "extended_deadline", "TEXT").await;
-            try_add_column(pool, "resolution", "TEXT").await;
-            try_add_column(pool, "department", "TEXT").await;
-            try_add_column(pool, "controller", "TEXT").await;
-            try_add_column(pool, "controller_user_id", "INTEGER").await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(dest);
         }
-
-        Ok(())
+        result.map(|_| ())
     }
 
-    fn get_pool(&self) -> Result<&SqlitePool, String> {
-        self.pool.as_ref().ok_or_else(|| "Database not connected".to_string())
-    }
+    /// Копия БД с другим ключом шифрования (None — без шифрования)
+    pub async fn export_with_key(&self, dest: &Path, key: Option<&str>) -> AppResult<()> {
+        let options = self.pool.connect_options();
+        let mut conn = options
+            .connect()
+            .await
+            .map_err(|e| AppError::from_sqlx("Failed to open database", e))?;
 
-    fn get_runtime(&self) -> Result<&Runtime, String> {
-        self.runtime.as_ref().ok_or_else(|| "Runtime not available".to_string())
+        let result = encryption::export_with_key(&mut conn, dest, key).await;
+        let _ = sqlx::Connection::close(conn).await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(dest);
+        }
+        result.map_err(|e| AppError::from_sqlx("Failed to re-encrypt database", e))
     }
 
-    fn execute_async<F, T>(&self, operation: F, error_msg: &str) -> Result<T, String>
-    where
-        F: std::future::Future<Output = Result<T, sqlx::Error>>,
-    {
-        let runtime = self.get_runtime()?;
-        let result = runtime
-            .block_on(operation)
-            .map_err(|e| format!("{}: {}", error_msg, e));
-        result
-    }
+    pub async fn get_backup_settings(&self) -> AppResult<BackupSettings> {
+        let pool = &self.pool;
 
-    pub fn get_next_card_number(&self, year: i32) -> Result<i32, String> {
-        let pool = self.get_pool()?.clone();
-        let year = year;
-        
         self.execute_async(
-            async move {
-                let result: Option<(i64,)> = sqlx::query_as(
-                    r#"
-                    SELECT MAX(card_number) as max_number
-                    FROM control_cards
-                    WHERE year = ?1
-                    "#,
+            || async move {
+                sqlx::query_as::<_, BackupSettings>(
+                    "SELECT enabled, interval_hours, retention, directory, last_backup_at FROM backup_settings WHERE id = 1",
                 )
-                .bind(year)
-                .fetch_optional(&pool)
-                .await?;
-
-                let next_number = match result {
-                    Some((max,)) if max > 0 => (max as i32) + 1,
-                    _ => 1,
-                };
-                Ok(next_number)
+                .fetch_one(pool)
+                .await
             },
-            "Failed to get next card number",
-        )
+            "Failed to get backup settings",
+        ).await
     }
 
-    pub fn create_control_card(
-        &self,
-        card_number: i32,
-        year: i32,
-        executor: &str,
-        reporter: &str,
-        summary: &str,
-        document_reference: &str,
-        user_id: Option<i64>,
-        executor_user_id: Option<i64>,
-        return_to: Option<&str>,
-        execution_deadline: Option<&str>,
-        execution_period_type: Option<&str>,
-        extended_deadline: Option<&str>,
-        resolution: Option<&str>,
-        department: Option<&str>,
-        controller: Option<&str>,
-        controller_user_id: Option<i64>,
-    ) -> Result<i64, String> {
-        let pool = self.get_pool()?.clone();
-        let executor = executor.to_string();
-        let reporter = reporter.to_string();
-        let summary = summary.to_string();
-        let document_reference = document_reference.to_string();
-        let return_to = return_to.map(|s| s.to_string());
-        let execution_deadline = execution_deadline.map(|s| s.to_string());
-        let execution_period_type = execution_period_type.map(|s| s.to_string());
-        let extended_deadline = extended_deadline.map(|s| s.to_string());
-        let resolution = resolution.map(|s| s.to_string());
-        let department = department.map(|s| s.to_string());
-        let controller = controller.map(|s| s.to_string());
-        
+    pub async fn update_backup_settings(&self, settings: &BackupSettings) -> AppResult<BackupSettings> {
+        let pool = &self.pool;
+        let directory = settings.directory.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
         self.execute_async(
-            async move {
-                let result = sqlx::query(
+            || async move {
+                sqlx::query_as::<_, BackupSettings>(
                     r#"
-                    INSERT INTO control_cards (card_number, year, executor, reporter, summary, document_reference, user_id, executor_user_id, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id)
-                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                    UPDATE backup_settings
+                    SET enabled = ?1, interval_hours = ?2, retention = ?3, directory = ?4
+                    WHERE id = 1
+                    RETURNING enabled, interval_hours, retention, directory, last_backup_at
                     "#,
                 )
-                .bind(card_number)
-                .bind(year)
-                .bind(&executor)
-                .bind(&reporter)
-                .bind(&summary)
-                .bind(&document_reference)
-                .bind(user_id)
-                .bind(executor_user_id)
-                .bind(&return_to)
-                .bind(&execution_deadline)
-                .bind(&execution_period_type)
-                .bind(&extended_deadline)
-                .bind(&resolution)
-                .bind(&department)
-                .bind(&controller)
-                .bind(controller_user_id)
-                .execute(&pool)
-                .await?;
-                Ok(result.last_insert_rowid())
+                .bind(settings.enabled)
+                .bind(settings.interval_hours)
+                .bind(settings.retention)
+                .bind(directory)
+                .fetch_one(pool)
+                .await
             },
-            "Failed to create control card",
-        )
+            "Failed to update backup settings",
+        ).await
     }
 
-    pub fn get_control_card(&self, id: i64) -> Result<ControlCard, String> {
-        let pool = self.get_pool()?.clone();
-        
+    /// Отмечает начало автоматического копирования, если оно включено и подошло время.
+    /// Отметка ставится одним UPDATE, поэтому копию делает только одно рабочее место.
+    pub async fn claim_scheduled_backup(&self) -> AppResult<Option<BackupSettings>> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                sqlx::query_as::<_, ControlCard>(
+            || async move {
+                sqlx::query_as::<_, BackupSettings>(
                     r#"
-                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id
-                    FROM control_cards
-                    WHERE id = ?1
+                    UPDATE backup_settings
+                    SET last_backup_at = datetime('now')
+                    WHERE id = 1 AND enabled = 1
+                      AND (last_backup_at IS NULL
+                           OR last_backup_at <= datetime('now', '-' || interval_hours || ' hours'))
+                    RETURNING enabled, interval_hours, retention, directory, last_backup_at
                     "#,
                 )
-                .bind(id)
-                .fetch_one(&pool)
+                .fetch_optional(pool)
                 .await
             },
-            "Failed to get control card",
-        )
+            "Failed to schedule backup",
+        ).await
     }
 
-    pub fn get_all_control_cards(&self) -> Result<Vec<ControlCard>, String> {
-        let pool = self.get_pool()?.clone();
-        
+    pub async fn get_smtp_settings(&self) -> AppResult<SmtpSettings> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result = sqlx::query_as::<_, ControlCard>(
+            || async move {
+                sqlx::query_as::<_, SmtpSettings>(
                     r#"
-                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id
-                    FROM control_cards
-                    ORDER BY year DESC, card_number DESC
+                    SELECT enabled, host, port, security, username, password, from_address, remind_days_before
+                    FROM smtp_settings WHERE id = 1
                     "#,
                 )
-                .fetch_all(&pool)
-                .await;
-                result
+                .fetch_one(pool)
+                .await
             },
-            "Failed to get control cards",
-        )
+            "Failed to get SMTP settings",
+        ).await
     }
 
-    pub fn get_control_cards_by_executor_id(&self, executor_user_id: i64) -> Result<Vec<ControlCard>, String> {
-        let pool = self.get_pool()?.clone();
-        
+    /// Пустой пароль оставляет прежний; без имени пользователя пароль сбрасывается
+    pub async fn update_smtp_settings(&self, settings: &SmtpSettings) -> AppResult<SmtpSettings> {
+        let pool = &self.pool;
+        let username = settings.username.as_deref().map(str::trim).filter(|u| !u.is_empty());
+        let password = settings.password.as_deref().filter(|p| !p.is_empty());
+
         self.execute_async(
-            async move {
-                sqlx::query_as::<_, ControlCard>(
+            || async move {
+                sqlx::query_as::<_, SmtpSettings>(
                     r#"
-                    SELECT id, card_number, year, executor, reporter, summary, document_reference, executor_user_id, created_at, return_to, execution_deadline, execution_period_type, extended_deadline, resolution, department, controller, controller_user_id
-                    FROM control_cards
-                    WHERE executor_user_id = ?1
-                    ORDER BY year DESC, card_number DESC
+                    UPDATE smtp_settings
+                    SET enabled = ?1, host = ?2, port = ?3, security = ?4, username = ?5,
+                        password = CASE WHEN ?5 IS NULL THEN NULL ELSE COALESCE(?6, password) END,
+                        from_address = ?7, remind_days_before = ?8
+                    WHERE id = 1
+                    RETURNING enabled, host, port, security, username, password, from_address, remind_days_before
                     "#,
                 )
-                .bind(executor_user_id)
-                .fetch_all(&pool)
+                .bind(settings.enabled)
+                .bind(settings.host.trim())
+                .bind(settings.port)
+                .bind(settings.security)
+                .bind(username)
+                .bind(password)
+                .bind(settings.from_address.trim())
+                .bind(settings.remind_days_before)
+                .fetch_one(pool)
                 .await
             },
-            "Failed to get control cards by executor_user_id",
-        )
+            "Failed to update SMTP settings",
+        ).await
     }
 
+    /// Отмечает, что напоминание отправляется, и возвращает id записи.
+    /// None — письмо уже отправлено или отправляется другим рабочим местом.
+    /// Неудачную отправку можно повторить через полчаса, пока не исчерпаны попытки.
+    pub async fn claim_notification(&self, card_id: i64, recipient: &str, kind: &str, deadline: NaiveDate) -> AppResult<Option<i64>> {
+        let pool = &self.pool;
 
-    pub fn update_control_card(
-        &self,
-        id: i64,
-        card_number: i32,
-        year: i32,
-        executor: &str,
-        reporter: &str,
-        summary: &str,
-        document_reference: &str,
-        user_id: Option<i64>,
-        executor_user_id: Option<i64>,
-        return_to: Option<&str>,
-        execution_deadline: Option<&str>,
-        execution_period_type: Option<&str>,
-        extended_deadline: Option<&str>,
-        resolution: Option<&str>,
-        department: Option<&str>,
-        controller: Option<&str>,
-        controller_user_id: Option<i64>,
-    ) -> Result<usize, String> {
-        let pool = self.get_pool()?.clone();
-        let executor = executor.to_string();
-        let reporter = reporter.to_string();
-        let summary = summary.to_string();
-        let document_reference = document_reference.to_string();
-        let return_to = return_to.map(|s| s.to_string());
-        let execution_deadline = execution_deadline.map(|s| s.to_string());
-        let execution_period_type = execution_period_type.map(|s| s.to_string());
-        let extended_deadline = extended_deadline.map(|s| s.to_string());
-        let resolution = resolution.map(|s| s.to_string());
-        let department = department.map(|s| s.to_string());
-        let controller = controller.map(|s| s.to_string());
-        
         self.execute_async(
-            async move {
-                let result = sqlx::query(
+            || async move {
+                sqlx::query_scalar::<_, i64>(
                     r#"
-                    UPDATE control_cards
-                    SET card_number = ?1, year = ?2, executor = ?3, reporter = ?4, summary = ?5, document_reference = ?6, user_id = ?7, executor_user_id = ?8, return_to = ?9, execution_deadline = ?10, execution_period_type = ?11, extended_deadline = ?12, resolution = ?13, department = ?14, controller = ?15, controller_user_id = ?16
-                    WHERE id = ?17
+                    INSERT INTO sent_notifications (card_id, recipient, kind, deadline)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT (card_id, recipient, kind, deadline) DO UPDATE
+                    SET status = 'sending', attempts = attempts + 1, updated_at = datetime('now')
+                    WHERE status IN ('failed', 'sending')
+                      AND attempts < ?5
+                      AND updated_at <= datetime('now', '-30 minutes')
+                    RETURNING id
                     "#,
                 )
-                .bind(card_number)
-                .bind(year)
-                .bind(&executor)
-                .bind(&reporter)
-                .bind(&summary)
-                .bind(&document_reference)
-                .bind(user_id)
-                .bind(executor_user_id)
-                .bind(&return_to)
-                .bind(&execution_deadline)
-                .bind(&execution_period_type)
-                .bind(&extended_deadline)
-                .bind(&resolution)
-                .bind(&department)
-                .bind(&controller)
-                .bind(controller_user_id)
-                .bind(id)
-                .execute(&pool)
-                .await?;
-
-                Ok(result.rows_affected() as usize)
+                .bind(card_id)
+                .bind(recipient)
+                .bind(kind)
+                .bind(deadlines::format_date(deadline))
+                .bind(reminders::MAX_SEND_ATTEMPTS)
+                .fetch_optional(pool)
+                .await
             },
-            "Failed to update control card",
-        )
+            "Failed to record notification",
+        ).await
     }
 
-    pub fn delete_control_card(&self, id: i64) -> Result<usize, String> {
-        let pool = self.get_pool()?.clone();
-        
+    pub async fn finish_notification(&self, id: i64, error: Option<&str>) -> AppResult<()> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result = sqlx::query(
+            || async move {
+                sqlx::query(
                     r#"
-                    DELETE FROM control_cards
-                    WHERE id = ?1
+                    UPDATE sent_notifications
+                    SET status = CASE WHEN ?1 IS NULL THEN 'sent' ELSE 'failed' END,
+                        last_error = ?1, updated_at = datetime('now')
+                    WHERE id = ?2
                     "#,
                 )
+                .bind(error)
                 .bind(id)
-                .execute(&pool)
+                .execute(pool)
                 .await?;
-
-                Ok(result.rows_affected() as usize)
+                Ok(())
             },
-            "Failed to delete control card",
-        )
+            "Failed to record notification",
+        ).await
     }
 
-    pub fn create_user(
-        &self,
-        username: &str,
-        password_hash: &str,
-        role: &str,
-    ) -> Result<i64, String> {
-        let pool = self.get_pool()?.clone();
-        let username = username.to_string();
-        let password_hash = password_hash.to_string();
-        let role = role.to_string();
-        
+    pub async fn list_webhooks(&self) -> AppResult<Vec<Webhook>> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result = sqlx::query(
-                    r#"
-                    INSERT INTO users (username, password_hash, role)
-                    VALUES (?1, ?2, ?3)
-                    "#,
+            || async move {
+                sqlx::query_as::<_, Webhook>(
+                    "SELECT id, url, secret, events, enabled, created_at FROM webhooks ORDER BY id",
                 )
-                .bind(&username)
-                .bind(&password_hash)
-                .bind(&role)
-                .execute(&pool)
-                .await?;
-
-                Ok(result.last_insert_rowid())
+                .fetch_all(pool)
+                .await
             },
-            "Failed to create user",
-        )
+            "Failed to get webhooks",
+        ).await
     }
 
-    pub fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
-        let pool = self.get_pool()?.clone();
-        let username = username.to_string();
-        
+    pub async fn create_webhook(&self, url: &str, secret: &str, events: &str, enabled: bool) -> AppResult<Webhook> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                sqlx::query_as::<_, User>(
+            || async move {
+                sqlx::query_as::<_, Webhook>(
                     r#"
-                    SELECT id, username, password_hash, role, created_at
-                    FROM users
-                    WHERE username = ?1
+                    INSERT INTO webhooks (url, secret, events, enabled)
+                    VALUES (?1, ?2, ?3, ?4)
+                    RETURNING id, url, secret, events, enabled, created_at
                     "#,
                 )
-                .bind(&username)
-                .fetch_optional(&pool)
+                .bind(url)
+                .bind(secret)
+                .bind(events)
+                .bind(enabled)
+                .fetch_one(pool)
                 .await
             },
-            "Failed to get user by username",
-        )
+            "Failed to create webhook",
+        ).await
     }
 
-    pub fn get_user_by_id(&self, id: i64) -> Result<Option<User>, String> {
-        let pool = self.get_pool()?.clone();
-        
+    /// Пустой secret оставляет прежний
+    pub async fn update_webhook(&self, id: i64, url: &str, secret: Option<&str>, events: &str, enabled: bool) -> AppResult<Webhook> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result = sqlx::query_as::<_, User>(
+            || async move {
+                sqlx::query_as::<_, Webhook>(
                     r#"
-                    SELECT id, username, password_hash, role, created_at
-                    FROM users
-                    WHERE id = ?1
+                    UPDATE webhooks
+                    SET url = ?1, secret = COALESCE(?2, secret), events = ?3, enabled = ?4
+                    WHERE id = ?5
+                    RETURNING id, url, secret, events, enabled, created_at
                     "#,
                 )
+                .bind(url)
+                .bind(secret)
+                .bind(events)
+                .bind(enabled)
                 .bind(id)
-                .fetch_optional(&pool)
-                .await;
-                result
+                .fetch_optional(pool)
+                .await
             },
-            "Failed to get user by id",
-        )
+            "Failed to update webhook",
+        ).await?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))
     }
 
-    pub fn has_any_users(&self) -> Result<bool, String> {
-        let pool = self.get_pool()?.clone();
-        
+    /// Удаляет вебхук вместе с его очередью и журналом доставки
+    pub async fn delete_webhook(&self, id: i64) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        let deleted = self.execute_async(
+            || async move {
+                let mut tx = pool.begin().await?;
+                sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                let result = sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                Ok(result.rows_affected() as usize)
+            },
+            "Failed to delete webhook",
+        ).await?;
+
+        if deleted == 0 {
+            return Err(AppError::not_found("Webhook not found"));
+        }
+        Ok(deleted)
+    }
+
+    /// Ключи событий, поставленных в очередь за период поиска событий (с запасом в день)
+    pub async fn recent_webhook_event_keys(&self, webhook_id: i64) -> AppResult<Vec<String>> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result: (i64,) = sqlx::query_as(
-                    r#"
-                    SELECT COUNT(*) as count
-                    FROM users
-                    "#,
+            || async move {
+                sqlx::query_scalar::<_, String>(
+                    "SELECT event_key FROM webhook_deliveries WHERE webhook_id = ?1 AND created_at >= datetime('now', ?2)",
                 )
-                .fetch_one(&pool)
-                .await?;
+                .bind(webhook_id)
+                .bind(format!("-{} days", webhooks::EVENT_LOOKBACK_DAYS + 1))
+                .fetch_all(pool)
+                .await
+            },
+            "Failed to get webhook deliveries",
+        ).await
+    }
 
-                Ok(result.0 > 0)
+    /// Ставит доставки в очередь; уже известные события пропускаются
+    pub async fn enqueue_webhook_deliveries(&self, deliveries: &[NewDelivery]) -> AppResult<usize> {
+        let pool = &self.pool;
+
+        self.execute_async(
+            || async move {
+                let mut tx = pool.begin().await?;
+                let mut added = 0;
+                for d in deliveries {
+                    let result = sqlx::query(
+                        r#"
+                        INSERT OR IGNORE INTO webhook_deliveries (webhook_id, event, card_id, event_key, payload)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                        "#,
+                    )
+                    .bind(d.webhook_id)
+                    .bind(d.event)
+                    .bind(d.card_id)
+                    .bind(&d.event_key)
+                    .bind(&d.payload)
+                    .execute(&mut *tx)
+                    .await?;
+                    added += result.rows_affected() as usize;
+                }
+                tx.commit().await?;
+                Ok(added)
             },
-            "Failed to check if users exist",
-        )
+            "Failed to enqueue webhook deliveries",
+        ).await
     }
 
-    pub fn get_all_users(&self) -> Result<Vec<User>, String> {
-        let pool = self.get_pool()?.clone();
-        
+    /// Забирает подошедшие доставки включённых вебхуков и увеличивает счётчик попыток.
+    /// Доставка, зависшая в состоянии sending дольше 5 минут, забирается повторно.
+    pub async fn claim_webhook_deliveries(&self, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                sqlx::query_as::<_, User>(
+            || async move {
+                sqlx::query_as::<_, WebhookDelivery>(
                     r#"
-                    SELECT id, username, password_hash, role, created_at
-                    FROM users
-                    ORDER BY created_at DESC
+                    UPDATE webhook_deliveries
+                    SET status = 'sending', attempts = attempts + 1, next_attempt_at = datetime('now', '+5 minutes')
+                    WHERE id IN (
+                        SELECT id FROM webhook_deliveries
+                        WHERE status IN ('pending', 'sending') AND next_attempt_at <= datetime('now')
+                          AND webhook_id IN (SELECT id FROM webhooks WHERE enabled = 1)
+                        ORDER BY id
+                        LIMIT ?1
+                    )
+                    RETURNING id, webhook_id, NULL AS url, event, card_id, payload, status, attempts, next_attempt_at,
+                              response_status, last_error, created_at, delivered_at
                     "#,
                 )
-                .fetch_all(&pool)
+                .bind(limit)
+                .fetch_all(pool)
                 .await
             },
-            "Failed to get all users",
-        )
+            "Failed to claim webhook deliveries",
+        ).await
     }
 
-    pub fn update_user(
+    /// Записывает результат попытки. Без ошибки доставка завершена; с ошибкой она
+    /// повторяется через retry_in_minutes, а без него считается неудачной.
+    pub async fn finish_webhook_delivery(
         &self,
         id: i64,
-        username: &str,
-        role: &str,
-    ) -> Result<usize, String> {
-        let pool = self.get_pool()?.clone();
-        let username = username.to_string();
-        let role = role.to_string();
-        
+        response_status: Option<i64>,
+        error: Option<&str>,
+        retry_in_minutes: Option<i64>,
+    ) -> AppResult<()> {
+        let pool = &self.pool;
+        let status = match (error, retry_in_minutes) {
+            (None, _) => "delivered",
+            (Some(_), Some(_)) => "pending",
+            (Some(_), None) => "failed",
+        };
+
         self.execute_async(
-            async move {
-                let result = sqlx::query(
+            || async move {
+                sqlx::query(
                     r#"
-                    UPDATE users
-                    SET username = ?1, role = ?2
-                    WHERE id = ?3
+                    UPDATE webhook_deliveries
+                    SET status = ?1, response_status = ?2, last_error = ?3,
+                        next_attempt_at = datetime('now', ?4),
+                        delivered_at = CASE WHEN ?1 = 'delivered' THEN datetime('now') ELSE delivered_at END
+                    WHERE id = ?5
                     "#,
                 )
-                .bind(&username)
-                .bind(&role)
+                .bind(status)
+                .bind(response_status)
+                .bind(error)
+                .bind(format!("+{} minutes", retry_in_minutes.unwrap_or(0)))
                 .bind(id)
-                .execute(&pool)
+                .execute(pool)
                 .await?;
-
-                Ok(result.rows_affected() as usize)
+                Ok(())
             },
-            "Failed to update user",
-        )
+            "Failed to record webhook delivery",
+        ).await
     }
 
-    pub fn update_user_password(
-        &self,
-        id: i64,
-        password_hash: &str,
-    ) -> Result<usize, String> {
-        let pool = self.get_pool()?.clone();
-        let password_hash = password_hash.to_string();
-        
+    /// Журнал доставки, от новых к старым
+    pub async fn list_webhook_deliveries(&self, webhook_id: Option<i64>, limit: i64) -> AppResult<Vec<WebhookDelivery>> {
+        let pool = &self.pool;
+
         self.execute_async(
-            async move {
-                let result = sqlx::query(
+            || async move {
+                sqlx::query_as::<_, WebhookDelivery>(
                     r#"
-                    UPDATE users
-                    SET password_hash = ?1
-                    WHERE id = ?2
+                    SELECT d.id, d.webhook_id, w.url, d.event, d.card_id, d.payload, d.status, d.attempts,
+                           d.next_attempt_at, d.response_status, d.last_error, d.created_at, d.delivered_at
+                    FROM webhook_deliveries d
+                    LEFT JOIN webhooks w ON w.id = d.webhook_id
+                    WHERE ?1 IS NULL OR d.webhook_id = ?1
+                    ORDER BY d.id DESC
+                    LIMIT ?2
                     "#,
                 )
-                .bind(&password_hash)
-                .bind(id)
-                .execute(&pool)
-                .await?;
-
-                Ok(result.rows_affected() as usize)
+                .bind(webhook_id)
+                .bind(limit)
+                .fetch_all(pool)
+                .await
             },
-            "Failed to update user password",
-        )
+            "Failed to get webhook deliveries",
+        ).await
     }
 
-    pub fn delete_user(&self, id: i64) -> Result<usize, String> {
-        let pool = self.get_pool()?.clone();
-        
-        self.execute_async(
-            async move {
-                let result = sqlx::query(
+    /// Возвращает неудачную доставку в очередь с полным набором попыток
+    pub async fn retry_webhook_delivery(&self, id: i64) -> AppResult<()> {
+        let pool = &self.pool;
+
+        let updated = self.execute_async(
+            || async move {
+                sqlx::query(
                     r#"
-                    DELETE FROM users
-                    WHERE id = ?1
+                    UPDATE webhook_deliveries
+                    SET status = 'pending', attempts = 0, next_attempt_at = datetime('now')
+                    WHERE id = ?1 AND status = 'failed'
                     "#,
                 )
                 .bind(id)
-                .execute(&pool)
-                .await?;
-
-                Ok(result.rows_affected() as usize)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected())
             },
-            "Failed to delete user",
-        )
+            "Failed to retry webhook delivery",
+        ).await?;
+
+        if updated == 0 {
+            return Err(AppError::not_found("Failed webhook delivery not found"));
+        }
+        Ok(())
     }
 }
 
-impl Default for Database {
-    fn default() -> Self {
-        Self::new()
+/// Уникальный в пределах общей БД идентификатор запущенного клиента
+fn new_client_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{}-{}-{:x}", whoami::devicename(), std::process::id(), nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numbering::NumberingSchemeInput;
+
+    async fn card_with_scheme(db: &Database, number: i32, year: i32, scheme_id: i64) -> i64 {
+        db.create_control_card(Some(number), year, "e", "r", "s", "d", None, None, None, None, None, None, None, None, None, None, Some(scheme_id))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn renumbering_keeps_scheme_numbers_unique() {
+        let db = Database::connect_in_memory(false).await.unwrap();
+        let user = db.create_user("admin", None, None, "not-a-hash", "admin").await.unwrap();
+        let scheme_id = db
+            .create_numbering_scheme(&NumberingSchemeInput {
+                name: "Основная".to_string(),
+                template: "{seq:3}/{yy}".to_string(),
+                prefix: None,
+                department_code: None,
+                reset_policy: "yearly".to_string(),
+            })
+            .await
+            .unwrap();
+        let first = card_with_scheme(&db, 10, 2026, scheme_id).await;
+        let second = card_with_scheme(&db, 20, 2026, scheme_id).await;
+        let formatted = |id| {
+            let db = db.clone();
+            async move { db.get_control_card(id).await.unwrap().formatted_number }
+        };
+        assert_eq!(formatted(first).await.as_deref(), Some("001/26"));
+        assert_eq!(formatted(second).await.as_deref(), Some("002/26"));
+
+        // Смена номера в том же году номер по схеме не меняет и не совпадает с чужим
+        db.patch_control_card(first, &[("card_number", PatchValue::Integer(Some(2)))], 1, user).await.unwrap();
+        assert_eq!(formatted(first).await.as_deref(), Some("001/26"));
+
+        // Со сменой года номер берётся из счётчика нового периода
+        db.patch_control_card(second, &[("year", PatchValue::Integer(Some(2025)))], 1, user).await.unwrap();
+        assert_eq!(formatted(second).await.as_deref(), Some("001/25"));
+
+        // Если счётчик отстал, совпадение с номером другой карточки — конфликт
+        sqlx::query("DELETE FROM numbering_counters").execute(&db.pool).await.unwrap();
+        let error = db
+            .patch_control_card(first, &[("year", PatchValue::Integer(Some(2025)))], 2, user)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), "conflict");
+        assert_eq!(formatted(first).await.as_deref(), Some("001/26"));
+
+        // Без схемы прежний номер по схеме сохраняется
+        db.delete_numbering_scheme(scheme_id).await.unwrap();
+        db.patch_control_card(first, &[("year", PatchValue::Integer(Some(2024)))], 2, user).await.unwrap();
+        assert_eq!(formatted(first).await.as_deref(), Some("001/26"));
     }
 }
@@ -0,0 +1,207 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::error::{AppError, AppResult};
+
+/// Формат дат, которым обмениваются фронтенд и БД
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Разбирает дату в формате ISO-8601: YYYY-MM-DD или полная дата-время (берётся дата)
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    let trimmed = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(trimmed, DATE_FORMAT) {
+        return Ok(date);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime.date_naive());
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(datetime.date());
+    }
+    Err(format!("Invalid date '{}': expected ISO-8601 (YYYY-MM-DD)", value))
+}
+
+/// Разбирает необязательную дату из ввода. Пустая строка считается отсутствием значения.
+pub fn parse_optional_date(field: &str, value: Option<&str>) -> AppResult<Option<NaiveDate>> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(v) => parse_date(v).map(Some).map_err(|e| AppError::validation(field, e)),
+    }
+}
+
+/// Разбор дат, сохранённых старыми версиями приложения в произвольном виде
+pub fn parse_legacy_date(value: &str) -> Option<NaiveDate> {
+    if let Ok(date) = parse_date(value) {
+        return Some(date);
+    }
+
+    let trimmed = value.trim();
+    ["%d.%m.%Y", "%d/%m/%Y", "%Y/%m/%d", "%Y.%m.%d", "%d-%m-%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(trimmed, format).ok())
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|dt| dt.date())
+        })
+}
+
+pub fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+pub fn format_date(date: NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+pub fn is_working_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Наибольший срок исполнения в шаблоне карточки, в рабочих днях
+pub const MAX_DEADLINE_OFFSET_DAYS: i32 = 365;
+
+/// Срок исполнения в шаблоне: от 0 до MAX_DEADLINE_OFFSET_DAYS рабочих дней
+pub fn validate_deadline_offset(days: Option<i32>) -> AppResult<()> {
+    match days {
+        Some(days) if !(0..=MAX_DEADLINE_OFFSET_DAYS).contains(&days) => Err(AppError::validation(
+            "deadline_offset_days",
+            format!("Deadline offset must be between 0 and {} working days", MAX_DEADLINE_OFFSET_DAYS),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Сдвигает дату на указанное количество рабочих дней (пн-пт).
+/// Если результат попадает на выходной при нулевом сдвиге, переносит на ближайший рабочий день.
+/// None — результат выходит за пределы представимых дат.
+pub fn add_working_days(start: NaiveDate, days: i64) -> Option<NaiveDate> {
+    let step = if days < 0 { -1 } else { 1 };
+    let mut date = start;
+    let mut remaining = days.unsigned_abs();
+
+    while remaining > 0 {
+        date = date.checked_add_signed(chrono::Duration::days(step))?;
+        if is_working_day(date) {
+            remaining -= 1;
+        }
+    }
+
+    while !is_working_day(date) {
+        date = date.succ_opt()?;
+    }
+
+    Some(date)
+}
+
+/// Даты карточки, участвующие в проверках
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardDates {
+    pub year: i32,
+    pub execution_deadline: Option<NaiveDate>,
+    pub extended_deadline: Option<NaiveDate>,
+    pub completed_at: Option<NaiveDate>,
+}
+
+/// Состояние исполнения карточки
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CardStatus {
+    Open,
+    Completed,
+    Overdue,
+}
+
+impl CardDates {
+    /// Действующий срок: продлённый, если он есть
+    pub fn effective_deadline(&self) -> Option<NaiveDate> {
+        self.extended_deadline.or(self.execution_deadline)
+    }
+
+    /// Просроченной считается неисполненная карточка, срок которой истёк до сегодняшнего дня
+    pub fn status(&self, today: NaiveDate) -> CardStatus {
+        if self.completed_at.is_some() {
+            CardStatus::Completed
+        } else if self.effective_deadline().is_some_and(|d| d < today) {
+            CardStatus::Overdue
+        } else {
+            CardStatus::Open
+        }
+    }
+}
+
+/// Проверяет согласованность дат карточки.
+/// previous — даты до изменения: срок, оставшийся прежним, может быть в прошлом.
+pub fn validate_card_dates(dates: &CardDates, today: NaiveDate, previous: Option<&CardDates>) -> AppResult<()> {
+    check_card_dates(dates, today, previous, false)
+}
+
+/// Проверка дат карточки из существующего реестра: сроки в прошлом у неё обычны
+pub fn validate_imported_card_dates(dates: &CardDates, today: NaiveDate) -> AppResult<()> {
+    check_card_dates(dates, today, None, true)
+}
+
+fn check_card_dates(dates: &CardDates, today: NaiveDate, previous: Option<&CardDates>, allow_past: bool) -> AppResult<()> {
+    if dates.year < 1900 || dates.year > today.year() + 1 {
+        return Err(AppError::validation("year", format!("Card year {} is out of range", dates.year)));
+    }
+
+    if let Some(deadline) = dates.execution_deadline {
+        if deadline.year() < dates.year {
+            return Err(AppError::validation(
+                "execution_deadline",
+                format!(
+                    "Execution deadline {} is earlier than card year {}",
+                    format_date(deadline),
+                    dates.year
+                ),
+            ));
+        }
+
+        let unchanged = previous.is_some_and(|p| p.execution_deadline == Some(deadline));
+        if deadline < today && !unchanged && !allow_past {
+            return Err(AppError::validation(
+                "execution_deadline",
+                format!("Execution deadline {} is in the past", format_date(deadline)),
+            ));
+        }
+    }
+
+    if let Some(extended) = dates.extended_deadline {
+        let Some(deadline) = dates.execution_deadline else {
+            return Err(AppError::validation(
+                "extended_deadline",
+                "Extended deadline requires an execution deadline",
+            ));
+        };
+
+        if extended <= deadline {
+            return Err(AppError::validation(
+                "extended_deadline",
+                format!(
+                    "Extended deadline {} must be later than execution deadline {}",
+                    format_date(extended),
+                    format_date(deadline)
+                ),
+            ));
+        }
+
+        let unchanged = previous.is_some_and(|p| p.extended_deadline == Some(extended));
+        if extended < today && !unchanged && !allow_past {
+            return Err(AppError::validation(
+                "extended_deadline",
+                format!("Extended deadline {} is in the past", format_date(extended)),
+            ));
+        }
+    }
+
+    if let Some(completed) = dates.completed_at {
+        if completed > today {
+            return Err(AppError::validation(
+                "completed_at",
+                format!("Completion date {} is in the future", format_date(completed)),
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use serde::Serialize;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, FromRow, SqliteConnection};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::backup;
+use crate::database::{SCHEMA_VERSION, SESSION_TIMEOUT_SECONDS};
+use crate::encryption;
+use crate::error::{AppError, AppResult};
+use crate::storage::{self, StorageInfo};
+
+/// Ссылка на другую таблицу и способ исправить висячую ссылку
+struct Reference {
+    table: &'static str,
+    column: &'static str,
+    target: &'static str,
+    /// Обязательную ссылку нельзя обнулить, такие строки удаляются
+    delete_row: bool,
+}
+
+const REFERENCES: &[Reference] = &[
+    Reference { table: "control_cards", column: "user_id", target: "users", delete_row: false },
+    Reference { table: "control_cards", column: "executor_user_id", target: "users", delete_row: false },
+    Reference { table: "control_cards", column: "controller_user_id", target: "users", delete_row: false },
+    Reference { table: "control_cards", column: "deleted_by", target: "users", delete_row: false },
+    Reference { table: "control_cards", column: "numbering_scheme_id", target: "numbering_schemes", delete_row: false },
+    Reference { table: "card_history", column: "card_id", target: "control_cards", delete_row: true },
+    Reference { table: "card_history", column: "user_id", target: "users", delete_row: false },
+    Reference { table: "card_templates", column: "controller_user_id", target: "users", delete_row: false },
+    Reference { table: "card_number_reservations", column: "user_id", target: "users", delete_row: true },
+    Reference { table: "client_sessions", column: "user_id", target: "users", delete_row: false },
+    Reference { table: "archives", column: "created_by", target: "users", delete_row: false },
+];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileStatus {
+    pub exists: bool,
+    pub size_bytes: Option<u64>,
+    pub readonly: bool,
+    /// Файл не похож на обычную БД SQLite: скорее всего, зашифрован
+    pub encrypted: bool,
+    /// В каталог можно писать: SQLite создаёт рядом файлы журнала
+    pub directory_writable: bool,
+    /// Файлы журнала (-wal, -shm, -journal), лежащие рядом с БД
+    pub journal_files: Vec<String>,
+}
+
+/// Рабочее место, которое недавно отмечалось в client_sessions и может держать блокировку
+#[derive(Debug, Serialize, Clone, FromRow)]
+pub struct LockHolder {
+    pub hostname: String,
+    pub os_username: String,
+    pub last_seen: String,
+}
+
+#[derive(Debug, Serialize, Clone, FromRow)]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrphanedReference {
+    pub table: String,
+    pub column: String,
+    pub target: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TableRowCount {
+    pub table: String,
+    pub rows: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DiagnosticReport {
+    pub path: String,
+    pub file: FileStatus,
+    pub storage: StorageInfo,
+    /// Ошибка открытия файла, если открыть его не удалось
+    pub open_error: Option<String>,
+    /// Запись заблокирована другим процессом
+    pub write_locked: bool,
+    pub lock_holders: Vec<LockHolder>,
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    pub schema_version: Option<i64>,
+    pub supported_schema_version: i64,
+    pub orphaned_references: Vec<OrphanedReference>,
+    pub row_counts: Vec<TableRowCount>,
+    /// Найденные проблемы простыми словами
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RepairAction {
+    pub description: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RepairReport {
+    /// Копия файла до исправления
+    pub backup_path: String,
+    pub actions: Vec<RepairAction>,
+    /// Состояние после исправления
+    pub report: DiagnosticReport,
+}
+
+/// Проверяет файл БД, не изменяя его. Работает и тогда, когда подключиться к БД не удаётся.
+pub async fn diagnose(db_path: &str, key: Option<&str>) -> DiagnosticReport {
+    let path = Path::new(db_path);
+    let mut report = DiagnosticReport {
+        path: db_path.to_string(),
+        file: file_status(path),
+        storage: storage::detect_storage(db_path),
+        open_error: None,
+        write_locked: false,
+        lock_holders: Vec::new(),
+        integrity_errors: Vec::new(),
+        foreign_key_violations: Vec::new(),
+        schema_version: None,
+        supported_schema_version: SCHEMA_VERSION,
+        orphaned_references: Vec::new(),
+        row_counts: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    if !report.file.exists {
+        report.problems.push("Database file does not exist".to_string());
+        return report;
+    }
+    if report.file.readonly {
+        report.problems.push("Database file is read-only".to_string());
+    }
+    if !report.file.directory_writable {
+        report.problems.push("Database directory is not writable; SQLite cannot create journal files".to_string());
+    }
+
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .busy_timeout(Duration::from_secs(2));
+    let options = encryption::with_key(options, key);
+    let mut conn = match SqliteConnection::connect_with(&options).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            report.problems.push(format!("Cannot open database: {}", e));
+            report.open_error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    if let Err(e) = inspect(&mut conn, &mut report).await {
+        if encryption::is_wrong_key_error(&e) && report.file.encrypted {
+            report.problems.push("Database is encrypted and cannot be read with the supplied key".to_string());
+        } else {
+            report.problems.push(format!("Cannot read database: {}", e));
+        }
+        report.open_error = Some(e.to_string());
+    }
+    let _ = conn.close().await;
+
+    if !report.file.readonly && report.open_error.is_none() {
+        report.write_locked = is_write_locked(path, key).await;
+        if report.write_locked {
+            report.problems.push("Database is locked for writing by another process".to_string());
+        }
+    }
+
+    report
+}
+
+fn file_status(path: &Path) -> FileStatus {
+    let metadata = std::fs::metadata(path).ok();
+    let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+
+    let journal_files = ["-wal", "-shm", "-journal"]
+        .iter()
+        .map(|suffix| sidecar_path(path, suffix))
+        .filter(|p| p.exists())
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    FileStatus {
+        exists: metadata.is_some(),
+        size_bytes: metadata.as_ref().map(|m| m.len()),
+        readonly: metadata.as_ref().is_some_and(|m| m.permissions().readonly()),
+        encrypted: encryption::is_encrypted_file(path),
+        directory_writable: std::fs::metadata(directory).is_ok_and(|m| !m.permissions().readonly()),
+        journal_files,
+    }
+}
+
+fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+async fn inspect(conn: &mut SqliteConnection, report: &mut DiagnosticReport) -> Result<(), sqlx::Error> {
+    let tables = table_names(conn).await?;
+
+    let integrity: Vec<(String,)> = sqlx::query_as("PRAGMA integrity_check").fetch_all(&mut *conn).await?;
+    report.integrity_errors = integrity
+        .into_iter()
+        .map(|(row,)| row)
+        .filter(|row| row != "ok")
+        .collect();
+    if !report.integrity_errors.is_empty() {
+        report.problems.push(format!("Integrity check found {} problem(s)", report.integrity_errors.len()));
+    }
+
+    report.foreign_key_violations = sqlx::query_as::<_, ForeignKeyViolation>(
+        r#"SELECT "table", rowid, parent FROM pragma_foreign_key_check"#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    if !report.foreign_key_violations.is_empty() {
+        report.problems.push(format!("{} foreign key violation(s)", report.foreign_key_violations.len()));
+    }
+
+    let (version,): (i64,) = sqlx::query_as("PRAGMA user_version").fetch_one(&mut *conn).await?;
+    report.schema_version = Some(version);
+    if version > SCHEMA_VERSION {
+        report.problems.push(format!(
+            "Schema version {} is newer than supported {}; update the application",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    for table in &tables {
+        let (rows,): (i64,) = sqlx::query_as(&format!(r#"SELECT COUNT(*) FROM "{}""#, table))
+            .fetch_one(&mut *conn)
+            .await?;
+        report.row_counts.push(TableRowCount { table: table.clone(), rows });
+    }
+
+    for reference in existing_references(conn, &tables).await? {
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {} WHERE {}", reference.table, orphan_condition(reference)))
+            .fetch_one(&mut *conn)
+            .await?;
+        if count > 0 {
+            report.problems.push(format!(
+                "{} row(s) in {}.{} reference missing {}",
+                count, reference.table, reference.column, reference.target
+            ));
+            report.orphaned_references.push(OrphanedReference {
+                table: reference.table.to_string(),
+                column: reference.column.to_string(),
+                target: reference.target.to_string(),
+                count,
+            });
+        }
+    }
+
+    if tables.iter().any(|t| t == "client_sessions") {
+        report.lock_holders = sqlx::query_as::<_, LockHolder>(
+            "SELECT hostname, os_username, last_seen FROM client_sessions WHERE last_seen >= datetime('now', ?1) ORDER BY last_seen DESC",
+        )
+        .bind(format!("-{} seconds", SESSION_TIMEOUT_SECONDS))
+        .fetch_all(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn table_names(conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(tables.into_iter().map(|(name,)| name).collect())
+}
+
+/// Ссылки, для которых в файле есть и таблица с колонкой, и целевая таблица
+async fn existing_references(conn: &mut SqliteConnection, tables: &[String]) -> Result<Vec<&'static Reference>, sqlx::Error> {
+    let mut existing = Vec::new();
+    for reference in REFERENCES {
+        if !tables.iter().any(|t| t == reference.table) || !tables.iter().any(|t| t == reference.target) {
+            continue;
+        }
+        let (has_column,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        )
+        .bind(reference.table)
+        .bind(reference.column)
+        .fetch_one(&mut *conn)
+        .await?;
+        if has_column {
+            existing.push(reference);
+        }
+    }
+    Ok(existing)
+}
+
+fn orphan_condition(reference: &Reference) -> String {
+    format!(
+        "{column} IS NOT NULL AND {column} NOT IN (SELECT id FROM {target})",
+        column = reference.column,
+        target = reference.target,
+    )
+}
+
+/// Пробует взять блокировку на запись, не дожидаясь её освобождения
+async fn is_write_locked(path: &Path, key: Option<&str>) -> bool {
+    is_locked(path, key, "BEGIN IMMEDIATE").await
+}
+
+/// Файл сейчас читает или изменяет другое подключение. BEGIN EXCLUSIVE
+/// замечает и клиентов только для чтения, не оставляющих записей в client_sessions.
+pub async fn is_in_use(path: &Path, key: Option<&str>) -> bool {
+    is_locked(path, key, "BEGIN EXCLUSIVE").await
+}
+
+async fn is_locked(path: &Path, key: Option<&str>, begin: &str) -> bool {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .busy_timeout(Duration::ZERO);
+    let options = encryption::with_key(options, key);
+    let Ok(mut conn) = SqliteConnection::connect_with(&options).await else {
+        return false;
+    };
+
+    let locked = match sqlx::query(begin).execute(&mut conn).await {
+        Ok(_) => {
+            let _ = sqlx::query("ROLLBACK").execute(&mut conn).await;
+            false
+        }
+        Err(e) => storage::is_busy_error(&e),
+    };
+    let _ = conn.close().await;
+    locked
+}
+
+/// Исправляет безопасные случаи: висячие ссылки, просроченные резервы номеров,
+/// устаревшие сессии и повреждённые индексы. Перед изменениями снимается копия файла.
+/// Файл не должен использоваться другими клиентами.
+pub async fn repair(db_path: &str, key: Option<&str>) -> AppResult<RepairReport> {
+    let before = diagnose(db_path, key).await;
+    if let Some(error) = &before.open_error {
+        return Err(AppError::conflict(format!("Database cannot be opened and has to be restored from a backup: {}", error)));
+    }
+    if before.file.readonly {
+        return Err(AppError::conflict("Database file is read-only"));
+    }
+    if before.write_locked || !before.lock_holders.is_empty() {
+        let holders: Vec<String> = before.lock_holders.iter().map(|h| h.hostname.clone()).collect();
+        return Err(AppError::conflict_with(
+            "Database is in use; close the application on other workstations before repairing",
+            &holders,
+        ));
+    }
+
+    let path = Path::new(db_path);
+    let backup_path = backup::default_backup_directory(db_path).join(backup::pre_repair_file_name(db_path));
+    copy_before_repair(path, &backup_path)?;
+
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .busy_timeout(Duration::from_secs(5));
+    let options = encryption::with_key(options, key);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| AppError::from_sqlx("Failed to open database for repair", e))?;
+
+    let result = apply_repairs(&mut conn).await;
+    let _ = conn.close().await;
+    let actions = result.map_err(|e| AppError::from_sqlx("Failed to repair database", e))?;
+
+    let report = diagnose(db_path, key).await;
+    if !report.integrity_errors.is_empty() {
+        return Err(AppError::conflict_with(
+            "Integrity problems remain after repair; restore the database from a backup",
+            &report.integrity_errors,
+        ));
+    }
+
+    Ok(RepairReport {
+        backup_path: backup_path.to_string_lossy().to_string(),
+        actions,
+        report,
+    })
+}
+
+/// Копируется сам файл, а не VACUUM INTO: повреждённую БД VACUUM может не прочитать.
+/// Журнал WAL копируется вместе с файлом, иначе копия потеряет последние изменения.
+fn copy_before_repair(path: &Path, dest: &Path) -> AppResult<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::db(format!("Failed to create backup directory: {}", e)))?;
+    }
+    std::fs::copy(path, dest)
+        .map_err(|e| AppError::db(format!("Failed to back up database before repair: {}", e)))?;
+
+    let wal = sidecar_path(path, "-wal");
+    if wal.exists() {
+        std::fs::copy(&wal, sidecar_path(dest, "-wal"))
+            .map_err(|e| AppError::db(format!("Failed to back up database journal before repair: {}", e)))?;
+    }
+    Ok(())
+}
+
+async fn apply_repairs(conn: &mut SqliteConnection) -> Result<Vec<RepairAction>, sqlx::Error> {
+    let mut actions = Vec::new();
+
+    // Индексы пересобираются из данных таблиц; это исправляет большинство ошибок integrity_check
+    sqlx::query("REINDEX").execute(&mut *conn).await?;
+    actions.push(RepairAction { description: "Rebuilt indexes".to_string(), rows: 0 });
+
+    let tables = table_names(conn).await?;
+    let references = existing_references(conn, &tables).await?;
+
+    let mut tx = conn.begin().await?;
+
+    for reference in references {
+        let (sql, description) = if reference.delete_row {
+            (
+                format!("DELETE FROM {} WHERE {}", reference.table, orphan_condition(reference)),
+                format!("Deleted {} rows referencing missing {}", reference.table, reference.target),
+            )
+        } else {
+            (
+                format!("UPDATE {} SET {} = NULL WHERE {}", reference.table, reference.column, orphan_condition(reference)),
+                format!("Cleared {}.{} referencing missing {}", reference.table, reference.column, reference.target),
+            )
+        };
+
+        let rows = sqlx::query(&sql).execute(&mut *tx).await?.rows_affected();
+        if rows > 0 {
+            actions.push(RepairAction { description, rows });
+        }
+    }
+
+    if tables.iter().any(|t| t == "card_number_reservations") {
+        let rows = sqlx::query("DELETE FROM card_number_reservations WHERE expires_at <= datetime('now')")
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if rows > 0 {
+            actions.push(RepairAction { description: "Removed expired card number reservations".to_string(), rows });
+        }
+    }
+
+    if tables.iter().any(|t| t == "client_sessions") {
+        let rows = sqlx::query("DELETE FROM client_sessions WHERE last_seen < datetime('now', ?1)")
+            .bind(format!("-{} seconds", SESSION_TIMEOUT_SECONDS))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if rows > 0 {
+            actions.push(RepairAction { description: "Removed stale client sessions".to_string(), rows });
+        }
+    }
+
+    tx.commit().await?;
+    Ok(actions)
+}
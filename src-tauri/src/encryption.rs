@@ -0,0 +1,74 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use std::io::Read;
+use std::path::Path;
+
+/// Заголовок незашифрованного файла SQLite. Зашифрованный SQLCipher файл
+/// начинается со случайной соли, поэтому по заголовку их можно различить.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// SQLITE_NOTADB: файл зашифрован, ключ неверный или это вовсе не БД
+const SQLITE_NOTADB: i32 = 26;
+
+/// Строковый литерал SQL для PRAGMA key / ATTACH ... KEY
+pub fn quote_key(key: &str) -> String {
+    format!("'{}'", key.replace('\'', "''"))
+}
+
+/// Пустой ключ означает незашифрованную БД
+pub fn normalize_key(key: Option<String>) -> Option<String> {
+    key.filter(|k| !k.is_empty())
+}
+
+/// Добавляет ключ шифрования. PRAGMA key должна выполняться первой,
+/// sqlx ставит её перед остальными настройками соединения.
+pub fn with_key(options: SqliteConnectOptions, key: Option<&str>) -> SqliteConnectOptions {
+    match key {
+        Some(key) => options.pragma("key", quote_key(key)),
+        None => options,
+    }
+}
+
+/// Файл существует, не пуст и не похож на обычную БД SQLite
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut header = [0u8; 16];
+    match file.read_exact(&mut header) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+pub fn is_wrong_key_error(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db_error) = error else {
+        return false;
+    };
+
+    db_error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| code & 0xff == SQLITE_NOTADB)
+}
+
+/// Копирует БД соединения в новый файл с другим ключом (None — без шифрования).
+/// sqlcipher_export переносит схему и данные, но не user_version.
+pub async fn export_with_key(conn: &mut SqliteConnection, dest: &Path, key: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("ATTACH DATABASE ?1 AS rekeyed KEY {}", quote_key(key.unwrap_or(""))))
+        .bind(dest.to_string_lossy().to_string())
+        .execute(&mut *conn)
+        .await?;
+
+    let exported = async {
+        sqlx::query("SELECT sqlcipher_export('rekeyed')").execute(&mut *conn).await?;
+        let (version,): (i64,) = sqlx::query_as("PRAGMA main.user_version").fetch_one(&mut *conn).await?;
+        sqlx::query(&format!("PRAGMA rekeyed.user_version = {}", version))
+            .execute(&mut *conn)
+            .await?;
+        Ok::<_, sqlx::Error>(())
+    }
+    .await;
+
+    sqlx::query("DETACH DATABASE rekeyed").execute(&mut *conn).await?;
+    exported
+}
@@ -0,0 +1,136 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// Ошибка, возвращаемая командами. Сериализуется в `{ code, message, details }`:
+/// `code` стабилен и используется фронтендом для локализации, `message` — текст по умолчанию.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    NotConnected,
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Validation { field: String, message: String },
+    /// details — текущее состояние объекта на сервере, если оно известно
+    Conflict { message: String, details: Option<serde_json::Value> },
+    /// БД открыта только для чтения
+    ReadOnly,
+    Db(String),
+}
+
+/// SQLITE_READONLY, в том числе расширенные коды
+const SQLITE_READONLY: i32 = 8;
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        AppError::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        AppError::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(message.into())
+    }
+
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        AppError::Validation {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn conflict_with<T: Serialize>(message: impl Into<String>, current: &T) -> Self {
+        AppError::Conflict {
+            message: message.into(),
+            details: serde_json::to_value(current).ok(),
+        }
+    }
+
+    pub fn db(message: impl Into<String>) -> Self {
+        AppError::Db(message.into())
+    }
+
+    /// Ошибка sqlx с пояснением, в какой операции она произошла.
+    /// Нарушение уникальности превращается в Conflict, отсутствие строки — в NotFound,
+    /// запись в открытую только для чтения БД — в ReadOnly.
+    pub fn from_sqlx(context: &str, error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => AppError::not_found(format!("{}: not found", context)),
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                AppError::conflict(format!("{}: {}", context, db_error.message()))
+            }
+            sqlx::Error::Database(db_error)
+                if db_error.code().and_then(|code| code.parse::<i32>().ok()).is_some_and(|code| code & 0xff == SQLITE_READONLY) =>
+            {
+                AppError::ReadOnly
+            }
+            _ => AppError::db(format!("{}: {}", context, error)),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotConnected => "not_connected",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation { .. } => "validation",
+            AppError::Conflict { .. } => "conflict",
+            AppError::ReadOnly => "read_only",
+            AppError::Db(_) => "db",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotConnected => "Database not connected",
+            AppError::ReadOnly => "Database is opened read-only, changes are not allowed",
+            AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Validation { message, .. }
+            | AppError::Conflict { message, .. }
+            | AppError::Db(message) => message,
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::Validation { field, .. } => Some(serde_json::json!({ "field": field })),
+            AppError::Conflict { details, .. } => details.clone().map(|current| serde_json::json!({ "current": current })),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Validation { field, message } => write!(f, "{} ({})", message, field),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use crate::state::AppState;
+use crate::tray;
+
+pub const CARD_CREATED: &str = "card-created";
+pub const CARD_UPDATED: &str = "card-updated";
+pub const CARD_DELETED: &str = "card-deleted";
+
+/// Как часто проверяется журнал изменений, сделанных другими клиентами
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Данные события. Саму карточку окно запрашивает отдельно своим токеном,
+/// чтобы не раскрывать карточки пользователям без доступа к ним.
+#[derive(Debug, Serialize, Clone)]
+pub struct CardChangeEvent {
+    pub id: i64,
+    pub seq: i64,
+    pub changed_at: String,
+}
+
+/// Рассылает во все окна события по новым записям журнала изменений
+pub async fn publish_changes<R: Runtime>(app: &AppHandle<R>, state: &AppState) {
+    let changes = match state.take_changes().await {
+        Ok(changes) => changes,
+        Err(e) => {
+            eprintln!("Failed to read change log: {}", e);
+            return;
+        }
+    };
+
+    let cards_changed = changes.iter().any(|change| change.entity == "control_card");
+    for change in changes {
+        let event = match (change.entity.as_str(), change.action.as_str()) {
+            ("control_card", "created") => CARD_CREATED,
+            ("control_card", "updated") => CARD_UPDATED,
+            ("control_card", "deleted") => CARD_DELETED,
+            _ => continue,
+        };
+
+        let payload = CardChangeEvent {
+            id: change.entity_id,
+            seq: change.seq,
+            changed_at: change.changed_at,
+        };
+        if let Err(e) = app.emit(event, payload) {
+            eprintln!("Failed to emit {}: {}", event, e);
+        }
+    }
+
+    if cards_changed {
+        tray::refresh(app).await;
+    }
+}
+
+/// Фоновая проверка журнала: так окна узнают об изменениях других рабочих мест
+pub fn spawn_change_watcher<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            publish_changes(&app, &state).await;
+        }
+    });
+}
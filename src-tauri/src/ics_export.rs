@@ -0,0 +1,194 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use crate::database::ControlCard;
+use crate::error::{AppError, AppResult};
+
+/// Вид записей календаря: событие в день срока или задача со сроком
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IcsKind {
+    #[default]
+    Event,
+    Todo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct IcsOptions {
+    #[serde(default)]
+    pub kind: IcsKind,
+    /// За сколько дней до срока напомнить; None или 0 — без напоминания
+    #[serde(default)]
+    pub alarm_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IcsExportInfo {
+    pub path: String,
+    pub entries: usize,
+    /// Карточки без срока исполнения в календарь не попадают
+    pub skipped: usize,
+}
+
+/// Записывает карточки в iCalendar-файл.
+/// UID строится из id карточки, а SEQUENCE — из её версии, поэтому повторная выгрузка
+/// в тот же календарь обновляет записи, а не дублирует их. На такой файл можно подписаться.
+pub fn write_calendar(path: &Path, cards: &[ControlCard], options: &IcsOptions, now: DateTime<Utc>) -> AppResult<IcsExportInfo> {
+    if path.as_os_str().is_empty() {
+        return Err(AppError::validation("path", "Export path is required"));
+    }
+
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//CalendarControlCard//Control cards//RU");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, "METHOD:PUBLISH");
+    line(&mut out, &format!("X-WR-CALNAME:{}", escape("Контрольные карточки")));
+
+    let mut entries = 0;
+    for card in cards {
+        if write_card(&mut out, card, options, now) {
+            entries += 1;
+        }
+    }
+    line(&mut out, "END:VCALENDAR");
+
+    // Подписанный клиент не должен прочитать файл наполовину записанным
+    let temp = path.with_extension("ics.tmp");
+    std::fs::write(&temp, out)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .map_err(|e| AppError::db(format!("Failed to write iCalendar file: {}", e)))?;
+
+    Ok(IcsExportInfo {
+        path: path.to_string_lossy().to_string(),
+        entries,
+        skipped: cards.len() - entries,
+    })
+}
+
+fn write_card(out: &mut String, card: &ControlCard, options: &IcsOptions, now: DateTime<Utc>) -> bool {
+    let dates = card.dates();
+    let Some(deadline) = dates.effective_deadline() else {
+        return false;
+    };
+
+    let number = card
+        .formatted_number
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}", card.card_number, card.year));
+    let component = match options.kind {
+        IcsKind::Event => "VEVENT",
+        IcsKind::Todo => "VTODO",
+    };
+
+    line(out, &format!("BEGIN:{}", component));
+    // created_at отличает карточки с одинаковым id из разных БД
+    line(out, &format!("UID:card-{}-{}@calendarcontrolcard", card.id, card.created_at.timestamp()));
+    line(out, &format!("DTSTAMP:{}", format_utc(now)));
+    line(out, &format!("LAST-MODIFIED:{}", format_utc(card.updated_at.unwrap_or(card.created_at))));
+    line(out, &format!("SEQUENCE:{}", card.version.max(0)));
+    line(out, &format!("SUMMARY:{}", escape(&format!("№{}: {}", number, card.summary))));
+    line(out, &format!("DESCRIPTION:{}", escape(&description(card))));
+    if let Some(department) = card.department.as_deref().filter(|d| !d.trim().is_empty()) {
+        line(out, &format!("CATEGORIES:{}", escape(department.trim())));
+    }
+
+    match options.kind {
+        IcsKind::Event => {
+            line(out, &format!("DTSTART;VALUE=DATE:{}", format_date(deadline)));
+            line(out, &format!("DTEND;VALUE=DATE:{}", format_date(deadline + chrono::Duration::days(1))));
+            line(out, "TRANSP:TRANSPARENT");
+        }
+        IcsKind::Todo => {
+            line(out, &format!("DTSTART;VALUE=DATE:{}", format_date(deadline)));
+            line(out, &format!("DUE;VALUE=DATE:{}", format_date(deadline)));
+            match dates.completed_at {
+                Some(completed) => {
+                    line(out, "STATUS:COMPLETED");
+                    line(out, &format!("COMPLETED:{}T000000Z", format_date(completed)));
+                }
+                None => line(out, "STATUS:NEEDS-ACTION"),
+            }
+        }
+    }
+
+    // Периодическая карточка повторяется с заданной частотой до отметки об исполнении
+    if let Some(freq) = card.execution_period_type.as_deref().and_then(rrule_freq) {
+        match dates.completed_at {
+            Some(completed) => line(out, &format!("RRULE:FREQ={};UNTIL={}", freq, format_date(completed))),
+            None => line(out, &format!("RRULE:FREQ={}", freq)),
+        }
+    }
+
+    if let Some(days) = options.alarm_days.filter(|d| *d > 0) {
+        if dates.completed_at.is_none() {
+            line(out, "BEGIN:VALARM");
+            line(out, "ACTION:DISPLAY");
+            line(out, &format!("TRIGGER:-P{}D", days));
+            line(out, &format!("DESCRIPTION:{}", escape(&format!("Срок по карточке №{}", number))));
+            line(out, "END:VALARM");
+        }
+    }
+
+    line(out, &format!("END:{}", component));
+    true
+}
+
+fn description(card: &ControlCard) -> String {
+    let mut text = format!("Исполнитель: {}\nДокумент: {}", card.executor, card.document_reference);
+    if let Some(extended) = card.extended_deadline {
+        if let Some(original) = card.execution_deadline {
+            let _ = write!(text, "\nСрок продлён с {} до {}", original.format("%d.%m.%Y"), extended.format("%d.%m.%Y"));
+        }
+    }
+    if let Some(resolution) = card.resolution.as_deref().filter(|r| !r.is_empty()) {
+        let _ = write!(text, "\nРезолюция: {}", resolution);
+    }
+    if let Some(completed) = card.completed_at {
+        let _ = write!(text, "\nИсполнена {}", completed.format("%d.%m.%Y"));
+    }
+    text
+}
+
+fn rrule_freq(period_type: &str) -> Option<&'static str> {
+    match period_type {
+        "daily" => Some("DAILY"),
+        "weekly" => Some("WEEKLY"),
+        "monthly" => Some("MONTHLY"),
+        _ => None,
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn format_utc(value: DateTime<Utc>) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Экранирование текстовых значений по RFC 5545
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Строки длиннее 75 октетов переносятся; многобайтовые символы не разрываются
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for ch in content.chars() {
+        if width + ch.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(ch);
+        width += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
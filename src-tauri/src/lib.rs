@@ -1,66 +1,229 @@
 mod database;
 mod models;
 mod auth;
+mod error;
+mod deadlines;
+mod numbering;
+mod state;
+mod storage;
+mod events;
+mod archive;
+mod backup;
+mod diagnostics;
+mod encryption;
+mod profiles;
+mod csv_import;
+mod operations;
+mod api;
+mod xlsx_export;
+mod ics_export;
+mod webhooks;
+mod reminders;
+mod notifications;
+mod tray;
+
+use database::{ClientSession, ConnectionInfo, DeletedCard, ControlCard, CardTemplate, CardTemplateInput, CardNumberReservation, CardHistoryEntry, DateMigrationIssue};
+use models::{CardFilter, CardInput, User, TemplateCardOverrides, ControlCardPatch};
+use numbering::{NumberingScheme, NumberingSchemeInput};
+use deadlines::CardDates;
+use archive::{ArchiveDb, ArchiveInfo};
+use backup::{BackupInfo, BackupSettings};
+use csv_import::{CsvMapping, ImportReport};
+use diagnostics::{DiagnosticReport, RepairReport};
+use xlsx_export::{RegisterColumn, XlsxExportInfo};
+use ics_export::{IcsExportInfo, IcsOptions};
+use error::{AppError, AppResult};
+use auth::{hash_password, generate_token};
+use operations::{check_card_access, check_controller, resolve_executor, visible_cards};
+use chrono::Datelike;
+use state::AppState;
+use storage::ConnectionMode;
+use profiles::{DatabaseProfile, ProfileList, ProfileRegistry, ProfileSwitch};
+use api::{ApiServer, ApiSettings, ApiStatus};
+use webhooks::{Webhook, WebhookDelivery, WebhookInput};
+use reminders::SmtpSettings;
+use notifications::{NotificationCenter, NotificationSettings};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+impl<R: Runtime> api::StateProvider for AppHandle<R> {
+    fn app_state(&self) -> &AppState {
+        self.state::<AppState>().inner()
+    }
+}
 
-use database::{Database, ControlCard};
-use models::User;
-use auth::{hash_password, verify_password, generate_token, verify_token};
-use std::sync::Mutex;
-use once_cell::sync::Lazy;
+/// mode — многопользовательский режим для БД в общей папке; по умолчанию single
+/// key — ключ шифрования; для нового файла с ключом создаётся зашифрованная БД
+/// read_only — открыть существующую БД только для чтения, без обновления схемы
+#[tauri::command]
+async fn connect_database(
+    state: State<'_, AppState>,
+    profiles: State<'_, ProfileRegistry>,
+    db_path: String,
+    mode: Option<ConnectionMode>,
+    key: Option<String>,
+    read_only: Option<bool>,
+) -> AppResult<ConnectionInfo> {
+    let key = encryption::normalize_key(key);
+    let info = state
+        .connect(&db_path, mode.unwrap_or_default(), key.as_deref(), read_only.unwrap_or(false))
+        .await?;
+    profiles.activate_path(&db_path).await?;
+    Ok(info)
+}
 
-static DB: Lazy<Mutex<Database>> = Lazy::new(|| Mutex::new(Database::new()));
+#[tauri::command]
+async fn list_profiles(profiles: State<'_, ProfileRegistry>) -> AppResult<ProfileList> {
+    Ok(profiles.list().await)
+}
 
-fn with_db<F, T>(f: F) -> Result<T, String>
-where
-    F: FnOnce(&mut Database) -> Result<T, String>,
-{
-    let mut db = DB.lock().map_err(|e| e.to_string())?;
-    let result = f(&mut db);
-    result
+#[tauri::command]
+async fn add_profile(
+    state: State<'_, AppState>,
+    profiles: State<'_, ProfileRegistry>,
+    name: String,
+    path: String,
+    read_only: Option<bool>,
+    mode: Option<ConnectionMode>,
+) -> AppResult<DatabaseProfile> {
+    let profile = profiles.add(&name, &path, read_only.unwrap_or(false), mode.unwrap_or_default()).await?;
+
+    // Профиль для уже открытой БД сразу становится активным
+    if let Some(current) = state.path().await {
+        profiles.activate_path(&current).await?;
+    }
+    Ok(profile)
 }
 
-fn with_db_immut<F, T>(f: F) -> Result<T, String>
-where
-    F: FnOnce(&Database) -> Result<T, String>,
-{
-    let db = DB.lock().map_err(|e| e.to_string())?;
-    f(&db)
+#[tauri::command]
+async fn remove_profile(profiles: State<'_, ProfileRegistry>, id: String) -> AppResult<bool> {
+    profiles.remove(&id).await
 }
 
+/// Подключается к БД профиля. Если под профилем уже входили, возвращается сохранённый токен.
 #[tauri::command]
-fn connect_database(db_path: String) -> Result<(), String> {
-    with_db(|db| {
-        db.connect(&db_path)?;
-        Ok(())
-    })
+async fn switch_profile(
+    state: State<'_, AppState>,
+    profiles: State<'_, ProfileRegistry>,
+    id: String,
+    key: Option<String>,
+) -> AppResult<ProfileSwitch> {
+    let profile = profiles.get(&id).await?;
+    let key = encryption::normalize_key(key);
+    let connection = state
+        .connect(&profile.path, profile.mode, key.as_deref(), profile.read_only)
+        .await?;
+    let (profile, session_token) = profiles.activate(&id).await?;
+    // Истёкший или выданный для другой БД токен не восстанавливает вход
+    let session_token = session_token.filter(|token| auth::verify_token(token, &connection.path).is_ok());
+
+    Ok(ProfileSwitch { profile, connection, session_token })
 }
 
+/// Выход пользователя: сохранённый для профиля токен больше не нужен
 #[tauri::command]
-fn disconnect_database() -> Result<(), String> {
-    with_db(|db| {
-        db.disconnect();
-        Ok(())
-    })
+async fn forget_profile_session(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    profiles: State<'_, ProfileRegistry>,
+) -> AppResult<()> {
+    state.sign_out().await;
+    tray::refresh(&app).await;
+    profiles.forget_session().await
 }
 
 #[tauri::command]
-fn get_database_path() -> Result<Option<String>, String> {
-    with_db_immut(|db| Ok(db.get_path().cloned()))
+async fn disconnect_database(state: State<'_, AppState>) -> AppResult<()> {
+    state.disconnect().await;
+    Ok(())
 }
 
 #[tauri::command]
-fn is_database_connected() -> Result<bool, String> {
-    with_db_immut(|db| Ok(db.is_connected()))
+async fn get_database_path(state: State<'_, AppState>) -> AppResult<Option<String>> {
+    Ok(state.path().await)
 }
 
 #[tauri::command]
-fn get_next_card_number(year: i32) -> Result<i32, String> {
-    with_db_immut(|db| db.get_next_card_number(year))
+async fn is_database_connected(state: State<'_, AppState>) -> AppResult<bool> {
+    Ok(state.is_connected().await)
 }
 
+/// Проверка файла БД без подключения к нему; помогает разобраться, почему не удаётся подключиться
 #[tauri::command]
-fn create_control_card(
-    card_number: i32,
+async fn diagnose_database(db_path: String, key: Option<String>) -> AppResult<DiagnosticReport> {
+    if db_path.trim().is_empty() {
+        return Err(AppError::validation("db_path", "Database path is required"));
+    }
+
+    let key = encryption::normalize_key(key);
+    Ok(diagnostics::diagnose(db_path.trim(), key.as_deref()).await)
+}
+
+/// Исправляет безопасные случаи, найденные диагностикой. Файл не должен быть подключён
+/// ни в этом приложении, ни на других рабочих местах; перед исправлением снимается копия.
+#[tauri::command]
+async fn repair_database(state: State<'_, AppState>, db_path: String, key: Option<String>) -> AppResult<RepairReport> {
+    let db_path = db_path.trim();
+    if db_path.is_empty() {
+        return Err(AppError::validation("db_path", "Database path is required"));
+    }
+
+    if state.path().await.as_deref() == Some(db_path) {
+        return Err(AppError::conflict("Disconnect from the database before repairing it"));
+    }
+
+    let key = encryption::normalize_key(key);
+    diagnostics::repair(db_path, key.as_deref()).await
+}
+
+#[tauri::command]
+async fn get_connection_info(state: State<'_, AppState>) -> AppResult<ConnectionInfo> {
+    let db = state.db().await?;
+    Ok(db.connection_info().clone())
+}
+
+/// Рабочие места, работающие с той же БД
+#[tauri::command]
+async fn get_client_sessions(state: State<'_, AppState>, token: String) -> AppResult<Vec<ClientSession>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can view connected clients"));
+    }
+
+    let db = state.db().await?;
+    db.get_client_sessions().await
+}
+
+#[tauri::command]
+async fn get_next_card_number(state: State<'_, AppState>, year: i32) -> AppResult<i32> {
+    let db = state.db().await?;
+    db.get_next_card_number(year).await
+}
+
+#[tauri::command]
+async fn reserve_card_number(state: State<'_, AppState>, year: i32, token: String) -> AppResult<CardNumberReservation> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can reserve card numbers"));
+    }
+
+    let db = state.writable_db().await?;
+    db.reserve_card_number(year, claims.sub).await
+}
+
+#[tauri::command]
+async fn release_card_number(state: State<'_, AppState>, year: i32, card_number: i32, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.writable_db().await?;
+    db.release_card_number(year, card_number, claims.sub).await
+}
+
+/// Если card_number не указан, номер выделяется сервером атомарно при вставке
+#[tauri::command]
+async fn create_control_card(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    card_number: Option<i32>,
     year: i32,
     executor_user_id: i64,
     reporter: String,
@@ -74,106 +237,126 @@ fn create_control_card(
     department: Option<String>,
     controller: Option<String>,
     controller_user_id: Option<i64>,
+    numbering_scheme_id: Option<i64>,
     token: String,
-) -> Result<i64, String> {
-    let claims = verify_token(&token)?;
-    let user_id = claims.sub;
-    let user_role = claims.role;
+) -> AppResult<i64> {
+    let claims = state.verify_token(&token).await?;
+    let input = CardInput {
+        card_number,
+        year,
+        executor_user_id,
+        reporter,
+        summary,
+        document_reference,
+        return_to,
+        execution_deadline,
+        execution_period_type,
+        extended_deadline,
+        resolution,
+        department,
+        controller,
+        controller_user_id,
+        numbering_scheme_id,
+    };
+
+    let result = operations::create_card(&state, &claims, &input).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(result)
+}
 
-    // Проверка прав: только admin или controller могут создавать карточки
-    if user_role != "admin" && user_role != "controller" {
-        return Err("Only admin or controller can create control cards".to_string());
+/// Импорт карточек из CSV. При dry_run строки вставляются и откатываются, поэтому
+/// проверяются и конфликты номеров. Если есть ошибки, ничего не сохраняется,
+/// кроме явного частичного импорта (mapping.partial).
+#[tauri::command]
+async fn import_cards_csv(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    mapping: CsvMapping,
+    dry_run: bool,
+    token: String,
+) -> AppResult<ImportReport> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can import control cards"));
     }
 
-    with_db_immut(|db| {
-        // Получаем username пользователя-исполнителя для отображения
-        let executor_user = db.get_user_by_id(executor_user_id)?;
-        
-        // Проверяем, что пользователь существует и имеет роль user
-        let executor_user = executor_user
-            .ok_or_else(|| "Executor user not found".to_string())?;
-        
-        if executor_user.role != "user" {
-            return Err("Executor must be a user with role 'user'".to_string());
-        }
+    // Проверить файл можно и в БД, открытой только для чтения
+    let db = if dry_run { state.db().await? } else { state.writable_db().await? };
+    let path = std::path::Path::new(path.trim());
+    let report = csv_import::import_file(&db, path, &mapping, claims.sub, dry_run, deadlines::today()).await?;
+    if report.committed {
+        events::publish_changes(&app, &state).await;
+    }
+    Ok(report)
+}
 
-        let executor = executor_user.username;
+#[tauri::command]
+async fn get_control_card(state: State<'_, AppState>, id: i64, token: String) -> AppResult<ControlCard> {
+    let claims = state.verify_token(&token).await?;
 
-        // Проверяем controller_user_id если указан
-        if let Some(controller_id) = controller_user_id {
-            let user = db.get_user_by_id(controller_id)?;
-            if let Some(ref u) = user {
-                if u.role != "controller" {
-                    return Err("Controller must be a user with role 'controller'".to_string());
-                }
-            }
-        }
+    let db = state.db().await?;
+    operations::get_card(&db, &claims, id).await
+}
 
-        let result = db.create_control_card(
-            card_number,
-            year,
-            &executor,
-            &reporter,
-            &summary,
-            &document_reference,
-            Some(user_id),
-            Some(executor_user_id),
-            return_to.as_deref(),
-            execution_deadline.as_deref(),
-            execution_period_type.as_deref(),
-            extended_deadline.as_deref(),
-            resolution.as_deref(),
-            department.as_deref(),
-            controller.as_deref(),
-            controller_user_id,
-        )?;
-        Ok(result)
-    })
-}
-
-#[tauri::command]
-fn get_control_card(id: i64, token: String) -> Result<ControlCard, String> {
-    let claims = verify_token(&token)?;
-    let user_id = claims.sub;
-    let user_role = claims.role;
+#[tauri::command]
+async fn get_all_control_cards(state: State<'_, AppState>, filter: Option<CardFilter>, token: String) -> AppResult<Vec<ControlCard>> {
+    let claims = state.verify_token(&token).await?;
 
-    with_db_immut(|db| {
-        let card = db.get_control_card(id)?;
-        
-        // Admin и controller видят все карточки
-        if user_role == "admin" || user_role == "controller" {
-            Ok(card)
-        } else {
-            // User видит только карточки, где он исполнитель
-            match card.executor_user_id {
-                Some(executor_id) if executor_id == user_id => Ok(card),
-                Some(_) => Err("Access denied: you can only view cards where you are the executor".to_string()),
-                None => Err("Card not found or has no executor assigned".to_string()),
-            }
-        }
-    })
+    let db = state.db().await?;
+    visible_cards(&db, &claims, &filter.unwrap_or_default()).await
 }
 
+/// Выгружает реестр отобранных карточек в XLSX. Пустой список колонок — набор по умолчанию.
 #[tauri::command]
-fn get_all_control_cards(token: String) -> Result<Vec<ControlCard>, String> {
-    let claims = verify_token(&token)?;
-    let user_id = claims.sub;
-    let user_role = claims.role;
+async fn export_cards_xlsx(
+    state: State<'_, AppState>,
+    filter: Option<CardFilter>,
+    columns: Option<Vec<RegisterColumn>>,
+    path: String,
+    token: String,
+) -> AppResult<XlsxExportInfo> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let cards = visible_cards(&db, &claims, &filter.unwrap_or_default()).await?;
+    xlsx_export::write_register(
+        std::path::Path::new(path.trim()),
+        &cards,
+        &columns.unwrap_or_default(),
+        deadlines::today(),
+    )
+}
 
-    with_db_immut(|db| {
-        // Admin и controller видят все карточки
-        if user_role == "admin" || user_role == "controller" {
-            db.get_all_control_cards()
-        } else {
-            // User видит только карточки, где он исполнитель
-            db.get_control_cards_by_executor_id(user_id)
-        }
-    })
+/// Выгружает сроки отобранных карточек в iCalendar. Повторная выгрузка обновляет записи в календаре.
+#[tauri::command]
+async fn export_cards_ics(
+    state: State<'_, AppState>,
+    filter: Option<CardFilter>,
+    options: Option<IcsOptions>,
+    path: String,
+    token: String,
+) -> AppResult<IcsExportInfo> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let cards = visible_cards(&db, &claims, &filter.unwrap_or_default()).await?;
+    ics_export::write_calendar(
+        std::path::Path::new(path.trim()),
+        &cards,
+        &options.unwrap_or_default(),
+        chrono::Utc::now(),
+    )
 }
 
+/// expected_version — версия карточки, которую редактировал пользователь.
+/// Если карточку успели изменить, возвращается конфликт с её текущим состоянием.
 #[tauri::command]
-fn update_control_card(
+async fn update_control_card(
+    app: AppHandle,
+    state: State<'_, AppState>,
     id: i64,
+    expected_version: i64,
     card_number: i32,
     year: i32,
     executor_user_id: i64,
@@ -189,93 +372,713 @@ fn update_control_card(
     controller: Option<String>,
     controller_user_id: Option<i64>,
     token: String,
-) -> Result<usize, String> {
-    let claims = verify_token(&token)?;
+) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
     let user_id = claims.sub;
     let user_role = claims.role;
 
     // Только admin или controller могут обновлять карточки
     if user_role != "admin" && user_role != "controller" {
-        return Err("Only admin or controller can update control cards".to_string());
+        return Err(AppError::forbidden("Only admin or controller can update control cards"));
     }
 
-    with_db_immut(|db| {
-        // Получаем username пользователя-исполнителя для отображения
-        let executor_user = db.get_user_by_id(executor_user_id)?;
-        
-        // Проверяем, что пользователь существует и имеет роль user
-        let executor_user = executor_user
-            .ok_or_else(|| "Executor user not found".to_string())?;
-        
-        if executor_user.role != "user" {
-            return Err("Executor must be a user with role 'user'".to_string());
-        }
+    let execution_deadline = deadlines::parse_optional_date("execution_deadline", execution_deadline.as_deref())?;
+    let extended_deadline = deadlines::parse_optional_date("extended_deadline", extended_deadline.as_deref())?;
+
+    let db = state.writable_db().await?;
+    let current = db.get_control_card(id).await?;
+    deadlines::validate_card_dates(
+        &CardDates { year, execution_deadline, extended_deadline, completed_at: current.completed_at },
+        deadlines::today(),
+        Some(&current.dates()),
+    )?;
+
+    let executor = resolve_executor(&db, executor_user_id).await?;
+    check_controller(&db, controller_user_id).await?;
+
+    let updated = db.update_control_card(
+        id,
+        card_number,
+        year,
+        &executor,
+        &reporter,
+        &summary,
+        &document_reference,
+        Some(user_id),
+        Some(executor_user_id),
+        return_to.as_deref(),
+        execution_deadline,
+        execution_period_type.as_deref(),
+        extended_deadline,
+        resolution.as_deref(),
+        department.as_deref(),
+        controller.as_deref(),
+        controller_user_id,
+        expected_version,
+    ).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(updated)
+}
 
-        let executor = executor_user.username;
+/// Частичное обновление: меняются только переданные поля.
+/// Исполнитель может менять в своих карточках лишь поля отчёта об исполнении.
+#[tauri::command]
+async fn patch_control_card(app: AppHandle, state: State<'_, AppState>, id: i64, patch: ControlCardPatch, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
 
-        // Проверяем controller_user_id если указан
-        if let Some(controller_id) = controller_user_id {
-            let user = db.get_user_by_id(controller_id)?;
-            if let Some(ref u) = user {
-                if u.role != "controller" {
-                    return Err("Controller must be a user with role 'controller'".to_string());
-                }
-            }
-        }
+    let changed = operations::patch_card(&state, &claims, id, &patch).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(changed)
+}
+
+#[tauri::command]
+async fn get_card_history(state: State<'_, AppState>, card_id: i64, token: String) -> AppResult<Vec<CardHistoryEntry>> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let card = db.get_control_card(card_id).await?;
+    check_card_access(&card, claims.sub, &claims.role)?;
+    db.get_card_history(card_id).await
+}
+
+/// Даты, которые не удалось разобрать при переходе на типизированные поля
+#[tauri::command]
+async fn get_date_migration_issues(state: State<'_, AppState>, token: String) -> AppResult<Vec<DateMigrationIssue>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view date migration issues"));
+    }
+
+    let db = state.db().await?;
+    db.get_date_migration_issues().await
+}
 
-        db.update_control_card(
-            id,
-            card_number,
-            year,
-            &executor,
-            &reporter,
-            &summary,
-            &document_reference,
-            Some(user_id),
-            Some(executor_user_id),
-            return_to.as_deref(),
-            execution_deadline.as_deref(),
-            execution_period_type.as_deref(),
-            extended_deadline.as_deref(),
-            resolution.as_deref(),
-            department.as_deref(),
-            controller.as_deref(),
-            controller_user_id,
-        )
-    })
-}
-
-#[tauri::command]
-fn delete_control_card(id: i64, token: String) -> Result<usize, String> {
-    let claims = verify_token(&token)?;
+/// Переносит карточку в корзину; reason — необязательная причина удаления
+#[tauri::command]
+async fn delete_control_card(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: i64,
+    reason: Option<String>,
+    token: String,
+) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
     let user_role = claims.role;
 
     // Только admin или controller могут удалять карточки
     if user_role != "admin" && user_role != "controller" {
-        return Err("Only admin or controller can delete control cards".to_string());
+        return Err(AppError::forbidden("Only admin or controller can delete control cards"));
+    }
+
+    let reason = reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+
+    let db = state.writable_db().await?;
+    let deleted = db.delete_control_card(id, claims.sub, reason).await?;
+    if deleted == 0 {
+        return Err(AppError::not_found("Control card not found"));
+    }
+    events::publish_changes(&app, &state).await;
+    Ok(deleted)
+}
+
+#[tauri::command]
+async fn list_deleted_cards(state: State<'_, AppState>, token: String) -> AppResult<Vec<DeletedCard>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can view deleted cards"));
+    }
+
+    let db = state.db().await?;
+    db.get_deleted_control_cards().await
+}
+
+#[tauri::command]
+async fn restore_control_card(app: AppHandle, state: State<'_, AppState>, id: i64, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can restore control cards"));
+    }
+
+    let db = state.writable_db().await?;
+    let restored = db.restore_control_card(id, claims.sub).await?;
+    if restored == 0 {
+        return Err(AppError::not_found("Deleted control card not found"));
+    }
+    events::publish_changes(&app, &state).await;
+    Ok(restored)
+}
+
+/// Окончательно удаляет карточки, находящиеся в корзине с даты раньше older_than (YYYY-MM-DD)
+#[tauri::command]
+async fn purge_deleted_cards(app: AppHandle, state: State<'_, AppState>, older_than: String, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can purge deleted cards"));
+    }
+
+    let older_than = deadlines::parse_date(&older_than).map_err(|e| AppError::validation("older_than", e))?;
+
+    let db = state.writable_db().await?;
+    let purged = db.purge_deleted_cards(older_than).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(purged)
+}
+
+/// Переносит закрытые карточки года в архивный файл.
+/// carry_over — незакрытые карточки, которые остаются в основной БД.
+#[tauri::command]
+async fn archive_year(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    year: i32,
+    carry_over: Option<Vec<i64>>,
+    token: String,
+) -> AppResult<ArchiveInfo> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can archive years"));
+    }
+
+    if year >= deadlines::today().year() {
+        return Err(AppError::validation("year", "Only past years can be archived"));
+    }
+
+    let db = state.writable_db().await?;
+    let archive = db.archive_year(year, &carry_over.unwrap_or_default(), claims.sub).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(archive)
+}
+
+#[tauri::command]
+async fn list_archives(state: State<'_, AppState>, token: String) -> AppResult<Vec<ArchiveInfo>> {
+    state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let attached: Vec<i32> = state.attached_archives().await.iter().map(|a| a.year).collect();
+    let mut archives = db.get_archives().await?;
+    for archive in &mut archives {
+        archive.attached = attached.contains(&archive.year);
+    }
+    Ok(archives)
+}
+
+/// Подключает архив года только для чтения, сверив контрольную сумму файла
+#[tauri::command]
+async fn attach_archive(state: State<'_, AppState>, year: i32, token: String) -> AppResult<()> {
+    state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let info = db.get_archive(year).await?
+        .ok_or_else(|| AppError::not_found(format!("Archive of {} not found", year)))?;
+    let path = archive::archive_path(db.get_path(), &info.file_name);
+
+    let archive = ArchiveDb::open(&info, &path, db.key()).await?;
+    state.attach_archive(archive).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn detach_archive(state: State<'_, AppState>, year: i32, token: String) -> AppResult<bool> {
+    state.verify_token(&token).await?;
+
+    Ok(state.detach_archive(year).await)
+}
+
+/// Поиск по подключённым архивам; year ограничивает поиск одним архивом
+#[tauri::command]
+async fn search_archived_cards(
+    state: State<'_, AppState>,
+    query: String,
+    year: Option<i32>,
+    token: String,
+) -> AppResult<Vec<ControlCard>> {
+    let claims = state.verify_token(&token).await?;
+
+    // User ищет только среди карточек, где он исполнитель
+    let executor_filter = if claims.role == "admin" || claims.role == "controller" {
+        None
+    } else {
+        Some(claims.sub)
+    };
+
+    let mut cards = Vec::new();
+    for archive in state.attached_archives().await.into_iter().rev() {
+        if year.is_some_and(|y| y != archive.year) {
+            continue;
+        }
+        cards.extend(archive.search_cards(&query, executor_filter).await?);
+    }
+    Ok(cards)
+}
+
+/// Резервная копия БД в указанный файл; выполняется без остановки других клиентов
+#[tauri::command]
+async fn backup_database(state: State<'_, AppState>, dest_path: String, token: String) -> AppResult<BackupInfo> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can back up the database"));
+    }
+
+    if dest_path.trim().is_empty() {
+        return Err(AppError::validation("dest_path", "Backup path is required"));
+    }
+
+    let db = state.db().await?;
+    let dest = std::path::Path::new(dest_path.trim());
+    db.backup_to(dest).await?;
+    BackupInfo::from_path(dest)
+}
+
+/// Восстанавливает БД из резервной копии после проверки целостности и версии схемы.
+/// backup_key передаётся, если копия зашифрована не текущим ключом ("" — копия не зашифрована).
+#[tauri::command]
+async fn restore_database(
+    state: State<'_, AppState>,
+    src_path: String,
+    backup_key: Option<String>,
+    token: String,
+) -> AppResult<ConnectionInfo> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can restore the database"));
+    }
+
+    let backup_key = backup_key.map(|key| encryption::normalize_key(Some(key)));
+    state
+        .restore(std::path::Path::new(src_path.trim()), backup_key.as_ref().map(Option::as_deref))
+        .await
+}
+
+/// Меняет ключ шифрования БД и её архивов; пустой ключ снимает шифрование
+#[tauri::command]
+async fn rekey_database(state: State<'_, AppState>, new_key: Option<String>, token: String) -> AppResult<ConnectionInfo> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can change the encryption key"));
+    }
+
+    let new_key = encryption::normalize_key(new_key);
+    state.rekey(new_key.as_deref()).await
+}
+
+#[tauri::command]
+async fn get_backup_settings(state: State<'_, AppState>, token: String) -> AppResult<BackupSettings> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view backup settings"));
+    }
+
+    let db = state.db().await?;
+    db.get_backup_settings().await
+}
+
+#[tauri::command]
+async fn update_backup_settings(state: State<'_, AppState>, settings: BackupSettings, token: String) -> AppResult<BackupSettings> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can change backup settings"));
     }
 
-    with_db_immut(|db| {
-        db.delete_control_card(id)
-    })
+    if settings.interval_hours < 1 {
+        return Err(AppError::validation("interval_hours", "Backup interval must be at least one hour"));
+    }
+    if settings.retention < 1 {
+        return Err(AppError::validation("retention", "At least one backup must be kept"));
+    }
+
+    let db = state.writable_db().await?;
+    db.update_backup_settings(&settings).await
+}
+
+#[tauri::command]
+async fn get_smtp_settings(state: State<'_, AppState>, token: String) -> AppResult<SmtpSettings> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view e-mail settings"));
+    }
+
+    let db = state.db().await?;
+    db.get_smtp_settings().await
+}
+
+#[tauri::command]
+async fn update_smtp_settings(state: State<'_, AppState>, settings: SmtpSettings, token: String) -> AppResult<SmtpSettings> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can change e-mail settings"));
+    }
+
+    settings.validate()?;
+    let db = state.writable_db().await?;
+    db.update_smtp_settings(&settings).await
+}
+
+/// Пробное письмо на адрес текущего администратора с сохранёнными настройками
+#[tauri::command]
+async fn send_test_email(state: State<'_, AppState>, token: String) -> AppResult<()> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can send test e-mails"));
+    }
+
+    let db = state.db().await?;
+    let user = operations::current_user(&db, &claims).await?;
+    let email = user.email.clone()
+        .ok_or_else(|| AppError::validation("email", "Set your e-mail address in the user profile first"))?;
+    let settings = db.get_smtp_settings().await?;
+    reminders::send_test_email(&settings, &user, &email).await
+}
+
+/// Настройки уведомлений рабочего стола хранятся на этом рабочем месте, вход не требуется
+#[tauri::command]
+async fn get_notification_settings(notifications: State<'_, NotificationCenter>) -> AppResult<NotificationSettings> {
+    Ok(notifications.settings().await)
+}
+
+#[tauri::command]
+async fn update_notification_settings(
+    notifications: State<'_, NotificationCenter>,
+    settings: NotificationSettings,
+) -> AppResult<NotificationSettings> {
+    notifications.update(settings).await
+}
+
+#[tauri::command]
+async fn get_api_settings(state: State<'_, AppState>, api: State<'_, ApiServer>, token: String) -> AppResult<ApiStatus> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view API settings"));
+    }
+
+    Ok(api.status().await)
+}
+
+/// Сохраняет настройки HTTP API и перезапускает сервер с ними
+#[tauri::command]
+async fn update_api_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    api: State<'_, ApiServer>,
+    settings: ApiSettings,
+    token: String,
+) -> AppResult<ApiStatus> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can change API settings"));
+    }
+
+    api.update(settings, app).await
+}
+
+#[tauri::command]
+async fn list_webhooks(state: State<'_, AppState>, token: String) -> AppResult<Vec<Webhook>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view webhooks"));
+    }
+
+    let db = state.db().await?;
+    db.list_webhooks().await
+}
+
+#[tauri::command]
+async fn create_webhook(state: State<'_, AppState>, webhook: WebhookInput, token: String) -> AppResult<Webhook> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage webhooks"));
+    }
+
+    let events = webhook.validate()?;
+    let secret = webhook.secret()
+        .ok_or_else(|| AppError::validation("secret", "Webhook secret is required"))?;
+
+    let db = state.writable_db().await?;
+    db.create_webhook(webhook.url.trim(), secret, &events, webhook.enabled).await
+}
+
+#[tauri::command]
+async fn update_webhook(state: State<'_, AppState>, id: i64, webhook: WebhookInput, token: String) -> AppResult<Webhook> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage webhooks"));
+    }
+
+    let events = webhook.validate()?;
+    let db = state.writable_db().await?;
+    db.update_webhook(id, webhook.url.trim(), webhook.secret(), &events, webhook.enabled).await
+}
+
+#[tauri::command]
+async fn delete_webhook(state: State<'_, AppState>, id: i64, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage webhooks"));
+    }
+
+    let db = state.writable_db().await?;
+    db.delete_webhook(id).await
+}
+
+/// Журнал доставки вебхуков, от новых к старым
+#[tauri::command]
+async fn list_webhook_deliveries(
+    state: State<'_, AppState>,
+    webhook_id: Option<i64>,
+    limit: Option<i64>,
+    token: String,
+) -> AppResult<Vec<WebhookDelivery>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view webhook deliveries"));
+    }
+
+    let db = state.db().await?;
+    db.list_webhook_deliveries(webhook_id, limit.unwrap_or(100).clamp(1, 1000)).await
+}
+
+/// Повторная отправка неудачной доставки; её заберёт ближайший проход очереди
+#[tauri::command]
+async fn retry_webhook_delivery(state: State<'_, AppState>, id: i64, token: String) -> AppResult<()> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can retry webhook deliveries"));
+    }
+
+    let db = state.writable_db().await?;
+    db.retry_webhook_delivery(id).await
+}
+
+/// Автоматические копии, от новых к старым
+#[tauri::command]
+async fn list_backups(state: State<'_, AppState>, token: String) -> AppResult<Vec<BackupInfo>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view backups"));
+    }
+
+    let db = state.db().await?;
+    let settings = db.get_backup_settings().await?;
+    let dir = backup::backup_directory(db.get_path(), &settings);
+    backup::list_auto_backups(&dir, db.get_path())
+}
+
+#[tauri::command]
+async fn get_all_card_templates(state: State<'_, AppState>, token: String) -> AppResult<Vec<CardTemplate>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can view card templates"));
+    }
+
+    let db = state.db().await?;
+    db.get_all_card_templates().await
+}
+
+#[tauri::command]
+async fn create_card_template(state: State<'_, AppState>, template: CardTemplateInput, token: String) -> AppResult<i64> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can create card templates"));
+    }
+
+    if template.name.trim().is_empty() {
+        return Err(AppError::validation("name", "Template name is required"));
+    }
+    deadlines::validate_deadline_offset(template.deadline_offset_days)?;
+
+    let db = state.writable_db().await?;
+    check_controller(&db, template.controller_user_id).await?;
+    db.create_card_template(&template).await
+}
+
+#[tauri::command]
+async fn update_card_template(state: State<'_, AppState>, id: i64, template: CardTemplateInput, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can update card templates"));
+    }
+
+    if template.name.trim().is_empty() {
+        return Err(AppError::validation("name", "Template name is required"));
+    }
+    deadlines::validate_deadline_offset(template.deadline_offset_days)?;
+
+    let db = state.writable_db().await?;
+    if db.get_card_template(id).await?.is_none() {
+        return Err(AppError::not_found("Card template not found"));
+    }
+    check_controller(&db, template.controller_user_id).await?;
+    db.update_card_template(id, &template).await
+}
+
+#[tauri::command]
+async fn delete_card_template(state: State<'_, AppState>, id: i64, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can delete card templates"));
+    }
+
+    let db = state.writable_db().await?;
+    db.delete_card_template(id).await
+}
+
+#[tauri::command]
+async fn create_card_from_template(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    template_id: i64,
+    overrides: TemplateCardOverrides,
+    token: String,
+) -> AppResult<i64> {
+    let claims = state.verify_token(&token).await?;
+    let user_id = claims.sub;
+
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can create control cards"));
+    }
+
+    // Дата документа: от неё считается срок исполнения и берётся год карточки
+    let document_date = match overrides.document_date.as_deref() {
+        Some(value) => deadlines::parse_date(value).map_err(|e| AppError::validation("document_date", e))?,
+        None => deadlines::today(),
+    };
+
+    let db = state.writable_db().await?;
+    let template = db.get_card_template(template_id).await?
+        .ok_or_else(|| AppError::not_found("Card template not found"))?;
+
+    let executor = resolve_executor(&db, overrides.executor_user_id).await?;
+
+    // Значения из запроса имеют приоритет над значениями шаблона
+    let reporter = overrides.reporter.or(template.reporter)
+        .ok_or_else(|| AppError::validation("reporter", "Reporter is required"))?;
+    let department = overrides.department.or(template.department);
+    let controller = overrides.controller.or(template.controller);
+    let controller_user_id = overrides.controller_user_id.or(template.controller_user_id);
+    let execution_period_type = overrides.execution_period_type.or(template.execution_period_type);
+    let execution_deadline = match overrides.execution_deadline.as_deref() {
+        Some(value) => deadlines::parse_optional_date("execution_deadline", Some(value))?,
+        // Шаблоны, сохранённые до проверки срока, могут содержать недопустимое значение
+        None => match template.deadline_offset_days {
+            Some(days) => {
+                deadlines::validate_deadline_offset(Some(days))?;
+                let deadline = deadlines::add_working_days(document_date, days as i64)
+                    .ok_or_else(|| AppError::validation("deadline_offset_days", "Deadline is out of the supported date range"))?;
+                Some(deadline)
+            }
+            None => None,
+        },
+    };
+
+    check_controller(&db, controller_user_id).await?;
+
+    let year = overrides.year.unwrap_or_else(|| document_date.year());
+    deadlines::validate_card_dates(
+        &CardDates { year, execution_deadline, extended_deadline: None, completed_at: None },
+        deadlines::today(),
+        None,
+    )?;
+
+    let id = db.create_control_card(
+        None,
+        year,
+        &executor,
+        &reporter,
+        &overrides.summary,
+        &overrides.document_reference,
+        Some(user_id),
+        Some(overrides.executor_user_id),
+        overrides.return_to.as_deref(),
+        execution_deadline,
+        execution_period_type.as_deref(),
+        None,
+        overrides.resolution.as_deref(),
+        department.as_deref(),
+        controller.as_deref(),
+        controller_user_id,
+        overrides.numbering_scheme_id,
+    ).await?;
+    events::publish_changes(&app, &state).await;
+    Ok(id)
 }
 
 #[tauri::command]
-fn ensure_database_connected() -> Result<(), String> {
+async fn get_all_numbering_schemes(state: State<'_, AppState>, token: String) -> AppResult<Vec<NumberingScheme>> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can view numbering schemes"));
+    }
+
+    let db = state.db().await?;
+    db.get_all_numbering_schemes().await
+}
+
+#[tauri::command]
+async fn create_numbering_scheme(state: State<'_, AppState>, scheme: NumberingSchemeInput, token: String) -> AppResult<i64> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage numbering schemes"));
+    }
+
+    numbering::validate_scheme(&scheme)?;
+
+    let db = state.writable_db().await?;
+    db.create_numbering_scheme(&scheme).await
+}
+
+#[tauri::command]
+async fn update_numbering_scheme(state: State<'_, AppState>, id: i64, scheme: NumberingSchemeInput, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage numbering schemes"));
+    }
+
+    numbering::validate_scheme(&scheme)?;
+
+    let db = state.writable_db().await?;
+    db.update_numbering_scheme(id, &scheme).await
+}
+
+#[tauri::command]
+async fn delete_numbering_scheme(state: State<'_, AppState>, id: i64, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can manage numbering schemes"));
+    }
+
+    let db = state.writable_db().await?;
+    db.delete_numbering_scheme(id).await
+}
+
+#[tauri::command]
+async fn search_control_cards_by_number(state: State<'_, AppState>, query: String, token: String) -> AppResult<Vec<ControlCard>> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    // User ищет только среди карточек, где он исполнитель
+    let executor_filter = if claims.role == "admin" || claims.role == "controller" {
+        None
+    } else {
+        Some(claims.sub)
+    };
+    db.search_control_cards_by_number(&query, executor_filter).await
+}
+
+#[tauri::command]
+async fn ensure_database_connected(state: State<'_, AppState>) -> AppResult<()> {
     // Вычисляем правильный путь БД
     let app_data_dir = std::env::var("APPDATA")
         .or_else(|_| std::env::var("LOCALAPPDATA"))
-        .map_err(|_| "Failed to get app data directory".to_string())?;
+        .map_err(|_| AppError::db("Failed to get app data directory"))?;
     let db_dir = std::path::Path::new(&app_data_dir).join("calendar-tauri");
     std::fs::create_dir_all(&db_dir)
-        .map_err(|e| format!("Failed to create database directory: {}", e))?;
+        .map_err(|e| AppError::db(format!("Failed to create database directory: {}", e)))?;
     
     let default_db_path = db_dir.join("calendar.db");
     let correct_db_path_str = default_db_path.to_str()
-        .ok_or_else(|| "Invalid database path".to_string())?;
+        .ok_or_else(|| AppError::validation("db_path", "Invalid database path"))?;
     
     // Проверяем текущий путь БД
-    let current_path = with_db_immut(|db| Ok(db.get_path().cloned()))?;
+    let current_path = state.path().await;
     
     // Если БД подключена к правильному пути, ничего не делаем
     if let Some(ref path) = current_path {
@@ -287,245 +1090,326 @@ fn ensure_database_connected() -> Result<(), String> {
             return Ok(());
         } else {
             // БД подключена к неправильному пути, отключаем и переподключаем
-            with_db(|db| {
-                db.disconnect();
-                Ok(())
-            })?;
+            state.disconnect().await;
         }
     }
     
     // Подключаемся к правильному пути
-    with_db(|db| {
-        db.connect(correct_db_path_str)?;
-        Ok(())
-    })
+    state.connect(correct_db_path_str, ConnectionMode::Single, None, false).await?;
+    Ok(())
 }
 
 #[tauri::command]
-fn init_admin(username: String, password: String) -> Result<i64, String> {
+async fn init_admin(state: State<'_, AppState>, username: String, password: String) -> AppResult<i64> {
     if password.len() < 6 {
-        return Err("Password must be at least 6 characters long".to_string());
+        return Err(AppError::validation("password", "Password must be at least 6 characters long"));
     }
 
     let password_hash = hash_password(&password)?;
 
-    with_db(|db| {
-        let existing_user = db.get_user_by_username(&username)?;
-        if existing_user.is_some() {
-            return Err("User already exists".to_string());
-        }
+    let db = state.writable_db().await?;
+    let existing_user = db.get_user_by_username(&username).await?;
+    if existing_user.is_some() {
+        return Err(AppError::conflict("User already exists"));
+    }
 
-        db.create_user(&username, &password_hash, "admin")
-    })
+    db.create_user(&username, None, None, &password_hash, "admin").await
 }
 
 #[tauri::command]
-fn register_user(
+async fn register_user(
+    state: State<'_, AppState>,
     username: String,
+    full_name: Option<String>,
+    email: Option<String>,
     password: String,
     role: String,
     token: String,
-) -> Result<i64, String> {
-    let claims = verify_token(&token)?;
+) -> AppResult<i64> {
+    let claims = state.verify_token(&token).await?;
     if claims.role != "admin" {
-        return Err("Only admin can register users".to_string());
+        return Err(AppError::forbidden("Only admin can register users"));
     }
 
     if !matches!(role.as_str(), "admin" | "user" | "controller") {
-        return Err("Invalid role".to_string());
+        return Err(AppError::validation("role", "Invalid role"));
     }
 
     if password.len() < 6 {
-        return Err("Password must be at least 6 characters long".to_string());
+        return Err(AppError::validation("password", "Password must be at least 6 characters long"));
     }
 
+    let email = reminders::normalize_email(email.as_deref())?;
     let password_hash = hash_password(&password)?;
 
-    with_db(|db| {
-        db.create_user(&username, &password_hash, &role)
-    })
+    let db = state.writable_db().await?;
+    let full_name = full_name.as_deref().map(str::trim).filter(|name| !name.is_empty());
+    db.create_user(&username, full_name, email.as_deref(), &password_hash, &role).await
 }
 
 #[tauri::command]
-fn login(username: String, password: String) -> Result<String, String> {
-    let user = with_db_immut(|db| {
-        db.get_user_by_username(&username)
-    })?;
-
-    let user = user.ok_or_else(|| "Invalid username or password".to_string())?;
-
-    let is_valid = verify_password(&password, &user.password_hash)?;
-    if !is_valid {
-        return Err("Invalid username or password".to_string());
-    }
-
-    generate_token(user.id, &user.role)
+async fn login(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    profiles: State<'_, ProfileRegistry>,
+    notifications: State<'_, NotificationCenter>,
+    username: String,
+    password: String,
+) -> AppResult<String> {
+    let db = state.db().await?;
+    let user = operations::authenticate(&db, &username, &password).await?;
+
+    db.set_session_user(Some(user.id)).await?;
+    state.sign_in(user.id, &user.role).await;
+    notifications.wake();
+    tray::refresh(&app).await;
+
+    let token = generate_token(user.id, &user.role, db.get_path())?;
+    profiles.remember_session(&token).await?;
+    Ok(token)
 }
 
 #[tauri::command]
-fn get_current_user(token: String) -> Result<User, String> {
-    let claims = verify_token(&token)?;
-    
-    with_db_immut(|db| {
-        db.get_user_by_id(claims.sub)
-            .and_then(|opt| opt.ok_or_else(|| "User not found".to_string()))
-    })
+async fn get_current_user(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    notifications: State<'_, NotificationCenter>,
+    token: String,
+) -> AppResult<User> {
+    let claims = state.verify_token(&token).await?;
+
+    let db = state.db().await?;
+    let user = operations::current_user(&db, &claims).await?;
+
+    // Окно восстанавливает сохранённую сессию через эту команду, поэтому здесь тоже отмечаем вход
+    state.sign_in(user.id, &user.role).await;
+    notifications.wake();
+    tray::refresh(&app).await;
+    Ok(user)
 }
 
 #[tauri::command]
-fn get_windows_username() -> Result<String, String> {
+fn get_windows_username() -> AppResult<String> {
     Ok(whoami::username())
 }
 
 #[tauri::command]
-fn has_any_users() -> Result<bool, String> {
-    let is_connected = with_db_immut(|db| Ok(db.is_connected()))?;
-    
-    if !is_connected {
+async fn has_any_users(state: State<'_, AppState>) -> AppResult<bool> {
+    let Ok(db) = state.db().await else {
         return Ok(false);
-    }
-    
-    with_db_immut(|db| {
-        db.has_any_users()
-    })
+    };
+
+    db.has_any_users().await
 }
 
 #[tauri::command]
-fn get_all_users(token: String) -> Result<Vec<User>, String> {
-    let claims = verify_token(&token)?;
-    if claims.role != "admin" {
-        return Err("Only admin can view all users".to_string());
-    }
+async fn get_all_users(state: State<'_, AppState>, token: String) -> AppResult<Vec<User>> {
+    let claims = state.verify_token(&token).await?;
 
-    with_db_immut(|db| {
-        db.get_all_users()
-    })
+    let db = state.db().await?;
+    operations::list_users(&db, &claims).await
 }
 
 #[tauri::command]
-fn get_users_for_executor_selection(token: String) -> Result<Vec<User>, String> {
-    let claims = verify_token(&token)?;
-    // Только admin или controller могут выбирать исполнителей
-    if claims.role != "admin" && claims.role != "controller" {
-        return Err("Only admin or controller can select executors".to_string());
-    }
+async fn get_users_for_executor_selection(state: State<'_, AppState>, token: String) -> AppResult<Vec<User>> {
+    let claims = state.verify_token(&token).await?;
 
-    with_db_immut(|db| {
-        // Получаем всех пользователей с ролью 'user'
-        let all_users = db.get_all_users()?;
-        Ok(all_users.into_iter().filter(|u| u.role == "user").collect())
-    })
+    let db = state.db().await?;
+    operations::list_executors(&db, &claims).await
 }
 
 #[tauri::command]
-fn get_users_for_controller_selection(token: String) -> Result<Vec<User>, String> {
-    let claims = verify_token(&token)?;
+async fn get_users_for_controller_selection(state: State<'_, AppState>, token: String) -> AppResult<Vec<User>> {
+    let claims = state.verify_token(&token).await?;
     // Только admin или controller могут выбирать контроллеров
     if claims.role != "admin" && claims.role != "controller" {
-        return Err("Only admin or controller can select controllers".to_string());
+        return Err(AppError::forbidden("Only admin or controller can select controllers"));
     }
 
-    with_db_immut(|db| {
-        // Получаем всех пользователей с ролью 'controller'
-        let all_users = db.get_all_users()?;
-        Ok(all_users.into_iter().filter(|u| u.role == "controller").collect())
-    })
+    let db = state.db().await?;
+    // Получаем всех пользователей с ролью 'controller'
+    let all_users = db.get_all_users().await?;
+    Ok(all_users.into_iter().filter(|u| u.role == "controller").collect())
 }
 
 #[tauri::command]
-fn update_user(
+async fn update_user(
+    state: State<'_, AppState>,
     id: i64,
     username: String,
+    full_name: Option<String>,
+    email: Option<String>,
     role: String,
     token: String,
-) -> Result<usize, String> {
-    let claims = verify_token(&token)?;
+) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
     if claims.role != "admin" {
-        return Err("Only admin can update users".to_string());
+        return Err(AppError::forbidden("Only admin can update users"));
     }
 
     if !matches!(role.as_str(), "admin" | "user" | "controller") {
-        return Err("Invalid role".to_string());
+        return Err(AppError::validation("role", "Invalid role"));
     }
+    let email = reminders::normalize_email(email.as_deref())?;
 
-    with_db_immut(|db| {
-        let existing_user = db.get_user_by_id(id)?;
-        if existing_user.is_none() {
-            return Err("User not found".to_string());
-        }
+    let db = state.writable_db().await?;
+    let existing_user = db.get_user_by_id(id).await?;
+    if existing_user.is_none() {
+        return Err(AppError::not_found("User not found"));
+    }
 
-        let user_by_username = db.get_user_by_username(&username)?;
-        if let Some(user) = user_by_username {
-            if user.id != id {
-                return Err("Username already exists".to_string());
-            }
+    let user_by_username = db.get_user_by_username(&username).await?;
+    if let Some(user) = user_by_username {
+        if user.id != id {
+            return Err(AppError::conflict("Username already exists"));
         }
+    }
 
-        db.update_user(id, &username, &role)
-    })
+    let full_name = full_name.as_deref().map(str::trim).filter(|name| !name.is_empty());
+    db.update_user(id, &username, full_name, email.as_deref(), &role).await
 }
 
 #[tauri::command]
-fn delete_user(id: i64, token: String) -> Result<usize, String> {
-    let claims = verify_token(&token)?;
+async fn delete_user(state: State<'_, AppState>, id: i64, token: String) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
     if claims.role != "admin" {
-        return Err("Only admin can delete users".to_string());
+        return Err(AppError::forbidden("Only admin can delete users"));
     }
 
-    with_db_immut(|db| {
-        let existing_user = db.get_user_by_id(id)?;
-        if existing_user.is_none() {
-            return Err("User not found".to_string());
-        }
+    let db = state.writable_db().await?;
+    let existing_user = db.get_user_by_id(id).await?;
+    if existing_user.is_none() {
+        return Err(AppError::not_found("User not found"));
+    }
 
-        db.delete_user(id)
-    })
+    db.delete_user(id).await
 }
 
 #[tauri::command]
-fn change_user_password(
+async fn change_user_password(
+    state: State<'_, AppState>,
     id: i64,
     new_password: String,
     token: String,
-) -> Result<usize, String> {
-    let claims = verify_token(&token)?;
+) -> AppResult<usize> {
+    let claims = state.verify_token(&token).await?;
     if claims.role != "admin" {
-        return Err("Only admin can change user passwords".to_string());
+        return Err(AppError::forbidden("Only admin can change user passwords"));
     }
 
     if new_password.len() < 6 {
-        return Err("Password must be at least 6 characters long".to_string());
+        return Err(AppError::validation("password", "Password must be at least 6 characters long"));
     }
 
     let password_hash = hash_password(&new_password)?;
 
-    with_db_immut(|db| {
-        let existing_user = db.get_user_by_id(id)?;
-        if existing_user.is_none() {
-            return Err("User not found".to_string());
-        }
+    let db = state.writable_db().await?;
+    let existing_user = db.get_user_by_id(id).await?;
+    if existing_user.is_none() {
+        return Err(AppError::not_found("User not found"));
+    }
 
-        db.update_user_password(id, &password_hash)
-    })
+    db.update_user_password(id, &password_hash).await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .manage(AppState::default())
+        .setup(|app| {
+            let config_dir = app.path().app_config_dir()?;
+            if let Err(e) = auth::init_token_secret(&config_dir.join("token_secret.json")) {
+                eprintln!("Failed to prepare token secret: {}", e);
+            }
+            app.manage(ProfileRegistry::load(config_dir.join("profiles.json")));
+            app.manage(ApiServer::load(config_dir.join("api.json")));
+            app.manage(NotificationCenter::load(config_dir.join("notifications.json")));
+            events::spawn_change_watcher(app.handle().clone());
+            backup::spawn_auto_backup(app.handle().clone());
+            webhooks::spawn_webhook_dispatcher(app.handle().clone());
+            reminders::spawn_reminder_scheduler(app.handle().clone());
+            notifications::spawn_notification_scheduler(app.handle().clone());
+            tray::setup_tray(app.handle())?;
+            tray::spawn_tray_refresher(app.handle().clone());
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = handle.state::<ApiServer>().start(handle.clone()).await {
+                    eprintln!("Failed to start API server: {}", e);
+                }
+            });
+            Ok(())
+        })
+        .on_window_event(tray::hide_on_close)
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             connect_database,
             disconnect_database,
             get_database_path,
             is_database_connected,
+            get_connection_info,
+            list_profiles,
+            add_profile,
+            remove_profile,
+            switch_profile,
+            forget_profile_session,
+            diagnose_database,
+            repair_database,
+            get_client_sessions,
             get_next_card_number,
+            reserve_card_number,
+            release_card_number,
             create_control_card,
+            import_cards_csv,
             get_control_card,
             get_all_control_cards,
+            export_cards_xlsx,
+            export_cards_ics,
             update_control_card,
+            patch_control_card,
+            get_card_history,
+            get_date_migration_issues,
             delete_control_card,
+            list_deleted_cards,
+            restore_control_card,
+            purge_deleted_cards,
+            archive_year,
+            list_archives,
+            attach_archive,
+            detach_archive,
+            search_archived_cards,
+            backup_database,
+            restore_database,
+            rekey_database,
+            get_backup_settings,
+            update_backup_settings,
+            list_backups,
+            get_api_settings,
+            update_api_settings,
+            list_webhooks,
+            create_webhook,
+            update_webhook,
+            delete_webhook,
+            list_webhook_deliveries,
+            retry_webhook_delivery,
+            get_smtp_settings,
+            update_smtp_settings,
+            send_test_email,
+            get_notification_settings,
+            update_notification_settings,
+            get_all_card_templates,
+            create_card_template,
+            update_card_template,
+            delete_card_template,
+            create_card_from_template,
+            get_all_numbering_schemes,
+            create_numbering_scheme,
+            update_numbering_scheme,
+            delete_numbering_scheme,
+            search_control_cards_by_number,
             ensure_database_connected,
             init_admin,
             register_user,
@@ -1,5 +1,9 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
+use crate::database::ControlCard;
+use crate::deadlines::{self, CardStatus};
 
 #[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +38,11 @@ impl UserRole {
 pub struct User {
     pub id: i64,
     pub username: String,
+    /// ФИО, по нему сопоставляются исполнители при импорте
+    pub full_name: Option<String>,
+    /// Адрес для напоминаний о сроках
+    #[serde(default)]
+    pub email: Option<String>,
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: String,
@@ -47,3 +56,294 @@ impl User {
     }
 }
 
+
+/// Данные новой карточки. Если card_number не указан, номер выделяется при вставке.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CardInput {
+    #[serde(default)]
+    pub card_number: Option<i32>,
+    pub year: i32,
+    pub executor_user_id: i64,
+    pub reporter: String,
+    pub summary: String,
+    pub document_reference: String,
+    #[serde(default)]
+    pub return_to: Option<String>,
+    /// YYYY-MM-DD
+    #[serde(default)]
+    pub execution_deadline: Option<String>,
+    #[serde(default)]
+    pub execution_period_type: Option<String>,
+    #[serde(default)]
+    pub extended_deadline: Option<String>,
+    #[serde(default)]
+    pub resolution: Option<String>,
+    #[serde(default)]
+    pub department: Option<String>,
+    #[serde(default)]
+    pub controller: Option<String>,
+    #[serde(default)]
+    pub controller_user_id: Option<i64>,
+    #[serde(default)]
+    pub numbering_scheme_id: Option<i64>,
+}
+
+/// Данные карточки, создаваемой по шаблону. Незаполненные поля берутся из шаблона.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemplateCardOverrides {
+    pub executor_user_id: i64,
+    pub summary: String,
+    pub document_reference: String,
+    /// Дата документа (YYYY-MM-DD), от неё отсчитывается срок исполнения
+    #[serde(default)]
+    pub document_date: Option<String>,
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub reporter: Option<String>,
+    #[serde(default)]
+    pub return_to: Option<String>,
+    #[serde(default)]
+    pub execution_deadline: Option<String>,
+    #[serde(default)]
+    pub execution_period_type: Option<String>,
+    #[serde(default)]
+    pub resolution: Option<String>,
+    #[serde(default)]
+    pub department: Option<String>,
+    #[serde(default)]
+    pub controller: Option<String>,
+    #[serde(default)]
+    pub controller_user_id: Option<i64>,
+    #[serde(default)]
+    pub numbering_scheme_id: Option<i64>,
+}
+
+/// Отбор карточек списка и выгрузок. Пустые поля не ограничивают выборку.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CardFilter {
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub executor_user_id: Option<i64>,
+    #[serde(default)]
+    pub department: Option<String>,
+    #[serde(default)]
+    pub status: Option<CardStatus>,
+    /// Границы действующего срока исполнения, включительно
+    #[serde(default)]
+    pub deadline_from: Option<NaiveDate>,
+    #[serde(default)]
+    pub deadline_to: Option<NaiveDate>,
+    /// Подстрока номера, содержания, документа или исполнителя без учёта регистра
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+impl CardFilter {
+    pub fn matches(&self, card: &ControlCard, today: NaiveDate) -> bool {
+        let dates = card.dates();
+
+        if self.year.is_some_and(|year| card.year != year) {
+            return false;
+        }
+        if self.executor_user_id.is_some_and(|id| card.executor_user_id != Some(id)) {
+            return false;
+        }
+        if let Some(department) = self.department.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            let department = department.to_lowercase();
+            if card.department.as_deref().is_none_or(|d| d.trim().to_lowercase() != department) {
+                return false;
+            }
+        }
+        if self.status.is_some_and(|status| dates.status(today) != status) {
+            return false;
+        }
+        if self.deadline_from.is_some() || self.deadline_to.is_some() {
+            let Some(deadline) = dates.effective_deadline() else {
+                return false;
+            };
+            if self.deadline_from.is_some_and(|from| deadline < from)
+                || self.deadline_to.is_some_and(|to| deadline > to)
+            {
+                return false;
+            }
+        }
+        if let Some(query) = self.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            let query = query.to_lowercase();
+            let number = card
+                .formatted_number
+                .clone()
+                .unwrap_or_else(|| format!("{}/{}", card.card_number, card.year));
+            let found = [number.as_str(), &card.summary, &card.document_reference, &card.executor]
+                .iter()
+                .any(|value| value.to_lowercase().contains(&query));
+            if !found {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Свод по исполнителю: сколько карточек в каждом состоянии
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ExecutorSummary {
+    pub executor: String,
+    pub total: u32,
+    pub open: u32,
+    pub completed: u32,
+    pub overdue: u32,
+}
+
+impl ExecutorSummary {
+    fn add(&mut self, status: CardStatus) {
+        self.total += 1;
+        match status {
+            CardStatus::Open => self.open += 1,
+            CardStatus::Completed => self.completed += 1,
+            CardStatus::Overdue => self.overdue += 1,
+        }
+    }
+}
+
+/// Свод по исполнителям в алфавитном порядке
+pub fn summarize_by_executor(cards: &[ControlCard], today: NaiveDate) -> Vec<ExecutorSummary> {
+    let mut totals: BTreeMap<&str, ExecutorSummary> = BTreeMap::new();
+    for card in cards {
+        totals
+            .entry(card.executor.as_str())
+            .or_insert_with(|| ExecutorSummary { executor: card.executor.clone(), ..Default::default() })
+            .add(card.dates().status(today));
+    }
+    totals.into_values().collect()
+}
+
+/// Значение поля в частичном обновлении карточки
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchValue {
+    Text(Option<String>),
+    Integer(Option<i64>),
+    Date(Option<NaiveDate>),
+}
+
+impl PatchValue {
+    /// Строковое представление для журнала изменений
+    pub fn to_history_value(&self) -> Option<String> {
+        match self {
+            PatchValue::Text(v) => v.clone(),
+            PatchValue::Integer(v) => v.map(|n| n.to_string()),
+            PatchValue::Date(v) => v.map(deadlines::format_date),
+        }
+    }
+}
+
+/// Позволяет отличить отсутствующее поле (None) от явного null (Some(None))
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// То же для дат: строка разбирается как ISO-8601, null очищает значение
+fn deserialize_some_date<'de, D>(deserializer: D) -> Result<Option<Option<NaiveDate>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    deadlines::parse_optional_date("date", value.as_deref())
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Поля, которые исполнитель может менять в своих карточках
+pub const EXECUTOR_PATCH_FIELDS: &[&str] = &["execution_report", "completed_at"];
+
+/// Частичное обновление карточки: меняются только переданные поля.
+/// Для необязательных полей null очищает значение.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ControlCardPatch {
+    pub expected_version: i64,
+    #[serde(default)]
+    pub card_number: Option<i32>,
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub executor_user_id: Option<i64>,
+    #[serde(default)]
+    pub reporter: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub document_reference: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub return_to: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some_date")]
+    pub execution_deadline: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub execution_period_type: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some_date")]
+    pub extended_deadline: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub resolution: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub department: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub controller: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub controller_user_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub execution_report: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some_date")]
+    pub completed_at: Option<Option<NaiveDate>>,
+}
+
+impl ControlCardPatch {
+    /// Переданные поля в виде пар (колонка, значение)
+    pub fn changes(&self) -> Vec<(&'static str, PatchValue)> {
+        let mut changes = Vec::new();
+
+        let mut text = |column: &'static str, value: &Option<Option<String>>| {
+            if let Some(v) = value {
+                changes.push((column, PatchValue::Text(v.clone())));
+            }
+        };
+        text("reporter", &self.reporter.clone().map(Some));
+        text("summary", &self.summary.clone().map(Some));
+        text("document_reference", &self.document_reference.clone().map(Some));
+        text("return_to", &self.return_to);
+        text("execution_period_type", &self.execution_period_type);
+        text("resolution", &self.resolution);
+        text("department", &self.department);
+        text("controller", &self.controller);
+        text("execution_report", &self.execution_report);
+
+        for (column, value) in [
+            ("execution_deadline", self.execution_deadline),
+            ("extended_deadline", self.extended_deadline),
+            ("completed_at", self.completed_at),
+        ] {
+            if let Some(v) = value {
+                changes.push((column, PatchValue::Date(v)));
+            }
+        }
+
+        if let Some(v) = self.card_number {
+            changes.push(("card_number", PatchValue::Integer(Some(v as i64))));
+        }
+        if let Some(v) = self.year {
+            changes.push(("year", PatchValue::Integer(Some(v as i64))));
+        }
+        if let Some(v) = self.executor_user_id {
+            changes.push(("executor_user_id", PatchValue::Integer(Some(v))));
+        }
+        if let Some(v) = self.controller_user_id {
+            changes.push(("controller_user_id", PatchValue::Integer(v)));
+        }
+
+        changes
+    }
+}
@@ -0,0 +1,288 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::{Mutex, Notify};
+use crate::database::ControlCard;
+use crate::error::{AppError, AppResult};
+use crate::profiles::json_store;
+use crate::state::AppState;
+
+/// Как часто проверяются сроки карточек вошедшего пользователя
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Больше этого числа карточек за раз показывается одно общее уведомление
+const MAX_SEPARATE_NOTIFICATIONS: usize = 5;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Настройки уведомлений этого рабочего места
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    /// За сколько дней до срока карточка считается подходящей к сроку
+    pub days_before: i64,
+    /// Начало и конец тихих часов в формате ЧЧ:ММ; интервал может переходить через полночь
+    #[serde(default)]
+    pub quiet_hours_start: Option<String>,
+    #[serde(default)]
+    pub quiet_hours_end: Option<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self { enabled: true, days_before: 3, quiet_hours_start: None, quiet_hours_end: None }
+    }
+}
+
+fn parse_time(field: &str, value: Option<&str>) -> AppResult<Option<NaiveTime>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(v) => NaiveTime::parse_from_str(v, TIME_FORMAT)
+            .map(Some)
+            .map_err(|_| AppError::validation(field, "Time must be in HH:MM format")),
+    }
+}
+
+impl NotificationSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        if !(0..=60).contains(&self.days_before) {
+            return Err(AppError::validation("days_before", "Lead time must be between 0 and 60 days"));
+        }
+        let start = parse_time("quiet_hours_start", self.quiet_hours_start.as_deref())?;
+        let end = parse_time("quiet_hours_end", self.quiet_hours_end.as_deref())?;
+        if start.is_some() != end.is_some() {
+            return Err(AppError::validation("quiet_hours_end", "Set both start and end of quiet hours"));
+        }
+        Ok(())
+    }
+
+    pub fn is_quiet(&self, time: NaiveTime) -> bool {
+        let start = parse_time("quiet_hours_start", self.quiet_hours_start.as_deref()).ok().flatten();
+        let end = parse_time("quiet_hours_end", self.quiet_hours_end.as_deref()).ok().flatten();
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => start <= time && time < end,
+            (Some(start), Some(end)) => time >= start || time < end,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueKind {
+    /// Срок через столько дней
+    DueSoon(i64),
+    DueToday,
+    Overdue,
+}
+
+/// Неисполненные карточки пользователя (исполнителя или контролёра) со сроком
+/// не позже чем через days_before дней, от самых срочных
+pub fn my_due_cards(cards: &[ControlCard], user_id: i64, today: NaiveDate, days_before: i64) -> Vec<(&ControlCard, DueKind)> {
+    let mut due: Vec<_> = cards
+        .iter()
+        .filter(|card| card.executor_user_id == Some(user_id) || card.controller_user_id == Some(user_id))
+        .filter_map(|card| {
+            let dates = card.dates();
+            if dates.completed_at.is_some() {
+                return None;
+            }
+            let days_left = (dates.effective_deadline()? - today).num_days();
+            let kind = match days_left {
+                d if d < 0 => DueKind::Overdue,
+                0 => DueKind::DueToday,
+                d if d <= days_before => DueKind::DueSoon(d),
+                _ => return None,
+            };
+            Some((card, kind, days_left))
+        })
+        .collect();
+    due.sort_by_key(|(card, _, days_left)| (*days_left, card.id));
+    due.into_iter().map(|(card, kind, _)| (card, kind)).collect()
+}
+
+/// Сколько карточек пользователя со сроком сегодня и просроченных
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DueCounts {
+    pub due_today: usize,
+    pub overdue: usize,
+}
+
+impl DueCounts {
+    pub fn of(cards: &[ControlCard], user_id: i64, today: NaiveDate) -> Self {
+        my_due_cards(cards, user_id, today, 0)
+            .into_iter()
+            .fold(Self::default(), |mut counts, (_, kind)| {
+                match kind {
+                    DueKind::DueToday => counts.due_today += 1,
+                    DueKind::Overdue => counts.overdue += 1,
+                    DueKind::DueSoon(_) => {}
+                }
+                counts
+            })
+    }
+
+    pub fn total(&self) -> usize {
+        self.due_today + self.overdue
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DesktopNotice {
+    pub title: String,
+    pub body: String,
+}
+
+fn card_number(card: &ControlCard) -> String {
+    card.formatted_number
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}", card.card_number, card.year))
+}
+
+fn notice(card: &ControlCard, kind: DueKind) -> DesktopNotice {
+    let number = card_number(card);
+    let title = match kind {
+        DueKind::Overdue => format!("Просрочена карточка {}", number),
+        DueKind::DueToday => format!("Сегодня срок по карточке {}", number),
+        DueKind::DueSoon(days) => format!("Через {} дн. срок по карточке {}", days, number),
+    };
+    DesktopNotice { title, body: card.summary.clone() }
+}
+
+fn summary_notice(due: &[(&ControlCard, DueKind)]) -> DesktopNotice {
+    let count = |pred: fn(&DueKind) -> bool| due.iter().filter(|(_, kind)| pred(kind)).count();
+    let overdue = count(|k| *k == DueKind::Overdue);
+    let today = count(|k| *k == DueKind::DueToday);
+    let soon = count(|k| matches!(k, DueKind::DueSoon(_)));
+    DesktopNotice {
+        title: format!("Карточек, требующих внимания: {}", due.len()),
+        body: format!("Просрочено: {}, срок сегодня: {}, скоро срок: {}", overdue, today, soon),
+    }
+}
+
+/// Карточки, о которых уже сообщили сегодня, по путям БД: в разных БД (профилях)
+/// у разных карточек могут совпадать id
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct ShownToday {
+    date: Option<NaiveDate>,
+    #[serde(default)]
+    databases: BTreeMap<String, BTreeSet<i64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct StoredState {
+    #[serde(default)]
+    settings: NotificationSettings,
+    #[serde(default)]
+    shown: ShownToday,
+}
+
+/// Уведомления рабочего стола; настройки и отметки о показанных хранятся в файле рядом с profiles.json
+#[derive(Default)]
+pub struct NotificationCenter {
+    file: Option<PathBuf>,
+    state: Mutex<StoredState>,
+    /// Будит планировщик, например сразу после входа пользователя
+    wake: Notify,
+}
+
+impl NotificationCenter {
+    /// Повреждённый или отсутствующий файл даёт настройки по умолчанию
+    pub fn load(file: PathBuf) -> Self {
+        let state = json_store::load(&file);
+        Self { file: Some(file), state: Mutex::new(state), wake: Notify::new() }
+    }
+
+    pub async fn settings(&self) -> NotificationSettings {
+        self.state.lock().await.settings.clone()
+    }
+
+    pub async fn update(&self, settings: NotificationSettings) -> AppResult<NotificationSettings> {
+        settings.validate()?;
+        let mut state = self.state.lock().await;
+        state.settings = settings.clone();
+        self.save(&state)?;
+        drop(state);
+        self.wake();
+        Ok(settings)
+    }
+
+    /// Просит планировщик проверить сроки, не дожидаясь очередного интервала
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Уведомления, которые пора показать вошедшему пользователю. Карточка попадает
+    /// в уведомление не чаще раза в день; в тихие часы ничего не показывается и не отмечается.
+    pub async fn pending(&self, app_state: &AppState, now: NaiveDateTime) -> AppResult<Vec<DesktopNotice>> {
+        let settings = self.settings().await;
+        if !settings.enabled || settings.is_quiet(now.time()) {
+            return Ok(Vec::new());
+        }
+        let Some(user) = app_state.signed_in_user().await else {
+            return Ok(Vec::new());
+        };
+        let db = app_state.db().await?;
+        let cards = db.get_all_control_cards().await?;
+
+        let today = now.date();
+        let mut state = self.state.lock().await;
+        if state.shown.date != Some(today) {
+            state.shown = ShownToday { date: Some(today), databases: BTreeMap::new() };
+        }
+        let shown = state.shown.databases.entry(user.db_path.clone()).or_default();
+
+        let due: Vec<_> = my_due_cards(&cards, user.user_id, today, settings.days_before)
+            .into_iter()
+            .filter(|(card, _)| !shown.contains(&card.id))
+            .collect();
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        shown.extend(due.iter().map(|(card, _)| card.id));
+        if let Err(e) = self.save(&state) {
+            eprintln!("Failed to save notification state: {}", e);
+        }
+
+        if due.len() > MAX_SEPARATE_NOTIFICATIONS {
+            return Ok(vec![summary_notice(&due)]);
+        }
+        Ok(due.into_iter().map(|(card, kind)| notice(card, kind)).collect())
+    }
+
+    fn save(&self, state: &StoredState) -> AppResult<()> {
+        json_store::save(self.file.as_deref(), state, "notification settings")
+    }
+}
+
+pub fn spawn_notification_scheduler<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            let center = app.state::<NotificationCenter>();
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = center.wake.notified() => {}
+            }
+
+            let state = app.state::<AppState>();
+            let notices = match center.pending(&state, chrono::Local::now().naive_local()).await {
+                Ok(notices) => notices,
+                Err(AppError::NotConnected) => continue,
+                Err(e) => {
+                    eprintln!("Desktop notifications failed: {}", e);
+                    continue;
+                }
+            };
+            for n in notices {
+                if let Err(e) = app.notification().builder().title(n.title).body(n.body).show() {
+                    eprintln!("Failed to show notification: {}", e);
+                }
+            }
+        }
+    });
+}
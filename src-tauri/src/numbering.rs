@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use crate::error::{AppError, AppResult};
+
+/// Схема нумерации карточек, например "{prefix}/{seq}" -> "01-12/345"
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct NumberingScheme {
+    pub id: i64,
+    pub name: String,
+    pub template: String,
+    pub prefix: Option<String>,
+    pub department_code: Option<String>,
+    pub reset_policy: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NumberingSchemeInput {
+    pub name: String,
+    pub template: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub department_code: Option<String>,
+    /// 'yearly' — счётчик начинается заново каждый год, 'never' — сквозная нумерация
+    #[serde(default = "default_reset_policy")]
+    pub reset_policy: String,
+}
+
+fn default_reset_policy() -> String {
+    "yearly".to_string()
+}
+
+/// Наибольшая ширина в {seq:N}
+const MAX_SEQ_WIDTH: usize = 12;
+
+/// Значения, подставляемые в шаблон номера
+pub struct NumberContext<'a> {
+    pub sequence: i64,
+    pub year: i32,
+    pub prefix: Option<&'a str>,
+    pub department_code: Option<&'a str>,
+}
+
+/// Период счётчика: год карточки при ежегодном сбросе, 0 — при сквозной нумерации
+pub fn counter_period(reset_policy: &str, year: i32) -> i32 {
+    match reset_policy {
+        "never" => 0,
+        _ => year,
+    }
+}
+
+pub fn validate_scheme(input: &NumberingSchemeInput) -> AppResult<()> {
+    if input.name.trim().is_empty() {
+        return Err(AppError::validation("name", "Numbering scheme name is required"));
+    }
+
+    if !matches!(input.reset_policy.as_str(), "yearly" | "never") {
+        return Err(AppError::validation("reset_policy", "Invalid reset policy"));
+    }
+
+    if !input.template.contains("{seq") {
+        return Err(AppError::validation("template", "Numbering template must contain {seq}"));
+    }
+
+    // Пробное форматирование выявляет неизвестные и незакрытые плейсхолдеры
+    format_number(
+        &input.template,
+        &NumberContext {
+            sequence: 1,
+            year: 2000,
+            prefix: input.prefix.as_deref(),
+            department_code: input.department_code.as_deref(),
+        },
+    )
+    .map(|_| ())
+}
+
+/// Подставляет значения в шаблон. Поддерживаются {seq}, {seq:N} (дополнение нулями до N знаков),
+/// {year}, {yy}, {prefix} и {dept}.
+pub fn format_number(template: &str, ctx: &NumberContext) -> AppResult<String> {
+    let mut result = String::with_capacity(template.len() + 8);
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| {
+                AppError::validation("template", format!("Unclosed placeholder in numbering template '{}'", template))
+            })?;
+        let placeholder = &after[..end];
+
+        let (name, width) = match placeholder.split_once(':') {
+            Some((name, width)) => {
+                let width: usize = width
+                    .parse()
+                    .ok()
+                    .filter(|width| *width <= MAX_SEQ_WIDTH)
+                    .ok_or_else(|| {
+                        AppError::validation(
+                            "template",
+                            format!("Invalid width in placeholder '{{{}}}', expected 0 to {}", placeholder, MAX_SEQ_WIDTH),
+                        )
+                    })?;
+                (name, width)
+            }
+            None => (placeholder, 0),
+        };
+
+        match name {
+            "seq" => result.push_str(&format!("{:0width$}", ctx.sequence, width = width)),
+            "year" => result.push_str(&ctx.year.to_string()),
+            "yy" => result.push_str(&format!("{:02}", ctx.year.rem_euclid(100))),
+            "prefix" => result.push_str(ctx.prefix.unwrap_or("")),
+            "dept" => result.push_str(ctx.department_code.unwrap_or("")),
+            _ => {
+                return Err(AppError::validation(
+                    "template",
+                    format!("Unknown placeholder '{{{}}}' in numbering template", placeholder),
+                ))
+            }
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
@@ -0,0 +1,182 @@
+use crate::auth::{verify_password, Claims};
+use crate::database::{ControlCard, Database};
+use crate::deadlines::{self, CardDates};
+use crate::error::{AppError, AppResult};
+use crate::models::{CardFilter, CardInput, ControlCardPatch, PatchValue, User, EXECUTOR_PATCH_FIELDS};
+use crate::state::AppState;
+
+// Операции, общие для команд Tauri и HTTP API: проверки прав живут здесь,
+// поэтому оба входа ведут себя одинаково.
+
+/// Возвращает username исполнителя, проверив, что это пользователь с ролью user
+pub async fn resolve_executor(db: &Database, executor_user_id: i64) -> AppResult<String> {
+    let executor_user = db.get_user_by_id(executor_user_id).await?
+        .ok_or_else(|| AppError::not_found("Executor user not found"))?;
+
+    if executor_user.role != "user" {
+        return Err(AppError::validation("executor_user_id", "Executor must be a user with role 'user'"));
+    }
+
+    Ok(executor_user.username)
+}
+
+/// Проверяет, что указанный контроллер (если есть) имеет роль controller
+pub async fn check_controller(db: &Database, controller_user_id: Option<i64>) -> AppResult<()> {
+    if let Some(controller_id) = controller_user_id {
+        if let Some(u) = db.get_user_by_id(controller_id).await? {
+            if u.role != "controller" {
+                return Err(AppError::validation("controller_user_id", "Controller must be a user with role 'controller'"));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn check_card_access(card: &ControlCard, user_id: i64, user_role: &str) -> AppResult<()> {
+    // Admin и controller видят все карточки
+    if user_role == "admin" || user_role == "controller" {
+        return Ok(());
+    }
+
+    // User видит только карточки, где он исполнитель
+    match card.executor_user_id {
+        Some(executor_id) if executor_id == user_id => Ok(()),
+        Some(_) => Err(AppError::forbidden("Access denied: you can only view cards where you are the executor")),
+        None => Err(AppError::not_found("Card not found or has no executor assigned")),
+    }
+}
+
+/// Проверяет логин и пароль, возвращает пользователя
+pub async fn authenticate(db: &Database, username: &str, password: &str) -> AppResult<User> {
+    let user = db.get_user_by_username(username).await?
+        .ok_or_else(|| AppError::unauthorized("Invalid username or password"))?;
+
+    if !verify_password(password, &user.password_hash)? {
+        return Err(AppError::unauthorized("Invalid username or password"));
+    }
+    Ok(user)
+}
+
+pub async fn current_user(db: &Database, claims: &Claims) -> AppResult<User> {
+    db.get_user_by_id(claims.sub).await
+        .and_then(|opt| opt.ok_or_else(|| AppError::not_found("User not found")))
+}
+
+pub async fn list_users(db: &Database, claims: &Claims) -> AppResult<Vec<User>> {
+    if claims.role != "admin" {
+        return Err(AppError::forbidden("Only admin can view all users"));
+    }
+    db.get_all_users().await
+}
+
+pub async fn list_executors(db: &Database, claims: &Claims) -> AppResult<Vec<User>> {
+    // Только admin или controller могут выбирать исполнителей
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can select executors"));
+    }
+
+    // Получаем всех пользователей с ролью 'user'
+    let all_users = db.get_all_users().await?;
+    Ok(all_users.into_iter().filter(|u| u.role == "user").collect())
+}
+
+pub async fn get_card(db: &Database, claims: &Claims, id: i64) -> AppResult<ControlCard> {
+    let card = db.get_control_card(id).await?;
+    check_card_access(&card, claims.sub, &claims.role)?;
+    Ok(card)
+}
+
+/// Карточки, доступные пользователю, с учётом отбора. Общая выборка для списка и выгрузок.
+pub async fn visible_cards(db: &Database, claims: &Claims, filter: &CardFilter) -> AppResult<Vec<ControlCard>> {
+    // Admin и controller видят все карточки
+    let cards = if claims.role == "admin" || claims.role == "controller" {
+        db.get_all_control_cards().await?
+    } else {
+        // User видит только карточки, где он исполнитель
+        db.get_control_cards_by_executor_id(claims.sub).await?
+    };
+
+    let today = deadlines::today();
+    Ok(cards.into_iter().filter(|card| filter.matches(card, today)).collect())
+}
+
+pub async fn create_card(state: &AppState, claims: &Claims, input: &CardInput) -> AppResult<i64> {
+    // Проверка прав: только admin или controller могут создавать карточки
+    if claims.role != "admin" && claims.role != "controller" {
+        return Err(AppError::forbidden("Only admin or controller can create control cards"));
+    }
+
+    let execution_deadline = deadlines::parse_optional_date("execution_deadline", input.execution_deadline.as_deref())?;
+    let extended_deadline = deadlines::parse_optional_date("extended_deadline", input.extended_deadline.as_deref())?;
+    deadlines::validate_card_dates(
+        &CardDates { year: input.year, execution_deadline, extended_deadline, completed_at: None },
+        deadlines::today(),
+        None,
+    )?;
+
+    let db = state.writable_db().await?;
+    let executor = resolve_executor(&db, input.executor_user_id).await?;
+    check_controller(&db, input.controller_user_id).await?;
+
+    db.create_control_card(
+        input.card_number,
+        input.year,
+        &executor,
+        &input.reporter,
+        &input.summary,
+        &input.document_reference,
+        Some(claims.sub),
+        Some(input.executor_user_id),
+        input.return_to.as_deref(),
+        execution_deadline,
+        input.execution_period_type.as_deref(),
+        extended_deadline,
+        input.resolution.as_deref(),
+        input.department.as_deref(),
+        input.controller.as_deref(),
+        input.controller_user_id,
+        input.numbering_scheme_id,
+    ).await
+}
+
+/// Частичное обновление: меняются только переданные поля.
+/// Исполнитель может менять в своих карточках лишь поля отчёта об исполнении.
+pub async fn patch_card(state: &AppState, claims: &Claims, id: i64, patch: &ControlCardPatch) -> AppResult<usize> {
+    let mut changes = patch.changes();
+
+    let db = state.writable_db().await?;
+    let card = db.get_control_card(id).await?;
+
+    if claims.role != "admin" && claims.role != "controller" {
+        if card.executor_user_id != Some(claims.sub) {
+            return Err(AppError::forbidden("Access denied: you can only update cards where you are the executor"));
+        }
+
+        if let Some((column, _)) = changes.iter().find(|(column, _)| !EXECUTOR_PATCH_FIELDS.contains(column)) {
+            return Err(AppError::forbidden(format!("Access denied: executor cannot change field '{}'", column)));
+        }
+    }
+
+    // Проверяем даты в том виде, какими они станут после применения изменений
+    let previous = card.dates();
+    let patched = CardDates {
+        year: patch.year.unwrap_or(previous.year),
+        execution_deadline: patch.execution_deadline.unwrap_or(previous.execution_deadline),
+        extended_deadline: patch.extended_deadline.unwrap_or(previous.extended_deadline),
+        completed_at: patch.completed_at.unwrap_or(previous.completed_at),
+    };
+    if patched != previous {
+        deadlines::validate_card_dates(&patched, deadlines::today(), Some(&previous))?;
+    }
+
+    if let Some(executor_user_id) = patch.executor_user_id {
+        let executor = resolve_executor(&db, executor_user_id).await?;
+        changes.push(("executor", PatchValue::Text(Some(executor))));
+    }
+
+    if let Some(controller_user_id) = patch.controller_user_id {
+        check_controller(&db, controller_user_id).await?;
+    }
+
+    db.patch_control_card(id, &changes, patch.expected_version, claims.sub).await
+}
@@ -0,0 +1,239 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use crate::database::ConnectionInfo;
+use crate::error::{AppError, AppResult};
+use crate::storage::ConnectionMode;
+
+/// Именованная БД, к которой рабочее место подключается регулярно
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseProfile {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub read_only: bool,
+    #[serde(default)]
+    pub mode: ConnectionMode,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileList {
+    /// Недавно использованные первыми
+    pub profiles: Vec<DatabaseProfile>,
+    pub active_id: Option<String>,
+}
+
+/// Результат переключения: сохранённый токен позволяет не входить заново
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileSwitch {
+    pub profile: DatabaseProfile,
+    pub connection: ConnectionInfo,
+    pub session_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct ProfileData {
+    profiles: Vec<DatabaseProfile>,
+    active_id: Option<String>,
+    /// Токен вошедшего пользователя по id профиля
+    #[serde(default)]
+    sessions: HashMap<String, String>,
+}
+
+/// Реестр профилей рабочего места. Хранится в JSON-файле в каталоге настроек приложения.
+#[derive(Default)]
+pub struct ProfileRegistry {
+    file: Option<PathBuf>,
+    data: RwLock<ProfileData>,
+}
+
+impl ProfileRegistry {
+    /// Повреждённый или отсутствующий файл даёт пустой реестр
+    pub fn load(file: PathBuf) -> Self {
+        let data = json_store::load(&file);
+        Self { file: Some(file), data: RwLock::new(data) }
+    }
+
+    pub async fn list(&self) -> ProfileList {
+        let data = self.data.read().await;
+        let mut profiles = data.profiles.clone();
+        profiles.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at).then_with(|| a.name.cmp(&b.name)));
+
+        ProfileList { profiles, active_id: data.active_id.clone() }
+    }
+
+    pub async fn get(&self, id: &str) -> AppResult<DatabaseProfile> {
+        self.data
+            .read()
+            .await
+            .profiles
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or_else(|| AppError::not_found(format!("Profile '{}' not found", id)))
+    }
+
+    pub async fn add(&self, name: &str, path: &str, read_only: bool, mode: ConnectionMode) -> AppResult<DatabaseProfile> {
+        let name = name.trim();
+        let path = path.trim();
+        if name.is_empty() {
+            return Err(AppError::validation("name", "Profile name is required"));
+        }
+        if path.is_empty() {
+            return Err(AppError::validation("path", "Database path is required"));
+        }
+
+        let mut data = self.data.write().await;
+        if data.profiles.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(AppError::conflict(format!("Profile '{}' already exists", name)));
+        }
+
+        let profile = DatabaseProfile {
+            id: new_profile_id(),
+            name: name.to_string(),
+            path: path.to_string(),
+            read_only,
+            mode,
+            last_used_at: None,
+        };
+        data.profiles.push(profile.clone());
+        self.save(&data)?;
+        Ok(profile)
+    }
+
+    /// Активный профиль удалить нельзя: сначала нужно переключиться на другой
+    pub async fn remove(&self, id: &str) -> AppResult<bool> {
+        let mut data = self.data.write().await;
+        if data.active_id.as_deref() == Some(id) {
+            return Err(AppError::conflict("Cannot remove the active profile"));
+        }
+
+        let before = data.profiles.len();
+        data.profiles.retain(|p| p.id != id);
+        if data.profiles.len() == before {
+            return Ok(false);
+        }
+        data.sessions.remove(id);
+        self.save(&data)?;
+        Ok(true)
+    }
+
+    /// Делает профиль активным и возвращает сохранённый для него токен
+    pub async fn activate(&self, id: &str) -> AppResult<(DatabaseProfile, Option<String>)> {
+        let mut data = self.data.write().await;
+        let profile = data
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::not_found(format!("Profile '{}' not found", id)))?;
+        profile.last_used_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        let profile = profile.clone();
+
+        data.active_id = Some(profile.id.clone());
+        let session = data.sessions.get(id).cloned();
+        self.save(&data)?;
+        Ok((profile, session))
+    }
+
+    /// Подключение к произвольному пути: активным становится профиль с этим путём, если он есть
+    pub async fn activate_path(&self, path: &str) -> AppResult<()> {
+        let id = self
+            .data
+            .read()
+            .await
+            .profiles
+            .iter()
+            .find(|p| same_path(&p.path, path))
+            .map(|p| p.id.clone());
+
+        match id {
+            Some(id) => self.activate(&id).await.map(|_| ()),
+            None => {
+                let mut data = self.data.write().await;
+                data.active_id = None;
+                self.save(&data)
+            }
+        }
+    }
+
+    /// Запоминает токен пользователя, вошедшего под активным профилем
+    pub async fn remember_session(&self, token: &str) -> AppResult<()> {
+        let mut data = self.data.write().await;
+        let Some(id) = data.active_id.clone() else {
+            return Ok(());
+        };
+        data.sessions.insert(id, token.to_string());
+        self.save(&data)
+    }
+
+    pub async fn forget_session(&self) -> AppResult<()> {
+        let mut data = self.data.write().await;
+        let Some(id) = data.active_id.clone() else {
+            return Ok(());
+        };
+        if data.sessions.remove(&id).is_some() {
+            self.save(&data)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, data: &ProfileData) -> AppResult<()> {
+        json_store::save(self.file.as_deref(), data, "profiles")
+    }
+}
+
+/// Пути Windows регистронезависимы
+fn same_path(a: &str, b: &str) -> bool {
+    let (a, b) = (a.replace('\\', "/"), b.replace('\\', "/"));
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(&b)
+    } else {
+        a == b
+    }
+}
+
+fn new_profile_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}", nanos)
+}
+
+/// JSON-файлы настроек рабочего места в каталоге настроек приложения
+pub mod json_store {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::path::Path;
+    use crate::error::{AppError, AppResult};
+
+    /// Повреждённый или отсутствующий файл даёт значение по умолчанию
+    pub fn load<T: DeserializeOwned + Default>(file: &Path) -> T {
+        std::fs::read_to_string(file)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Файл записывается целиком через временный файл, чтобы сбой не оставил его обрезанным.
+    /// Без файла (None) ничего не сохраняется; what попадает в текст ошибки.
+    pub fn save<T: Serialize>(file: Option<&Path>, value: &T, what: &str) -> AppResult<()> {
+        let Some(file) = file else {
+            return Ok(());
+        };
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AppError::db(format!("Failed to create settings directory: {}", e)))?;
+        }
+
+        let content = serde_json::to_string_pretty(value)
+            .map_err(|e| AppError::db(format!("Failed to serialize {}: {}", what, e)))?;
+        let temp = file.with_extension("json.tmp");
+        std::fs::write(&temp, content)
+            .and_then(|_| std::fs::rename(&temp, file))
+            .map_err(|e| AppError::db(format!("Failed to save {}: {}", what, e)))
+    }
+}
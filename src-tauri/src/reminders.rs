@@ -0,0 +1,312 @@
+use chrono::NaiveDate;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use crate::database::{ControlCard, Database};
+use crate::deadlines;
+use crate::error::{AppError, AppResult};
+use crate::models::User;
+use crate::state::AppState;
+
+/// Как часто проверяется, кому пора напомнить о сроке
+const REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Сколько дней после срока ещё отправляется письмо о просрочке.
+/// Не даёт разослать письма по всем старым карточкам сразу после включения рассылки.
+const OVERDUE_LOOKBACK_DAYS: i64 = 7;
+
+/// Сколько раз пробуем отправить письмо, прежде чем отказаться
+pub const MAX_SEND_ATTEMPTS: i64 = 3;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Без шифрования, например локальный MailHog
+    None,
+    StartTls,
+    /// TLS с момента подключения, обычно порт 465
+    Tls,
+}
+
+/// Настройки почтового сервера для напоминаний, общие для всех рабочих мест
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct SmtpSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: i64,
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Пароль наружу не отдаётся; при сохранении пустой пароль оставляет прежний
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    pub from_address: String,
+    /// За сколько дней до срока напоминать; 0 — только в день срока и при просрочке
+    pub remind_days_before: i64,
+}
+
+impl SmtpSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        if !(0..=60).contains(&self.remind_days_before) {
+            return Err(AppError::validation("remind_days_before", "Reminder lead time must be between 0 and 60 days"));
+        }
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.host.trim().is_empty() {
+            return Err(AppError::validation("host", "SMTP server is required"));
+        }
+        if !(1..=65535).contains(&self.port) {
+            return Err(AppError::validation("port", "Port must be between 1 and 65535"));
+        }
+        self.sender()?;
+        Ok(())
+    }
+
+    fn sender(&self) -> AppResult<Mailbox> {
+        self.from_address
+            .trim()
+            .parse()
+            .map_err(|_| AppError::validation("from_address", "Invalid sender address"))
+    }
+
+    fn transport(&self) -> AppResult<AsyncSmtpTransport<Tokio1Executor>> {
+        let host = self.host.trim();
+        let builder = match self.security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| AppError::validation("host", format!("Invalid SMTP server: {}", e)))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| AppError::validation("host", format!("Invalid SMTP server: {}", e)))?,
+        };
+        let mut builder = builder.port(self.port as u16).timeout(Some(SMTP_TIMEOUT));
+
+        if let Some(username) = self.username.as_deref().filter(|u| !u.is_empty()) {
+            let password = self.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.to_string(), password));
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Пустой адрес означает «без почты»; непустой должен быть корректным
+pub fn normalize_email(email: Option<&str>) -> AppResult<Option<String>> {
+    let Some(email) = email.map(str::trim).filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+    email
+        .parse::<Address>()
+        .map(|_| Some(email.to_string()))
+        .map_err(|_| AppError::validation("email", "Invalid e-mail address"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderKind {
+    DueSoon,
+    DueToday,
+    Overdue,
+}
+
+impl ReminderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReminderKind::DueSoon => "due_soon",
+            ReminderKind::DueToday => "due_today",
+            ReminderKind::Overdue => "overdue",
+        }
+    }
+
+    /// Какое напоминание положено карточке сегодня, если положено
+    pub fn for_deadline(deadline: NaiveDate, today: NaiveDate, days_before: i64) -> Option<Self> {
+        let days_left = (deadline - today).num_days();
+        match days_left {
+            0 => Some(ReminderKind::DueToday),
+            d if d > 0 && d <= days_before => Some(ReminderKind::DueSoon),
+            d if d < 0 && -d <= OVERDUE_LOOKBACK_DAYS => Some(ReminderKind::Overdue),
+            _ => None,
+        }
+    }
+
+    fn subject_template(self) -> &'static str {
+        match self {
+            ReminderKind::DueSoon => "Приближается срок исполнения карточки {number} — {deadline}",
+            ReminderKind::DueToday => "Сегодня срок исполнения карточки {number}",
+            ReminderKind::Overdue => "Просрочено исполнение карточки {number}",
+        }
+    }
+
+    fn message_template(self) -> &'static str {
+        match self {
+            ReminderKind::DueSoon => "До срока исполнения осталось дней: {days}.",
+            ReminderKind::DueToday => "Сегодня последний день срока исполнения.",
+            ReminderKind::Overdue => "Срок исполнения истёк {deadline}, карточка не отмечена исполненной.",
+        }
+    }
+}
+
+const BODY_TEMPLATE: &str = "Здравствуйте, {name}!
+
+{message}
+
+Карточка: {number}
+Содержание: {summary}
+Документ: {document}
+Исполнитель: {executor}
+Срок исполнения: {deadline}
+
+Это автоматическое напоминание, отвечать на него не нужно.
+";
+
+/// Подставляет значения вместо {имя} в шаблон
+fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Тема и текст письма о карточке
+pub fn compose(kind: ReminderKind, card: &ControlCard, deadline: NaiveDate, recipient: &User, today: NaiveDate) -> (String, String) {
+    let number = card
+        .formatted_number
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}", card.card_number, card.year));
+    let deadline_text = deadline.format("%d.%m.%Y").to_string();
+    let days = (deadline - today).num_days().to_string();
+    let name = recipient.full_name.clone().unwrap_or_else(|| recipient.username.clone());
+
+    let mut vars = vec![
+        ("number", number.as_str()),
+        ("deadline", deadline_text.as_str()),
+        ("days", days.as_str()),
+    ];
+    let message = render(kind.message_template(), &vars);
+    let subject = render(kind.subject_template(), &vars);
+
+    vars.extend([
+        ("name", name.as_str()),
+        ("message", message.as_str()),
+        ("summary", card.summary.as_str()),
+        ("document", card.document_reference.as_str()),
+        ("executor", card.executor.as_str()),
+    ]);
+    (subject, render(BODY_TEMPLATE, &vars))
+}
+
+async fn send(
+    transport: &AsyncSmtpTransport<Tokio1Executor>,
+    from: &Mailbox,
+    to: &User,
+    email: &str,
+    subject: String,
+    body: String,
+) -> Result<(), String> {
+    let address: Address = email.parse().map_err(|e| format!("Invalid address: {}", e))?;
+    let message = Message::builder()
+        .from(from.clone())
+        .to(Mailbox::new(to.full_name.clone(), address))
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|e| e.to_string())?;
+
+    transport.send(message).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Отправляет пробное письмо, чтобы проверить настройки сервера
+pub async fn send_test_email(settings: &SmtpSettings, to: &User, email: &str) -> AppResult<()> {
+    let from = settings.sender()?;
+    let transport = settings.transport()?;
+    let body = format!(
+        "Здравствуйте, {}!\n\nЭто пробное письмо: напоминания о сроках карточек будут приходить с этого адреса.\n",
+        to.full_name.as_deref().unwrap_or(&to.username)
+    );
+    send(&transport, &from, to, email, "Проверка почтовых напоминаний".to_string(), body)
+        .await
+        .map_err(|e| AppError::validation("smtp", format!("Failed to send e-mail: {}", e)))
+}
+
+/// Рассылает напоминания, которые положены сегодня и ещё не отправлены.
+/// Возвращает число отправленных писем.
+pub async fn run_reminders(db: &Database, today: NaiveDate) -> AppResult<usize> {
+    // Отметку об отправке в БД только для чтения не записать; рассылают клиенты с правом записи
+    if db.is_read_only() {
+        return Ok(0);
+    }
+    let settings = db.get_smtp_settings().await?;
+    if !settings.enabled {
+        return Ok(0);
+    }
+    let from = settings.sender()?;
+    let transport = settings.transport()?;
+
+    let users: HashMap<i64, User> = db.get_all_users().await?.into_iter().map(|u| (u.id, u)).collect();
+    let mut sent = 0;
+
+    for card in db.get_all_control_cards().await? {
+        let dates = card.dates();
+        if dates.completed_at.is_some() {
+            continue;
+        }
+        let Some(deadline) = dates.effective_deadline() else {
+            continue;
+        };
+        let Some(kind) = ReminderKind::for_deadline(deadline, today, settings.remind_days_before) else {
+            continue;
+        };
+
+        let mut recipients: Vec<&User> = [card.executor_user_id, card.controller_user_id]
+            .into_iter()
+            .flatten()
+            .filter_map(|id| users.get(&id))
+            .collect();
+        recipients.dedup_by_key(|u| u.id);
+
+        for user in recipients {
+            let Some(email) = user.email.as_deref().filter(|e| !e.is_empty()) else {
+                continue;
+            };
+            // Запись ставится до отправки, поэтому письмо отправляет одно рабочее место
+            let Some(id) = db.claim_notification(card.id, email, kind.as_str(), deadline).await? else {
+                continue;
+            };
+
+            let (subject, body) = compose(kind, &card, deadline, user, today);
+            match send(&transport, &from, user, email, subject, body).await {
+                Ok(()) => {
+                    db.finish_notification(id, None).await?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    eprintln!("Failed to send reminder for card {} to {}: {}", card.id, email, e);
+                    db.finish_notification(id, Some(&e)).await?;
+                }
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+pub fn spawn_reminder_scheduler<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REMINDER_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.db().await else {
+                continue;
+            };
+            if let Err(e) = run_reminders(&db, deadlines::today()).await {
+                eprintln!("E-mail reminders failed: {}", e);
+            }
+        }
+    });
+}
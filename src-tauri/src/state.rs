@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use crate::archive::{self, ArchiveDb};
+use crate::auth::{self, Claims};
+use crate::backup;
+use crate::diagnostics;
+use crate::database::{ChangeLogEntry, ConnectionInfo, Database};
+use crate::error::{AppError, AppResult};
+use crate::storage::ConnectionMode;
+
+/// Как часто клиент с правом записи отмечается в client_sessions
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Состояние приложения, управляемое Tauri.
+/// Пул соединений клонируется в каждую команду, поэтому блокировка держится
+/// только на время получения или замены подключения.
+#[derive(Default)]
+pub struct AppState {
+    db: RwLock<Option<Database>>,
+    heartbeat: Mutex<Option<JoinHandle<()>>>,
+    /// Последняя запись журнала изменений, о которой уже сообщили окнам
+    change_cursor: Mutex<i64>,
+    /// Архивы, подключённые для поиска, по годам
+    archives: RwLock<BTreeMap<i32, ArchiveDb>>,
+    /// Пользователь, вошедший на этом рабочем месте; нужен фоновым уведомлениям
+    signed_in: RwLock<Option<SignedInUser>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignedInUser {
+    /// БД, в которой выполнен вход: после переключения на другую БД вход не действует
+    pub db_path: String,
+    pub user_id: i64,
+    pub role: String,
+}
+
+impl AppState {
+    /// Состояние с уже открытой БД, без фоновых задач подключения
+    #[cfg(test)]
+    pub fn with_database(db: Database) -> Self {
+        Self { db: RwLock::new(Some(db)), ..Default::default() }
+    }
+
+    pub async fn db(&self) -> AppResult<Database> {
+        self.db.read().await.clone().ok_or(AppError::NotConnected)
+    }
+
+    /// Подключение для изменяющих команд: в БД, открытой только для чтения, они запрещены
+    pub async fn writable_db(&self) -> AppResult<Database> {
+        let db = self.db().await?;
+        if db.is_read_only() {
+            return Err(AppError::ReadOnly);
+        }
+        Ok(db)
+    }
+
+    /// Запоминает вошедшего пользователя для текущей БД
+    pub async fn sign_in(&self, user_id: i64, role: &str) {
+        let Some(db_path) = self.path().await else {
+            return;
+        };
+        *self.signed_in.write().await = Some(SignedInUser { db_path, user_id, role: role.to_string() });
+    }
+
+    pub async fn sign_out(&self) {
+        self.signed_in.write().await.take();
+    }
+
+    /// Вошедший пользователь, если вход выполнен в подключённую сейчас БД
+    pub async fn signed_in_user(&self) -> Option<SignedInUser> {
+        let path = self.path().await?;
+        self.signed_in.read().await.clone().filter(|user| user.db_path == path)
+    }
+
+    /// Токен, выданный для подключённой сейчас БД
+    pub async fn verify_token(&self, token: &str) -> AppResult<Claims> {
+        let path = self.path().await.ok_or(AppError::NotConnected)?;
+        auth::verify_token(token, &path)
+    }
+
+    pub async fn is_connected(&self) -> bool {
+        self.db.read().await.is_some()
+    }
+
+    pub async fn path(&self) -> Option<String> {
+        self.db.read().await.as_ref().map(|db| db.get_path().to_string())
+    }
+
+    /// Подключается к БД, закрывая предыдущее подключение
+    pub async fn connect(
+        &self,
+        db_path: &str,
+        mode: ConnectionMode,
+        key: Option<&str>,
+        read_only: bool,
+    ) -> AppResult<ConnectionInfo> {
+        let database = Database::connect(db_path, mode, key, read_only).await?;
+        let info = database.connection_info().clone();
+        let latest_seq = database.latest_change_seq().await?;
+
+        self.stop_heartbeat().await;
+        self.detach_all_archives().await;
+        if info.client_id.is_some() {
+            let db = database.clone();
+            *self.heartbeat.lock().await = Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = db.touch_client_session().await {
+                        eprintln!("Heartbeat failed: {}", e);
+                    }
+                }
+            }));
+        }
+
+        // Изменения, сделанные до подключения, уже отражены в загруженных данных
+        let mut cursor = self.change_cursor.lock().await;
+        let previous = self.db.write().await.replace(database);
+        *cursor = latest_seq;
+        drop(cursor);
+
+        if let Some(previous) = previous {
+            previous.close().await;
+        }
+        Ok(info)
+    }
+
+    pub async fn disconnect(&self) {
+        self.stop_heartbeat().await;
+        self.detach_all_archives().await;
+        let previous = self.db.write().await.take();
+        if let Some(previous) = previous {
+            previous.close().await;
+        }
+    }
+
+    /// Заменяет файл БД проверенной резервной копией и переподключается к нему.
+    /// Текущее состояние предварительно сохраняется в каталог резервных копий.
+    /// backup_key — ключ копии, если он отличается от ключа текущей БД; после
+    /// восстановления БД открывается этим ключом.
+    pub async fn restore(&self, src: &Path, backup_key: Option<Option<&str>>) -> AppResult<ConnectionInfo> {
+        let db = self.writable_db().await?;
+        let info = db.connection_info().clone();
+        let key = backup_key.unwrap_or(db.key()).map(str::to_string);
+        backup::validate_backup(src, key.as_deref()).await?;
+        ensure_single_client(&db, &info, "restoring").await?;
+
+        let settings = db.get_backup_settings().await?;
+        let safety = backup::backup_directory(&info.path, &settings).join(backup::pre_restore_file_name(&info.path));
+        db.backup_to(&safety).await?;
+        drop(db);
+
+        self.disconnect().await;
+        let replaced = backup::replace_database_file(src, Path::new(&info.path));
+        // Переподключаемся в любом случае: при ошибке замены остаётся прежний файл
+        let connected = self.connect(&info.path, info.mode, key.as_deref(), false).await;
+        replaced?;
+        connected
+    }
+
+    /// Перешифровывает БД и её архивы новым ключом (None — снять шифрование)
+    /// и переподключается с ним. Файлы заменяются только после успешной перешифровки.
+    pub async fn rekey(&self, new_key: Option<&str>) -> AppResult<ConnectionInfo> {
+        let db = self.writable_db().await?;
+        let info = db.connection_info().clone();
+        let key = db.key().map(str::to_string);
+        ensure_single_client(&db, &info, "changing the encryption key").await?;
+
+        let path = Path::new(&info.path);
+        let temp = path.with_extension("rekey.tmp");
+        let _ = std::fs::remove_file(&temp);
+        db.export_with_key(&temp, new_key).await?;
+
+        let mut archives = Vec::new();
+        let mut checksums = Vec::new();
+        for archive_info in db.get_archives().await? {
+            let archive_path = archive::archive_path(&info.path, &archive_info.file_name);
+            if !archive_path.exists() {
+                continue;
+            }
+            match archive::export_with_key(&archive_path, key.as_deref(), new_key).await {
+                Ok((archive_temp, checksum)) => {
+                    archives.push((archive_temp, archive_path));
+                    checksums.push((archive_info.year, checksum));
+                }
+                Err(e) => {
+                    let _ = std::fs::remove_file(&temp);
+                    for (archive_temp, _) in &archives {
+                        let _ = std::fs::remove_file(archive_temp);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        archive::record_checksums(&temp, new_key, &checksums).await?;
+        drop(db);
+
+        self.disconnect().await;
+        let mut replaced = backup::replace_database_file(&temp, path);
+        let _ = std::fs::remove_file(&temp);
+        for (archive_temp, archive_path) in &archives {
+            if replaced.is_ok() {
+                replaced = std::fs::rename(archive_temp, archive_path)
+                    .map_err(|e| AppError::db(format!("Failed to replace archive file: {}", e)));
+            }
+            let _ = std::fs::remove_file(archive_temp);
+        }
+
+        let key = if replaced.is_ok() { new_key } else { key.as_deref() };
+        let connected = self.connect(&info.path, info.mode, key, false).await;
+        replaced?;
+        connected
+    }
+
+    /// Новые записи журнала изменений с момента предыдущего вызова
+    pub async fn take_changes(&self) -> AppResult<Vec<ChangeLogEntry>> {
+        let mut cursor = self.change_cursor.lock().await;
+        let Some(db) = self.db.read().await.clone() else {
+            return Ok(Vec::new());
+        };
+
+        let changes = db.get_changes_since(*cursor).await?;
+        if let Some(last) = changes.last() {
+            *cursor = last.seq;
+        }
+        Ok(changes)
+    }
+
+    pub async fn attach_archive(&self, archive: ArchiveDb) {
+        let previous = self.archives.write().await.insert(archive.year, archive);
+        if let Some(previous) = previous {
+            previous.close().await;
+        }
+    }
+
+    pub async fn detach_archive(&self, year: i32) -> bool {
+        let removed = self.archives.write().await.remove(&year);
+        match removed {
+            Some(archive) => {
+                archive.close().await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn attached_archives(&self) -> Vec<ArchiveDb> {
+        self.archives.read().await.values().cloned().collect()
+    }
+
+    /// Архивы относятся к конкретной БД и отключаются при смене подключения
+    async fn detach_all_archives(&self) {
+        let archives = std::mem::take(&mut *self.archives.write().await);
+        for archive in archives.into_values() {
+            archive.close().await;
+        }
+    }
+
+    async fn stop_heartbeat(&self) {
+        if let Some(handle) = self.heartbeat.lock().await.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// Файл нельзя подменить под работающими клиентами
+async fn ensure_single_client(db: &Database, info: &ConnectionInfo, action: &str) -> AppResult<()> {
+    let others: Vec<String> = db
+        .get_client_sessions()
+        .await?
+        .into_iter()
+        .filter(|s| s.active && Some(&s.client_id) != info.client_id.as_ref())
+        .map(|s| s.hostname)
+        .collect();
+
+    if !others.is_empty() {
+        return Err(AppError::conflict_with(
+            format!("Other workstations are connected; close the application on them before {}", action),
+            &others,
+        ));
+    }
+    // Клиенты только для чтения в client_sessions не попадают
+    if diagnostics::is_in_use(Path::new(&info.path), db.key()).await {
+        return Err(AppError::conflict(format!(
+            "Database is in use by another process; close the application on other workstations before {}",
+            action
+        )));
+    }
+    Ok(())
+}
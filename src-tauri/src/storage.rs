@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Режим работы с файлом БД
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionMode {
+    /// Файл используется одним рабочим местом
+    #[default]
+    Single,
+    /// Несколько рабочих мест работают с одним файлом (например, в общей папке)
+    MultiUser,
+}
+
+/// Где физически лежит файл БД
+#[derive(Debug, Serialize, Clone)]
+pub struct StorageInfo {
+    pub network: bool,
+    /// Тип файловой системы, если его удалось определить
+    pub filesystem: Option<String>,
+}
+
+/// Сколько раз повторяется операция, упёршаяся в блокировку БД
+pub const BUSY_RETRY_ATTEMPTS: u32 = 5;
+
+const BUSY_RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Задержка перед повтором: 100, 200, 400, 800 мс
+pub fn busy_retry_delay(attempt: u32) -> Duration {
+    BUSY_RETRY_BASE_DELAY * 2u32.pow(attempt.min(4))
+}
+
+/// SQLITE_BUSY / SQLITE_LOCKED, в том числе расширенные коды (BUSY_SNAPSHOT и т.п.)
+pub fn is_busy_error(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db_error) = error else {
+        return false;
+    };
+
+    db_error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smbfs", "smb3", "9p", "afs", "ceph", "glusterfs", "davfs", "fuse.sshfs",
+];
+
+/// Определяет, находится ли файл на сетевом диске. Блокировки SQLite на сетевых
+/// файловых системах ненадёжны, а WAL на них не работает вовсе.
+pub fn detect_storage(db_path: &str) -> StorageInfo {
+    let path = Path::new(db_path);
+    // Для ещё не созданного файла смотрим на каталог
+    let resolved = std::fs::canonicalize(path)
+        .or_else(|_| path.parent().map(std::fs::canonicalize).unwrap_or_else(|| Ok(path.to_path_buf())))
+        .unwrap_or_else(|_| path.to_path_buf());
+    let resolved = resolved.to_string_lossy().replace('\\', "/");
+
+    // UNC-путь; подключённые сетевые диски Windows canonicalize тоже приводит к UNC
+    if resolved.starts_with("//?/UNC/") || (resolved.starts_with("//") && !resolved.starts_with("//?/")) {
+        return StorageInfo { network: true, filesystem: Some("smb".to_string()) };
+    }
+
+    match mount_filesystem(&resolved) {
+        Some(filesystem) => StorageInfo {
+            network: NETWORK_FILESYSTEMS.contains(&filesystem.as_str()),
+            filesystem: Some(filesystem),
+        },
+        None => StorageInfo { network: false, filesystem: None },
+    }
+}
+
+/// Тип файловой системы по /proc/mounts (самая длинная подходящая точка монтирования)
+fn mount_filesystem(path: &str) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let _device = parts.next()?;
+            let mount_point = parts.next()?.replace("\\040", " ");
+            let filesystem = parts.next()?;
+            let matches = path == mount_point
+                || mount_point == "/"
+                || path.starts_with(&format!("{}/", mount_point));
+            matches.then(|| (mount_point.len(), filesystem.to_string()))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, filesystem)| filesystem)
+}
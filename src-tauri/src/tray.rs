@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewWindow};
+use crate::deadlines;
+use crate::notifications::DueCounts;
+use crate::state::{AppState, SignedInUser};
+
+pub const TRAY_ID: &str = "main";
+
+/// Событие для окна: какое действие выбрано в меню значка
+pub const TRAY_ACTION: &str = "tray-action";
+
+const MAIN_WINDOW: &str = "main";
+const APP_TITLE: &str = "Календарь контрольных карточек";
+
+/// Счётчики меняются и без изменений карточек — со сменой дня
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const BADGE_COLOR: [u8; 4] = [0xd3, 0x2f, 0x2f, 0xff];
+const BADGE_TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+/// Цифры и «+» размером 3×5 точек: строка на элемент, старший из трёх битов — левая точка
+const GLYPHS: [(char, [u8; 5]); 11] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+];
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    OpenCalendar,
+    NewCard,
+    ShowOverdue,
+}
+
+impl TrayAction {
+    fn from_menu_id(id: &str) -> Option<Self> {
+        match id {
+            "open_calendar" => Some(TrayAction::OpenCalendar),
+            "new_card" => Some(TrayAction::NewCard),
+            "show_overdue" => Some(TrayAction::ShowOverdue),
+            _ => None,
+        }
+    }
+}
+
+/// Пункт меню, доступность которого зависит от роли вошедшего пользователя
+struct TrayMenu<R: Runtime> {
+    new_card: MenuItem<R>,
+}
+
+pub fn setup_tray<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    let open = MenuItem::with_id(app, "open_calendar", "Открыть календарь", true, None::<&str>)?;
+    let new_card = MenuItem::with_id(app, "new_card", "Новая карточка", false, None::<&str>)?;
+    let overdue = MenuItem::with_id(app, "show_overdue", "Показать просроченные", true, None::<&str>)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let quit = MenuItem::with_id(app, "quit", "Выход", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&open, &new_card, &overdue, &separator, &quit])?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(APP_TITLE)
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| {
+            if event.id.as_ref() == "quit" {
+                app.exit(0);
+            } else if let Some(action) = TrayAction::from_menu_id(event.id.as_ref()) {
+                run_action(app, action);
+            }
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayMenu { new_card });
+    Ok(())
+}
+
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Показывает окно и передаёт ему действие; переход по страницам выполняет окно
+fn run_action<R: Runtime>(app: &AppHandle<R>, action: TrayAction) {
+    show_main_window(app);
+    if let Err(e) = app.emit_to(MAIN_WINDOW, TRAY_ACTION, action) {
+        eprintln!("Failed to emit {}: {}", TRAY_ACTION, e);
+    }
+}
+
+/// Рисует красный кружок со счётчиком в правом нижнем углу изображения RGBA.
+/// Больше девяти показывается как «9+».
+fn draw_badge(rgba: &mut [u8], width: u32, height: u32, diameter: u32, count: usize) {
+    let diameter = diameter.min(width).min(height);
+    let (left, top) = (width - diameter, height - diameter);
+    let mut put = |x: u32, y: u32, color: &[u8; 4]| {
+        let i = ((y * width + x) * 4) as usize;
+        if let Some(pixel) = rgba.get_mut(i..i + 4) {
+            pixel.copy_from_slice(color);
+        }
+    };
+
+    let radius = diameter as f32 / 2.0;
+    for y in top..height {
+        for x in left..width {
+            let dx = (x - left) as f32 + 0.5 - radius;
+            let dy = (y - top) as f32 + 0.5 - radius;
+            if dx * dx + dy * dy <= radius * radius {
+                put(x, y, &BADGE_COLOR);
+            }
+        }
+    }
+
+    let text = if count > 9 { "9+".to_string() } else { count.to_string() };
+    let glyphs: Vec<[u8; 5]> = text
+        .chars()
+        .filter_map(|c| GLYPHS.iter().find(|(glyph, _)| *glyph == c).map(|(_, rows)| *rows))
+        .collect();
+    // Между символами одна пустая колонка; текст занимает около двух третей кружка
+    let columns = glyphs.len() as u32 * 4 - 1;
+    let scale = (diameter * 2 / 3 / columns.max(5)).max(1);
+    let x0 = left + diameter.saturating_sub(columns * scale) / 2;
+    let y0 = top + diameter.saturating_sub(5 * scale) / 2;
+
+    for (i, rows) in glyphs.iter().enumerate() {
+        for (row, bits) in rows.iter().enumerate() {
+            for column in (0..3).filter(|column| bits & (0b100 >> column) != 0) {
+                let x = x0 + (i as u32 * 4 + column) * scale;
+                let y = y0 + row as u32 * scale;
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    if x + dx < width && y + dy < height {
+                        put(x + dx, y + dy, &BADGE_TEXT_COLOR);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Значок приложения со счётчиком. В Windows у значка в области уведомлений нет подписи,
+/// поэтому число рисуется прямо на нём.
+fn icon_with_badge(icon: &Image<'_>, count: Option<usize>) -> Image<'static> {
+    let mut rgba = icon.rgba().to_vec();
+    if let Some(count) = count {
+        let diameter = (icon.width().min(icon.height()) * 3 / 5).max(10);
+        draw_badge(&mut rgba, icon.width(), icon.height(), diameter, count);
+    }
+    Image::new(&rgba, icon.width(), icon.height()).to_owned()
+}
+
+/// Счётчик на кнопке окна в панели задач Windows — наложенный значок
+#[cfg(windows)]
+fn set_window_badge<R: Runtime>(window: &WebviewWindow<R>, count: Option<usize>) -> tauri::Result<()> {
+    const SIZE: u32 = 32;
+    let overlay = count.map(|count| {
+        let mut rgba = vec![0; (SIZE * SIZE * 4) as usize];
+        draw_badge(&mut rgba, SIZE, SIZE, SIZE, count);
+        Image::new(&rgba, SIZE, SIZE).to_owned()
+    });
+    window.set_overlay_icon(overlay)
+}
+
+#[cfg(not(windows))]
+fn set_window_badge<R: Runtime>(window: &WebviewWindow<R>, count: Option<usize>) -> tauri::Result<()> {
+    window.set_badge_count(count.map(|n| n as i64))
+}
+
+/// Закрытие главного окна сворачивает приложение в область уведомлений
+pub fn hide_on_close<R: Runtime>(window: &tauri::Window<R>, event: &tauri::WindowEvent) {
+    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == MAIN_WINDOW {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+    }
+}
+
+async fn due_counts(state: &AppState, user: &SignedInUser) -> DueCounts {
+    let Ok(db) = state.db().await else {
+        return DueCounts::default();
+    };
+    match db.get_all_control_cards().await {
+        Ok(cards) => DueCounts::of(&cards, user.user_id, deadlines::today()),
+        Err(e) => {
+            eprintln!("Failed to count due cards: {}", e);
+            DueCounts::default()
+        }
+    }
+}
+
+/// Обновляет подсказку, счётчик значка и меню по карточкам вошедшего пользователя
+pub async fn refresh<R: Runtime>(app: &AppHandle<R>) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let user = state.signed_in_user().await;
+
+    let counts = match &user {
+        Some(user) => due_counts(&state, user).await,
+        None => DueCounts::default(),
+    };
+    let tooltip = match &user {
+        Some(_) => format!("{}\nСрок сегодня: {}\nПросрочено: {}", APP_TITLE, counts.due_today, counts.overdue),
+        None => APP_TITLE.to_string(),
+    };
+    let badge = (counts.total() > 0).then_some(counts.total());
+
+    if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+        eprintln!("Failed to update tray tooltip: {}", e);
+    }
+    if let Some(icon) = app.default_window_icon() {
+        if let Err(e) = tray.set_icon(Some(icon_with_badge(icon, badge))) {
+            eprintln!("Failed to update tray icon: {}", e);
+        }
+    }
+    // Подпись рядом со значком есть в macOS и Linux, в Windows её заменяет кружок на значке
+    #[cfg(not(windows))]
+    if let Err(e) = tray.set_title(badge.map(|n| n.to_string())) {
+        eprintln!("Failed to update tray title: {}", e);
+    }
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        if let Err(e) = set_window_badge(&window, badge) {
+            eprintln!("Failed to update window badge: {}", e);
+        }
+    }
+
+    if let Some(menu) = app.try_state::<TrayMenu<R>>() {
+        let can_create = user.is_some_and(|u| u.role == "admin" || u.role == "controller");
+        if let Err(e) = menu.new_card.set_enabled(can_create) {
+            eprintln!("Failed to update tray menu: {}", e);
+        }
+    }
+}
+
+pub fn spawn_tray_refresher<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh(&app).await;
+        }
+    });
+}
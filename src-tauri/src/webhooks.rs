@@ -0,0 +1,479 @@
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::Sha256;
+use sqlx::FromRow;
+use std::collections::HashSet;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use crate::database::{ControlCard, Database};
+use crate::deadlines::{self, CardStatus};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+pub const EVENT_CARD_CREATED: &str = "card.created";
+pub const EVENT_CARD_CLOSED: &str = "card.closed";
+pub const EVENT_CARD_OVERDUE: &str = "card.overdue";
+
+pub const EVENTS: &[&str] = &[EVENT_CARD_CREATED, EVENT_CARD_CLOSED, EVENT_CARD_OVERDUE];
+
+/// Как часто очередь пополняется новыми событиями и отправляется
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// За сколько дней назад ищутся события; более старые считаются уже обработанными
+pub const EVENT_LOOKBACK_DAYS: i64 = 7;
+
+/// Паузы между повторными попытками, в минутах. После последней доставка считается неудачной.
+const RETRY_DELAYS_MINUTES: &[i64] = &[1, 5, 30, 120, 720];
+
+/// Сколько доставок отправляется за один проход
+const DELIVERY_BATCH: i64 = 20;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Serialize, Clone, FromRow)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Секрет нужен только для подписи и наружу не отдаётся
+    #[serde(skip_serializing)]
+    pub secret: String,
+    #[serde(serialize_with = "serialize_events")]
+    pub events: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn subscribed(&self, event: &str) -> bool {
+        self.events.split(',').any(|e| e == event)
+    }
+}
+
+fn serialize_events<S: Serializer>(events: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(events.split(',').filter(|e| !e.is_empty()))
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookInput {
+    pub url: String,
+    /// При изменении пустой секрет оставляет прежний
+    #[serde(default)]
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookInput {
+    /// Проверяет адрес и события, возвращает события в виде строки для БД
+    pub fn validate(&self) -> AppResult<String> {
+        let url = reqwest::Url::parse(self.url.trim())
+            .map_err(|_| AppError::validation("url", "Invalid webhook URL"))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(AppError::validation("url", "Webhook URL must use http or https"));
+        }
+
+        if self.events.is_empty() {
+            return Err(AppError::validation("events", "Select at least one event"));
+        }
+        if let Some(unknown) = self.events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(AppError::validation("events", format!("Unknown event '{}'", unknown)));
+        }
+
+        // Порядок как в EVENTS, без повторов
+        Ok(EVENTS
+            .iter()
+            .filter(|e| self.events.iter().any(|s| s == *e))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(","))
+    }
+
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref().map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+/// Запись журнала доставки
+#[derive(Debug, Serialize, Clone, FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub url: Option<String>,
+    pub event: String,
+    pub card_id: i64,
+    pub payload: String,
+    /// pending, sending, delivered или failed
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: String,
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+pub struct NewDelivery {
+    pub webhook_id: i64,
+    pub event: &'static str,
+    pub card_id: i64,
+    /// Одно и то же событие карточки ставится в очередь вебхука только один раз
+    pub event_key: String,
+    pub payload: String,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: &'a str,
+    occurred_at: DateTime<Utc>,
+    card: &'a ControlCard,
+}
+
+/// События карточек, которые ещё не поставлены в очередь вебхука.
+/// Учитываются только случившиеся после создания вебхука и не раньше EVENT_LOOKBACK_DAYS.
+pub fn new_events(
+    webhook: &Webhook,
+    cards: &[ControlCard],
+    known_keys: &HashSet<String>,
+    today: NaiveDate,
+    now: DateTime<Utc>,
+) -> Vec<NewDelivery> {
+    let since = webhook.created_at.max(now - ChronoDuration::days(EVENT_LOOKBACK_DAYS));
+    let since_date = since.date_naive();
+    let mut result = Vec::new();
+
+    for card in cards {
+        let dates = card.dates();
+        let mut candidates = Vec::new();
+
+        if card.created_at >= since {
+            candidates.push((EVENT_CARD_CREATED, format!("{}:{}", EVENT_CARD_CREATED, card.id), card.created_at));
+        }
+
+        // Ключи доставок хранятся ограниченное время, поэтому правка давно закрытой
+        // карточки не должна снова давать событие: учитывается и сама дата исполнения
+        if let Some(completed_at) = card.completed_at {
+            let changed_at = card.updated_at.unwrap_or(card.created_at);
+            if changed_at >= since && completed_at >= since_date {
+                candidates.push((
+                    EVENT_CARD_CLOSED,
+                    format!("{}:{}:{}", EVENT_CARD_CLOSED, card.id, deadlines::format_date(completed_at)),
+                    changed_at,
+                ));
+            }
+        }
+
+        // Карточка становится просроченной на следующий день после срока или сразу,
+        // если создана с прошедшим сроком; новый срок после продления даёт новое событие
+        if dates.status(today) == CardStatus::Overdue {
+            if let Some(deadline) = dates.effective_deadline() {
+                let overdue_since = (deadline + ChronoDuration::days(1)).max(card.created_at.date_naive());
+                if overdue_since >= since_date {
+                    candidates.push((
+                        EVENT_CARD_OVERDUE,
+                        format!("{}:{}:{}", EVENT_CARD_OVERDUE, card.id, deadlines::format_date(deadline)),
+                        now,
+                    ));
+                }
+            }
+        }
+
+        for (event, event_key, occurred_at) in candidates {
+            if !webhook.subscribed(event) || known_keys.contains(&event_key) {
+                continue;
+            }
+            let payload = Payload { event, occurred_at, card };
+            let Ok(payload) = serde_json::to_string(&payload) else {
+                continue;
+            };
+            result.push(NewDelivery { webhook_id: webhook.id, event, card_id: card.id, event_key, payload });
+        }
+    }
+
+    result
+}
+
+/// Подпись тела запроса: HMAC-SHA256 с секретом вебхука в шестнадцатеричном виде
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Пауза перед следующей попыткой или None, если попытки исчерпаны
+fn retry_delay(attempts: i64) -> Option<i64> {
+    usize::try_from(attempts - 1)
+        .ok()
+        .and_then(|i| RETRY_DELAYS_MINUTES.get(i))
+        .copied()
+}
+
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("calendarcontrolcard/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default()
+}
+
+/// Отправляет одну доставку. Ok — код ответа 2xx, иначе код (если ответ был) и текст ошибки.
+async fn send(client: &reqwest::Client, webhook: &Webhook, delivery: &WebhookDelivery) -> Result<i64, (Option<i64>, String)> {
+    let signature = format!("sha256={}", sign(&webhook.secret, delivery.payload.as_bytes()));
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Event", &delivery.event)
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Signature", signature)
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16() as i64)
+    } else {
+        Err((Some(status.as_u16() as i64), format!("HTTP {}", status)))
+    }
+}
+
+/// Ставит в очередь новые события и отправляет подошедшие доставки.
+/// Возвращает число успешно доставленных.
+pub async fn run_webhooks(db: &Database, client: &reqwest::Client) -> AppResult<usize> {
+    // Очередь хранится в БД; клиенты только для чтения её не ведут
+    if db.is_read_only() {
+        return Ok(0);
+    }
+    let webhooks: Vec<Webhook> = db.list_webhooks().await?.into_iter().filter(|w| w.enabled).collect();
+    if webhooks.is_empty() {
+        return Ok(0);
+    }
+
+    let cards = db.get_all_control_cards().await?;
+    let today = deadlines::today();
+    let now = Utc::now();
+    for webhook in &webhooks {
+        let known: HashSet<String> = db.recent_webhook_event_keys(webhook.id).await?.into_iter().collect();
+        let deliveries = new_events(webhook, &cards, &known, today, now);
+        if !deliveries.is_empty() {
+            db.enqueue_webhook_deliveries(&deliveries).await?;
+        }
+    }
+
+    // Отметка «отправляется» ставится одним UPDATE, поэтому доставку отправляет одно рабочее место
+    let claimed = db.claim_webhook_deliveries(DELIVERY_BATCH).await?;
+    let mut delivered = 0;
+    for delivery in claimed {
+        let Some(webhook) = webhooks.iter().find(|w| w.id == delivery.webhook_id) else {
+            continue;
+        };
+        match send(client, webhook, &delivery).await {
+            Ok(status) => {
+                db.finish_webhook_delivery(delivery.id, Some(status), None, None).await?;
+                delivered += 1;
+            }
+            Err((status, error)) => {
+                let retry_in = retry_delay(delivery.attempts);
+                db.finish_webhook_delivery(delivery.id, status, Some(&error), retry_in).await?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+pub fn spawn_webhook_dispatcher<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let client = client();
+        let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            let Ok(db) = state.db().await else {
+                continue;
+            };
+            if let Err(e) = run_webhooks(&db, &client).await {
+                eprintln!("Webhook delivery failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use chrono::Datelike;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn card(id: i64, created_at: DateTime<Utc>) -> ControlCard {
+        serde_json::from_value(json!({
+            "id": id,
+            "card_number": id,
+            "year": created_at.year(),
+            "executor": "executor",
+            "reporter": "reporter",
+            "summary": "summary",
+            "document_reference": "doc",
+            "executor_user_id": null,
+            "created_at": created_at,
+        }))
+        .unwrap()
+    }
+
+    fn webhook(created_at: DateTime<Utc>) -> Webhook {
+        Webhook {
+            id: 1,
+            url: "http://127.0.0.1/hook".to_string(),
+            secret: "secret".to_string(),
+            events: EVENTS.join(","),
+            enabled: true,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn sign_matches_hmac_sha256_vector() {
+        // RFC 4231, тест 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn new_events_respect_lookback_and_known_keys() {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let days = ChronoDuration::days;
+        let webhook = webhook(now - days(30));
+
+        let fresh = card(1, now - days(2));
+        let stale = card(2, now - days(EVENT_LOOKBACK_DAYS + 2));
+        let mut overdue = card(3, now - days(20));
+        overdue.execution_deadline = Some(today - days(3));
+        let mut long_overdue = card(4, now - days(60));
+        long_overdue.execution_deadline = Some(today - days(30));
+        let mut closed = card(5, now - days(20));
+        closed.completed_at = Some(today - days(1));
+        closed.updated_at = Some(now - days(1));
+        let cards = [fresh, stale, overdue, long_overdue, closed];
+
+        let keys = |known: &HashSet<String>| -> Vec<String> {
+            new_events(&webhook, &cards, known, today, now).into_iter().map(|d| d.event_key).collect()
+        };
+
+        let all = keys(&HashSet::new());
+        assert_eq!(
+            all,
+            vec![
+                "card.created:1".to_string(),
+                format!("card.overdue:3:{}", deadlines::format_date(today - days(3))),
+                format!("card.closed:5:{}", deadlines::format_date(today - days(1))),
+            ]
+        );
+
+        // Уже поставленные в очередь события повторно не появляются
+        let known: HashSet<String> = all.iter().take(2).cloned().collect();
+        assert_eq!(keys(&known), all[2..].to_vec());
+
+        // События до создания вебхука не отправляются
+        let young = Webhook { created_at: now - ChronoDuration::hours(1), ..webhook.clone() };
+        assert!(new_events(&young, &cards, &HashSet::new(), today, now).is_empty());
+    }
+
+    #[test]
+    fn edited_card_closed_long_ago_is_not_resent() {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let webhook = webhook(now - ChronoDuration::days(90));
+
+        let mut closed = card(1, now - ChronoDuration::days(60));
+        closed.completed_at = Some(today - ChronoDuration::days(EVENT_LOOKBACK_DAYS + 30));
+        closed.updated_at = Some(now - ChronoDuration::hours(1));
+
+        assert!(new_events(&webhook, &[closed], &HashSet::new(), today, now).is_empty());
+    }
+
+    #[test]
+    fn retry_delay_runs_out_after_last_step() {
+        let delays: Vec<_> = (1..=RETRY_DELAYS_MINUTES.len() as i64).map(retry_delay).collect();
+        assert!(delays.iter().all(Option::is_some));
+        assert_eq!(delays.last().copied().flatten(), RETRY_DELAYS_MINUTES.last().copied());
+        assert_eq!(retry_delay(RETRY_DELAYS_MINUTES.len() as i64 + 1), None);
+    }
+
+    #[derive(Default)]
+    struct Receiver {
+        status: AtomicU16,
+        requests: Mutex<Vec<(HeaderMap, String)>>,
+    }
+
+    async fn receive(State(receiver): State<Arc<Receiver>>, headers: HeaderMap, body: String) -> StatusCode {
+        receiver.requests.lock().unwrap().push((headers, body));
+        StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_signed_and_rescheduled() {
+        let receiver = Arc::new(Receiver::default());
+        receiver.status.store(500, Ordering::SeqCst);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new().route("/hook", post(receive)).with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let db = Database::connect_in_memory(false).await.unwrap();
+        let hook = db.create_webhook(&url, "top-secret", EVENT_CARD_CREATED, true).await.unwrap();
+        let card_id = db
+            .create_control_card(None, Utc::now().year(), "e", "r", "s", "d", None, None, None, None, None, None, None, None, None, None, None)
+            .await
+            .unwrap();
+
+        let client = client();
+        assert_eq!(run_webhooks(&db, &client).await.unwrap(), 0);
+
+        let (headers, body) = receiver.requests.lock().unwrap()[0].clone();
+        assert_eq!(headers["x-webhook-event"], EVENT_CARD_CREATED);
+        assert_eq!(
+            headers["x-webhook-signature"].to_str().unwrap(),
+            format!("sha256={}", sign("top-secret", body.as_bytes()))
+        );
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["card"]["id"], card_id);
+
+        let deliveries = db.list_webhook_deliveries(Some(hook.id), 10).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        let delivery = &deliveries[0];
+        assert_eq!(delivery.status, "pending");
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.response_status, Some(500));
+        assert!(delivery.next_attempt_at > delivery.created_at);
+
+        // До следующей попытки доставка не отправляется, событие повторно не ставится в очередь
+        receiver.status.store(200, Ordering::SeqCst);
+        assert_eq!(run_webhooks(&db, &client).await.unwrap(), 0);
+        assert_eq!(receiver.requests.lock().unwrap().len(), 1);
+        assert_eq!(db.list_webhook_deliveries(Some(hook.id), 10).await.unwrap().len(), 1);
+
+        // Когда попытки исчерпаны, доставка считается неудачной
+        let exhausted = retry_delay(RETRY_DELAYS_MINUTES.len() as i64 + 1);
+        db.finish_webhook_delivery(delivery.id, Some(500), Some("HTTP 500"), exhausted).await.unwrap();
+        let deliveries = db.list_webhook_deliveries(Some(hook.id), 10).await.unwrap();
+        assert_eq!(deliveries[0].status, "failed");
+    }
+}
@@ -0,0 +1,265 @@
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Color, Format, FormatAlign, FormatBorder, Workbook, Worksheet, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::database::ControlCard;
+use crate::deadlines::CardStatus;
+use crate::error::{AppError, AppResult};
+use crate::models::{self, ExecutorSummary};
+
+/// Колонка листа реестра
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterColumn {
+    Number,
+    Year,
+    Executor,
+    Reporter,
+    Summary,
+    DocumentReference,
+    ExecutionDeadline,
+    ExtendedDeadline,
+    ExecutionPeriodType,
+    Status,
+    CompletedAt,
+    ExecutionReport,
+    ReturnTo,
+    Resolution,
+    Department,
+    Controller,
+    CreatedAt,
+}
+
+/// Колонки, если пользователь не выбрал свои
+pub const DEFAULT_COLUMNS: &[RegisterColumn] = &[
+    RegisterColumn::Number,
+    RegisterColumn::Executor,
+    RegisterColumn::Summary,
+    RegisterColumn::DocumentReference,
+    RegisterColumn::ExecutionDeadline,
+    RegisterColumn::ExtendedDeadline,
+    RegisterColumn::Status,
+    RegisterColumn::CompletedAt,
+];
+
+impl RegisterColumn {
+    fn header(self) -> &'static str {
+        match self {
+            RegisterColumn::Number => "Номер",
+            RegisterColumn::Year => "Год",
+            RegisterColumn::Executor => "Исполнитель",
+            RegisterColumn::Reporter => "Кому докладывать",
+            RegisterColumn::Summary => "Краткое содержание",
+            RegisterColumn::DocumentReference => "Документ-основание",
+            RegisterColumn::ExecutionDeadline => "Срок исполнения",
+            RegisterColumn::ExtendedDeadline => "Продлённый срок",
+            RegisterColumn::ExecutionPeriodType => "Тип срока",
+            RegisterColumn::Status => "Состояние",
+            RegisterColumn::CompletedAt => "Дата исполнения",
+            RegisterColumn::ExecutionReport => "Отметка об исполнении",
+            RegisterColumn::ReturnTo => "Вернуть",
+            RegisterColumn::Resolution => "Резолюция",
+            RegisterColumn::Department => "Отдел",
+            RegisterColumn::Controller => "Контроль",
+            RegisterColumn::CreatedAt => "Создана",
+        }
+    }
+
+    fn width(self) -> f64 {
+        match self {
+            RegisterColumn::Year => 8.0,
+            RegisterColumn::Number | RegisterColumn::Status => 14.0,
+            RegisterColumn::ExecutionDeadline
+            | RegisterColumn::ExtendedDeadline
+            | RegisterColumn::CompletedAt
+            | RegisterColumn::CreatedAt => 13.0,
+            RegisterColumn::Summary | RegisterColumn::ExecutionReport | RegisterColumn::Resolution => 50.0,
+            _ => 25.0,
+        }
+    }
+}
+
+enum Cell {
+    Text(Option<String>),
+    Number(f64),
+    Date(Option<NaiveDate>),
+}
+
+fn cell(column: RegisterColumn, card: &ControlCard, status: CardStatus) -> Cell {
+    match column {
+        RegisterColumn::Number => Cell::Text(Some(
+            card.formatted_number
+                .clone()
+                .unwrap_or_else(|| format!("{}/{}", card.card_number, card.year)),
+        )),
+        RegisterColumn::Year => Cell::Number(card.year as f64),
+        RegisterColumn::Executor => Cell::Text(Some(card.executor.clone())),
+        RegisterColumn::Reporter => Cell::Text(Some(card.reporter.clone())),
+        RegisterColumn::Summary => Cell::Text(Some(card.summary.clone())),
+        RegisterColumn::DocumentReference => Cell::Text(Some(card.document_reference.clone())),
+        RegisterColumn::ExecutionDeadline => Cell::Date(card.execution_deadline),
+        RegisterColumn::ExtendedDeadline => Cell::Date(card.extended_deadline),
+        RegisterColumn::ExecutionPeriodType => Cell::Text(card.execution_period_type.clone()),
+        RegisterColumn::Status => Cell::Text(Some(status_label(status).to_string())),
+        RegisterColumn::CompletedAt => Cell::Date(card.completed_at),
+        RegisterColumn::ExecutionReport => Cell::Text(card.execution_report.clone()),
+        RegisterColumn::ReturnTo => Cell::Text(card.return_to.clone()),
+        RegisterColumn::Resolution => Cell::Text(card.resolution.clone()),
+        RegisterColumn::Department => Cell::Text(card.department.clone()),
+        RegisterColumn::Controller => Cell::Text(card.controller.clone()),
+        RegisterColumn::CreatedAt => Cell::Date(Some(card.created_at.date_naive())),
+    }
+}
+
+fn status_label(status: CardStatus) -> &'static str {
+    match status {
+        CardStatus::Open => "В работе",
+        CardStatus::Completed => "Исполнена",
+        CardStatus::Overdue => "Просрочена",
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct XlsxExportInfo {
+    pub path: String,
+    pub rows: usize,
+    pub executors: usize,
+}
+
+struct Formats {
+    header: Format,
+    text: Format,
+    date: Format,
+    number: Format,
+    total: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        let header = Format::new()
+            .set_bold()
+            .set_text_wrap()
+            .set_align(FormatAlign::Center)
+            .set_align(FormatAlign::VerticalCenter)
+            .set_background_color(Color::RGB(0xE5E7EB))
+            .set_border(FormatBorder::Thin);
+
+        Self {
+            header,
+            text: Format::new().set_text_wrap().set_align(FormatAlign::Top),
+            date: Format::new().set_num_format("dd.mm.yyyy").set_align(FormatAlign::Top),
+            number: Format::new().set_align(FormatAlign::Top),
+            total: Format::new().set_bold().set_border_top(FormatBorder::Thin),
+        }
+    }
+}
+
+/// Записывает реестр карточек в XLSX: лист с выбранными колонками и свод по исполнителям.
+/// Карточки должны быть уже отобраны с учётом прав пользователя.
+pub fn write_register(path: &Path, cards: &[ControlCard], columns: &[RegisterColumn], today: NaiveDate) -> AppResult<XlsxExportInfo> {
+    if path.as_os_str().is_empty() {
+        return Err(AppError::validation("path", "Export path is required"));
+    }
+    let columns = if columns.is_empty() { DEFAULT_COLUMNS } else { columns };
+
+    let totals = models::summarize_by_executor(cards, today);
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+    write_workbook(&mut workbook, &formats, cards, columns, &totals, today)
+        .and_then(|_| workbook.save(path))
+        .map_err(|e| AppError::db(format!("Failed to write XLSX file: {}", e)))?;
+
+    Ok(XlsxExportInfo {
+        path: path.to_string_lossy().to_string(),
+        rows: cards.len(),
+        executors: totals.len(),
+    })
+}
+
+fn write_workbook(
+    workbook: &mut Workbook,
+    formats: &Formats,
+    cards: &[ControlCard],
+    columns: &[RegisterColumn],
+    totals: &[ExecutorSummary],
+    today: NaiveDate,
+) -> Result<(), XlsxError> {
+    let sheet = workbook.add_worksheet().set_name("Реестр")?;
+    write_register_sheet(sheet, formats, cards, columns, today)?;
+
+    let sheet = workbook.add_worksheet().set_name("По исполнителям")?;
+    write_summary_sheet(sheet, formats, totals)
+}
+
+fn write_register_sheet(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    cards: &[ControlCard],
+    columns: &[RegisterColumn],
+    today: NaiveDate,
+) -> Result<(), XlsxError> {
+    for (col, column) in columns.iter().enumerate() {
+        let col = col as u16;
+        sheet.write_string_with_format(0, col, column.header(), &formats.header)?;
+        sheet.set_column_width(col, column.width())?;
+    }
+
+    for (index, card) in cards.iter().enumerate() {
+        let row = index as u32 + 1;
+        let status = card.dates().status(today);
+        for (col, column) in columns.iter().enumerate() {
+            let col = col as u16;
+            match cell(*column, card, status) {
+                Cell::Text(Some(value)) => {
+                    sheet.write_string_with_format(row, col, value, &formats.text)?;
+                }
+                Cell::Number(value) => {
+                    sheet.write_number_with_format(row, col, value, &formats.number)?;
+                }
+                Cell::Date(Some(date)) => {
+                    sheet.write_date_with_format(row, col, date, &formats.date)?;
+                }
+                Cell::Text(None) | Cell::Date(None) => {}
+            }
+        }
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, cards.len() as u32, columns.len().saturating_sub(1) as u16)?;
+    Ok(())
+}
+
+fn write_summary_sheet(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    totals: &[ExecutorSummary],
+) -> Result<(), XlsxError> {
+    let headers = ["Исполнитель", "Всего", "В работе", "Исполнено", "Просрочено"];
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &formats.header)?;
+    }
+    sheet.set_column_width(0, 35)?;
+    sheet.set_column_range_width(1, 4, 13)?;
+
+    let mut sum = ExecutorSummary::default();
+    let mut row = 1;
+    for t in totals {
+        sheet.write_string(row, 0, &t.executor)?;
+        for (col, value) in [t.total, t.open, t.completed, t.overdue].into_iter().enumerate() {
+            sheet.write_number(row, col as u16 + 1, value)?;
+        }
+        sum.total += t.total;
+        sum.open += t.open;
+        sum.completed += t.completed;
+        sum.overdue += t.overdue;
+        row += 1;
+    }
+
+    sheet.write_string_with_format(row, 0, "Итого", &formats.total)?;
+    for (col, value) in [sum.total, sum.open, sum.completed, sum.overdue].into_iter().enumerate() {
+        sheet.write_number_with_format(row, col as u16 + 1, value, &formats.total)?;
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import { getErrorMessage } from '../utils/errors'
+
+export interface ApiSettings {
+  enabled: boolean
+  /** localhost — только этот компьютер, lan — вся локальная сеть (HTTP без шифрования) */
+  bind: 'localhost' | 'lan'
+  port: number
+}
+
+export interface ApiStatus {
+  settings: ApiSettings
+  /** Адрес запущенного сервера; null — сервер не запущен */
+  address: string | null
+  version: string
+}
+
+export const useApiServer = () => {
+  const authStore = useAuthStore()
+  const apiStatus = ref<ApiStatus | null>(null)
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async (operation: (token: string) => Promise<ApiStatus>, errorMessage: string) => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      apiStatus.value = await operation(authStore.token)
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadApiStatus = async () => {
+    if (!authStore.isAdmin) return
+    await run(
+      token => invoke<ApiStatus>('get_api_settings', { token }),
+      'Не удалось загрузить настройки HTTP API'
+    )
+  }
+
+  /**
+   * Сохраняет настройки и перезапускает сервер
+   */
+  const saveApiSettings = async (settings: ApiSettings) => {
+    await run(
+      token => invoke<ApiStatus>('update_api_settings', { settings, token }),
+      'Не удалось применить настройки HTTP API'
+    )
+  }
+
+  return {
+    apiStatus,
+    isLoading,
+    error,
+    loadApiStatus,
+    saveApiSettings
+  }
+}
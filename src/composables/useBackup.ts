@@ -0,0 +1,155 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import type { ConnectionInfo } from '../types/settings'
+import { getErrorMessage } from '../utils/errors'
+
+export interface BackupSettings {
+  enabled: boolean
+  intervalHours: number
+  retention: number
+  directory?: string
+  lastBackupAt?: string
+}
+
+export interface BackupInfo {
+  path: string
+  sizeBytes: number
+  createdAt: string
+}
+
+interface BackupSettingsResponse {
+  enabled: boolean
+  interval_hours: number
+  retention: number
+  directory?: string | null
+  last_backup_at?: string | null
+}
+
+interface BackupInfoResponse {
+  path: string
+  size_bytes: number
+  created_at: string
+}
+
+function mapBackupSettingsResponse(settings: BackupSettingsResponse): BackupSettings {
+  return {
+    enabled: settings.enabled,
+    intervalHours: settings.interval_hours,
+    retention: settings.retention,
+    directory: settings.directory ?? undefined,
+    lastBackupAt: settings.last_backup_at ?? undefined
+  }
+}
+
+function mapBackupInfoResponse(backup: BackupInfoResponse): BackupInfo {
+  return {
+    path: backup.path,
+    sizeBytes: backup.size_bytes,
+    createdAt: backup.created_at
+  }
+}
+
+export const useBackup = () => {
+  const authStore = useAuthStore()
+  const backupSettings = ref<BackupSettings | null>(null)
+  const backups = ref<BackupInfo[]>([])
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async <T>(operation: (token: string) => Promise<T>, errorMessage: string): Promise<T | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await operation(authStore.token)
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadBackupSettings = async () => {
+    const response = await run(
+      token => invoke<BackupSettingsResponse>('get_backup_settings', { token }),
+      'Не удалось загрузить настройки резервного копирования'
+    )
+    if (response) {
+      backupSettings.value = mapBackupSettingsResponse(response)
+    }
+  }
+
+  const saveBackupSettings = async (settings: BackupSettings) => {
+    const response = await run(
+      token => invoke<BackupSettingsResponse>('update_backup_settings', {
+        settings: {
+          enabled: settings.enabled,
+          interval_hours: settings.intervalHours,
+          retention: settings.retention,
+          directory: settings.directory || null
+        },
+        token
+      }),
+      'Не удалось сохранить настройки резервного копирования'
+    )
+    if (response) {
+      backupSettings.value = mapBackupSettingsResponse(response)
+    }
+  }
+
+  const loadBackups = async () => {
+    const response = await run(
+      token => invoke<BackupInfoResponse[]>('list_backups', { token }),
+      'Не удалось загрузить список резервных копий'
+    )
+    backups.value = (response || []).map(mapBackupInfoResponse)
+  }
+
+  const backupDatabase = async (destPath: string): Promise<BackupInfo | null> => {
+    const response = await run(
+      token => invoke<BackupInfoResponse>('backup_database', { destPath, token }),
+      'Не удалось создать резервную копию'
+    )
+    return response ? mapBackupInfoResponse(response) : null
+  }
+
+  /**
+   * Заменяет текущую БД резервной копией. Другие рабочие места должны быть отключены.
+   * backupKey нужен, если копия зашифрована не текущим ключом.
+   */
+  const restoreDatabase = async (srcPath: string, backupKey?: string): Promise<ConnectionInfo | null> => {
+    return await run(
+      token => invoke<ConnectionInfo>('restore_database', { srcPath, backupKey: backupKey ?? null, token }),
+      'Не удалось восстановить базу данных'
+    )
+  }
+
+  /**
+   * Меняет ключ шифрования БД и архивов; пустой ключ снимает шифрование
+   */
+  const rekeyDatabase = async (newKey: string): Promise<ConnectionInfo | null> => {
+    return await run(
+      token => invoke<ConnectionInfo>('rekey_database', { newKey: newKey || null, token }),
+      'Не удалось сменить ключ шифрования'
+    )
+  }
+
+  return {
+    backupSettings,
+    backups,
+    isLoading,
+    error,
+    loadBackupSettings,
+    saveBackupSettings,
+    loadBackups,
+    backupDatabase,
+    restoreDatabase,
+    rekeyDatabase
+  }
+}
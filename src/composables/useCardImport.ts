@@ -0,0 +1,109 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import { getErrorMessage } from '../utils/errors'
+
+/** Заголовки колонок CSV для полей карточки; пустое значение — колонки нет */
+export interface CsvMapping {
+  card_number?: string
+  year?: string
+  executor: string
+  reporter: string
+  summary: string
+  document_reference: string
+  return_to?: string
+  execution_deadline?: string
+  execution_period_type?: string
+  extended_deadline?: string
+  resolution?: string
+  department?: string
+  controller?: string
+  /** Дата исполнения: карточки с ней импортируются исполненными */
+  completed_at?: string
+  date_formats: string[]
+  delimiter?: string
+  default_year?: number
+  numbering_scheme_id?: number
+  /** Сохранить строки без ошибок, даже если в других строках ошибки есть */
+  partial: boolean
+}
+
+export interface ImportRowError {
+  row: number
+  field: string | null
+  message: string
+}
+
+export interface ImportReport {
+  dry_run: boolean
+  total_rows: number
+  valid_rows: number
+  committed: boolean
+  card_ids: number[]
+  errors: ImportRowError[]
+}
+
+export const DEFAULT_CSV_MAPPING: CsvMapping = {
+  card_number: 'Номер',
+  year: 'Год',
+  executor: 'Исполнитель',
+  reporter: 'Автор',
+  summary: 'Содержание',
+  document_reference: 'Документ',
+  return_to: '',
+  execution_deadline: 'Срок',
+  execution_period_type: '',
+  extended_deadline: '',
+  resolution: 'Резолюция',
+  department: 'Отдел',
+  controller: 'Контроль',
+  completed_at: '',
+  date_formats: ['%d.%m.%Y', '%Y-%m-%d'],
+  partial: false
+}
+
+export const useCardImport = () => {
+  const authStore = useAuthStore()
+  const report = ref<ImportReport | null>(null)
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  /**
+   * dryRun — только проверка: ничего не сохраняется, но проверяются и конфликты номеров
+   */
+  const importCsv = async (path: string, mapping: CsvMapping, dryRun: boolean): Promise<ImportReport | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      // Пустые заголовки означают, что колонки нет
+      const cleaned = Object.fromEntries(
+        Object.entries(mapping).map(([field, value]) => [field, value === '' ? null : value])
+      )
+      report.value = await invoke<ImportReport>('import_cards_csv', {
+        path,
+        mapping: cleaned,
+        dryRun,
+        token: authStore.token
+      })
+      return report.value
+    } catch (err) {
+      error.value = getErrorMessage(err, 'Не удалось импортировать карточки')
+      report.value = null
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  return {
+    report,
+    isLoading,
+    error,
+    importCsv
+  }
+}
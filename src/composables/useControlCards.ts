@@ -1,8 +1,11 @@
 import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
 import { useAuthStore } from '../stores/auth'
 import type { ControlCard } from '../types/calendar'
 import type { User } from '../types/auth'
+import { getErrorMessage } from '../utils/errors'
+import type { CardFilter } from './useRegisterExport'
 
 interface ControlCardResponse {
   id: number
@@ -22,6 +25,63 @@ interface ControlCardResponse {
   department?: string | null
   controller?: string | null
   controller_user_id?: number | null
+  formatted_number?: string | null
+  numbering_scheme_id?: number | null
+  version: number
+  updated_at?: string | null
+  execution_report?: string | null
+  completed_at?: string | null
+}
+
+interface DeletedCardResponse extends ControlCardResponse {
+  deleted_at: string
+  deleted_by?: number | null
+  deleted_by_username?: string | null
+  delete_reason?: string | null
+}
+
+export interface DeletedCard {
+  card: ControlCard
+  deletedAt: string
+  deletedBy?: string
+  deleteReason?: string
+}
+
+export interface ArchiveInfo {
+  year: number
+  fileName: string
+  checksum?: string
+  cardCount: number
+  carriedOver: number
+  createdAt: string
+  attached: boolean
+}
+
+interface ArchiveInfoResponse {
+  year: number
+  file_name: string
+  checksum?: string | null
+  card_count: number
+  carried_over: number
+  created_by?: number | null
+  created_at: string
+  attached: boolean
+}
+
+const mapArchiveInfoResponse = (info: ArchiveInfoResponse): ArchiveInfo => ({
+  year: info.year,
+  fileName: info.file_name,
+  checksum: info.checksum ?? undefined,
+  cardCount: info.card_count,
+  carriedOver: info.carried_over,
+  createdAt: info.created_at,
+  attached: info.attached
+})
+
+interface CardChangeEvent {
+  id: number
+  seq: number
+  changed_at: string
 }
 
 function mapControlCardResponse(card: ControlCardResponse): ControlCard {
@@ -44,7 +104,13 @@ function mapControlCardResponse(card: ControlCardResponse): ControlCard {
     resolution: card.resolution ?? undefined,
     department: card.department ?? undefined,
     controller: card.controller ?? undefined,
-    controllerUserId: card.controller_user_id ?? undefined
+    controllerUserId: card.controller_user_id ?? undefined,
+    formattedNumber: card.formatted_number ?? undefined,
+    numberingSchemeId: card.numbering_scheme_id ?? undefined,
+    version: card.version,
+    updatedAt: card.updated_at ?? undefined,
+    executionReport: card.execution_report ?? undefined,
+    completedAt: card.completed_at ?? undefined
   }
 }
 
@@ -70,7 +136,7 @@ export function useControlCards() {
       error.value = null
       return await operation()
     } catch (err) {
-      error.value = err instanceof Error ? err.message : errorMessage
+      error.value = getErrorMessage(err, errorMessage)
       console.error(errorMessage, err)
       return null
     } finally {
@@ -78,11 +144,11 @@ export function useControlCards() {
     }
   }
 
-  const loadCards = async () => {
+  const loadCards = async (filter?: CardFilter) => {
     const token = getToken()
     const response = await withLoading(
       async () => {
-        const result = await invoke<ControlCardResponse[]>('get_all_control_cards', { token })
+        const result = await invoke<ControlCardResponse[]>('get_all_control_cards', { filter: filter ?? null, token })
         return result
       },
       'Ошибка загрузки контрольных карточек'
@@ -99,6 +165,23 @@ export function useControlCards() {
     )
   }
 
+  const reserveCardNumber = async (year: number): Promise<number | null> => {
+    const token = getToken()
+    const reservation = await withLoading(
+      async () => invoke<{ year: number, card_number: number }>('reserve_card_number', { year, token }),
+      'Ошибка резервирования номера карточки'
+    )
+    return reservation ? reservation.card_number : null
+  }
+
+  const releaseCardNumber = async (year: number, cardNumber: number): Promise<void> => {
+    const token = getToken()
+    await withLoading(
+      async () => invoke('release_card_number', { year, cardNumber, token }),
+      'Ошибка освобождения номера карточки'
+    )
+  }
+
   const createCard = async (
     cardNumber: number,
     year: number,
@@ -149,6 +232,7 @@ export function useControlCards() {
 
   const updateCard = async (
     id: string,
+    version: number,
     cardNumber: number,
     year: number,
     executorUserId: number,
@@ -168,6 +252,7 @@ export function useControlCards() {
     const result = await withLoading(
       async () => invoke('update_control_card', {
         id: Number(id),
+        expectedVersion: version,
         cardNumber,
         year,
         executorUserId,
@@ -193,18 +278,175 @@ export function useControlCards() {
     return true
   }
 
-  const deleteCard = async (id: string): Promise<boolean> => {
+  /**
+   * Частичное обновление: передаются только изменённые поля (snake_case), null очищает значение
+   */
+  const patchCard = async (
+    id: string,
+    version: number,
+    patch: Record<string, unknown>
+  ): Promise<boolean> => {
     const token = getToken()
     const result = await withLoading(
-      async () => invoke('delete_control_card', { id: Number(id), token }),
+      async () => invoke<number>('patch_control_card', {
+        id: Number(id),
+        patch: { ...patch, expected_version: version },
+        token
+      }),
+      'Ошибка обновления контрольной карточки'
+    )
+    if (result === null) return false
+
+    await loadCards()
+    return true
+  }
+
+  const deleteCard = async (id: string, reason?: string): Promise<boolean> => {
+    const token = getToken()
+    const result = await withLoading(
+      async () => invoke('delete_control_card', { id: Number(id), reason, token }),
       'Ошибка удаления контрольной карточки'
     )
     if (!result) return false
 
-    cards.value = cards.value.filter(c => c.id !== id)
+    removeCardLocally(id)
     return true
   }
 
+  const listDeletedCards = async (): Promise<DeletedCard[]> => {
+    const token = getToken()
+    const response = await withLoading(
+      async () => invoke<DeletedCardResponse[]>('list_deleted_cards', { token }),
+      'Ошибка загрузки корзины'
+    )
+    return (response || []).map(item => ({
+      card: mapControlCardResponse(item),
+      deletedAt: item.deleted_at,
+      deletedBy: item.deleted_by_username ?? undefined,
+      deleteReason: item.delete_reason ?? undefined
+    }))
+  }
+
+  const restoreCard = async (id: string): Promise<boolean> => {
+    const token = getToken()
+    const result = await withLoading(
+      async () => invoke<number>('restore_control_card', { id: Number(id), token }),
+      'Ошибка восстановления контрольной карточки'
+    )
+    return Boolean(result)
+  }
+
+  /**
+   * Окончательно удаляет карточки, попавшие в корзину раньше olderThan (YYYY-MM-DD)
+   */
+  const purgeDeletedCards = async (olderThan: string): Promise<number | null> => {
+    const token = getToken()
+    return await withLoading(
+      async () => invoke<number>('purge_deleted_cards', { olderThan, token }),
+      'Ошибка очистки корзины'
+    )
+  }
+
+  /**
+   * Переносит закрытые карточки года в архив. carryOver — незакрытые карточки,
+   * которые остаются в основной базе.
+   */
+  const archiveYear = async (year: number, carryOver: string[] = []): Promise<ArchiveInfo | null> => {
+    const token = getToken()
+    const response = await withLoading(
+      async () => invoke<ArchiveInfoResponse>('archive_year', {
+        year,
+        carryOver: carryOver.map(Number),
+        token
+      }),
+      'Ошибка архивации года'
+    )
+    return response ? mapArchiveInfoResponse(response) : null
+  }
+
+  const listArchives = async (): Promise<ArchiveInfo[]> => {
+    const token = getToken()
+    const response = await withLoading(
+      async () => invoke<ArchiveInfoResponse[]>('list_archives', { token }),
+      'Ошибка загрузки списка архивов'
+    )
+    return (response || []).map(mapArchiveInfoResponse)
+  }
+
+  const attachArchive = async (year: number): Promise<boolean> => {
+    const token = getToken()
+    const result = await withLoading(
+      async () => {
+        await invoke('attach_archive', { year, token })
+        return true
+      },
+      'Ошибка подключения архива'
+    )
+    return Boolean(result)
+  }
+
+  const detachArchive = async (year: number): Promise<boolean> => {
+    const token = getToken()
+    const result = await withLoading(
+      async () => invoke<boolean>('detach_archive', { year, token }),
+      'Ошибка отключения архива'
+    )
+    return Boolean(result)
+  }
+
+  /**
+   * Поиск по подключённым архивам
+   */
+  const searchArchivedCards = async (query: string, year?: number): Promise<ControlCard[]> => {
+    const token = getToken()
+    const response = await withLoading(
+      async () => invoke<ControlCardResponse[]>('search_archived_cards', {
+        query,
+        year: year ?? null,
+        token
+      }),
+      'Ошибка поиска по архиву'
+    )
+    return (response || []).map(mapControlCardResponse)
+  }
+
+  const removeCardLocally = (id: string) => {
+    cards.value = cards.value.filter(c => c.id !== id)
+  }
+
+  /**
+   * Подписка на изменения карточек из других окон и рабочих мест.
+   * Возвращает функцию отписки.
+   */
+  const subscribeToChanges = async (): Promise<UnlistenFn> => {
+    const refreshCard = async (event: { payload: CardChangeEvent }) => {
+      const id = String(event.payload.id)
+      try {
+        const response = await invoke<ControlCardResponse>('get_control_card', {
+          id: event.payload.id,
+          token: getToken()
+        })
+        const card = mapControlCardResponse(response)
+        const index = cards.value.findIndex(c => c.id === id)
+        if (index >= 0) {
+          cards.value.splice(index, 1, card)
+        } else {
+          cards.value.unshift(card)
+        }
+      } catch {
+        // Карточка удалена или больше недоступна этому пользователю
+        removeCardLocally(id)
+      }
+    }
+
+    const unlisteners = await Promise.all([
+      listen<CardChangeEvent>('card-created', refreshCard),
+      listen<CardChangeEvent>('card-updated', refreshCard),
+      listen<CardChangeEvent>('card-deleted', (event) => removeCardLocally(String(event.payload.id)))
+    ])
+    return () => unlisteners.forEach(unlisten => unlisten())
+  }
+
   const getUsersForExecutorSelection = async (): Promise<User[]> => {
     const token = getToken()
     const users = await withLoading(
@@ -229,9 +471,21 @@ export function useControlCards() {
     error,
     loadCards,
     getNextCardNumber,
+    reserveCardNumber,
+    releaseCardNumber,
     createCard,
     updateCard,
+    patchCard,
     deleteCard,
+    listDeletedCards,
+    restoreCard,
+    purgeDeletedCards,
+    archiveYear,
+    listArchives,
+    attachArchive,
+    detachArchive,
+    searchArchivedCards,
+    subscribeToChanges,
     getUsersForExecutorSelection,
     getUsersForControllerSelection
   }
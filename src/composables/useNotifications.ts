@@ -0,0 +1,60 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { getErrorMessage } from '../utils/errors'
+
+/** Настройки уведомлений рабочего стола; хранятся на этом рабочем месте */
+export interface NotificationSettings {
+  enabled: boolean
+  /** За сколько дней до срока напоминать о карточке */
+  days_before: number
+  /** Тихие часы, ЧЧ:ММ; интервал может переходить через полночь */
+  quiet_hours_start: string | null
+  quiet_hours_end: string | null
+}
+
+export const useNotifications = () => {
+  const notificationSettings = ref<NotificationSettings | null>(null)
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async (operation: () => Promise<NotificationSettings>, errorMessage: string) => {
+    isLoading.value = true
+    error.value = null
+
+    try {
+      notificationSettings.value = await operation()
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadNotificationSettings = async () => {
+    await run(
+      () => invoke<NotificationSettings>('get_notification_settings'),
+      'Не удалось загрузить настройки уведомлений'
+    )
+  }
+
+  const saveNotificationSettings = async (settings: NotificationSettings) => {
+    await run(
+      () => invoke<NotificationSettings>('update_notification_settings', {
+        settings: {
+          ...settings,
+          quiet_hours_start: settings.quiet_hours_start || null,
+          quiet_hours_end: settings.quiet_hours_end || null
+        }
+      }),
+      'Не удалось сохранить настройки уведомлений'
+    )
+  }
+
+  return {
+    notificationSettings,
+    isLoading,
+    error,
+    loadNotificationSettings,
+    saveNotificationSettings
+  }
+}
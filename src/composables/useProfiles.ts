@@ -0,0 +1,89 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import type { DatabaseProfile, ProfileList, ProfileSwitch } from '../types/settings'
+import { useSettings } from './useSettings'
+import { getErrorMessage } from '../utils/errors'
+
+const profiles = ref<DatabaseProfile[]>([])
+const activeId = ref<string | null>(null)
+
+export const useProfiles = () => {
+  const { settings, connectionInfo, saveSettings } = useSettings()
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async <T>(operation: () => Promise<T>, errorMessage: string): Promise<T | null> => {
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await operation()
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadProfiles = async () => {
+    const response = await run(() => invoke<ProfileList>('list_profiles'), 'Не удалось загрузить профили')
+    if (response) {
+      profiles.value = response.profiles
+      activeId.value = response.active_id
+    }
+  }
+
+  const addProfile = async (name: string, path: string, readOnly = false) => {
+    const profile = await run(
+      () => invoke<DatabaseProfile>('add_profile', {
+        name,
+        path,
+        readOnly,
+        mode: settings.value.multiUser ? 'multi_user' : 'single'
+      }),
+      'Не удалось добавить профиль'
+    )
+    if (profile) {
+      await loadProfiles()
+    }
+    return profile
+  }
+
+  const removeProfile = async (id: string) => {
+    const removed = await run(() => invoke<boolean>('remove_profile', { id }), 'Не удалось удалить профиль')
+    if (removed) {
+      await loadProfiles()
+    }
+  }
+
+  /**
+   * Подключается к БД профиля. Возвращает токен сессии, если под профилем уже входили.
+   */
+  const switchProfile = async (id: string): Promise<ProfileSwitch | null> => {
+    const result = await run(
+      () => invoke<ProfileSwitch>('switch_profile', { id, key: settings.value.dbKey || null }),
+      'Не удалось переключиться на профиль'
+    )
+    if (result) {
+      connectionInfo.value = result.connection
+      settings.value.dbPath = result.profile.path
+      settings.value.multiUser = result.profile.mode === 'multi_user'
+      settings.value.readOnly = result.profile.read_only
+      saveSettings()
+      await loadProfiles()
+    }
+    return result
+  }
+
+  return {
+    profiles,
+    activeId,
+    isLoading,
+    error,
+    loadProfiles,
+    addProfile,
+    removeProfile,
+    switchProfile
+  }
+}
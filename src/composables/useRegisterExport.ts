@@ -0,0 +1,143 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import { getErrorMessage } from '../utils/errors'
+
+export type CardStatus = 'open' | 'completed' | 'overdue'
+
+/** Отбор карточек; тот же, что принимает get_all_control_cards */
+export interface CardFilter {
+  year?: number | null
+  executor_user_id?: number | null
+  department?: string | null
+  status?: CardStatus | null
+  deadline_from?: string | null
+  deadline_to?: string | null
+  query?: string | null
+}
+
+export type RegisterColumn =
+  | 'number'
+  | 'year'
+  | 'executor'
+  | 'reporter'
+  | 'summary'
+  | 'document_reference'
+  | 'execution_deadline'
+  | 'extended_deadline'
+  | 'execution_period_type'
+  | 'status'
+  | 'completed_at'
+  | 'execution_report'
+  | 'return_to'
+  | 'resolution'
+  | 'department'
+  | 'controller'
+  | 'created_at'
+
+export const REGISTER_COLUMNS: { key: RegisterColumn, label: string, default?: boolean }[] = [
+  { key: 'number', label: 'Номер', default: true },
+  { key: 'year', label: 'Год' },
+  { key: 'executor', label: 'Исполнитель', default: true },
+  { key: 'reporter', label: 'Кому докладывать' },
+  { key: 'summary', label: 'Краткое содержание', default: true },
+  { key: 'document_reference', label: 'Документ-основание', default: true },
+  { key: 'execution_deadline', label: 'Срок исполнения', default: true },
+  { key: 'extended_deadline', label: 'Продлённый срок', default: true },
+  { key: 'execution_period_type', label: 'Тип срока' },
+  { key: 'status', label: 'Состояние', default: true },
+  { key: 'completed_at', label: 'Дата исполнения', default: true },
+  { key: 'execution_report', label: 'Отметка об исполнении' },
+  { key: 'return_to', label: 'Вернуть' },
+  { key: 'resolution', label: 'Резолюция' },
+  { key: 'department', label: 'Отдел' },
+  { key: 'controller', label: 'Контроль' },
+  { key: 'created_at', label: 'Создана' }
+]
+
+export interface XlsxExportInfo {
+  path: string
+  rows: number
+  executors: number
+}
+
+export interface IcsOptions {
+  kind: 'event' | 'todo'
+  /** За сколько дней до срока напомнить; 0 — без напоминания */
+  alarm_days: number
+}
+
+export interface IcsExportInfo {
+  path: string
+  entries: number
+  skipped: number
+}
+
+/** Пустые поля отбора не ограничивают выборку */
+function cleanFilter(filter: CardFilter): CardFilter {
+  return Object.fromEntries(
+    Object.entries(filter).map(([field, value]) => [field, value === '' ? null : value])
+  )
+}
+
+export const useRegisterExport = () => {
+  const authStore = useAuthStore()
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const exportXlsx = async (path: string, filter: CardFilter, columns: RegisterColumn[]): Promise<XlsxExportInfo | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await invoke<XlsxExportInfo>('export_cards_xlsx', {
+        filter: cleanFilter(filter),
+        columns,
+        path,
+        token: authStore.token
+      })
+    } catch (err) {
+      error.value = getErrorMessage(err, 'Не удалось выгрузить реестр')
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  /**
+   * Повторная выгрузка в тот же файл обновляет записи календаря, а не дублирует их
+   */
+  const exportIcs = async (path: string, filter: CardFilter, options: IcsOptions): Promise<IcsExportInfo | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await invoke<IcsExportInfo>('export_cards_ics', {
+        filter: cleanFilter(filter),
+        options,
+        path,
+        token: authStore.token
+      })
+    } catch (err) {
+      error.value = getErrorMessage(err, 'Не удалось выгрузить календарь')
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  return {
+    isLoading,
+    error,
+    exportXlsx,
+    exportIcs
+  }
+}
@@ -1,9 +1,13 @@
 import { ref, watch } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
-import type { AppSettings } from '../types/settings'
+import type { AppSettings, ConnectionInfo, ConnectionMode } from '../types/settings'
 import { DEFAULT_SETTINGS } from '../constants/settings'
 
 const settings = ref<AppSettings>({ ...DEFAULT_SETTINGS })
+const connectionInfo = ref<ConnectionInfo | null>(null)
+
+export const connectionMode = (multiUser: boolean): ConnectionMode =>
+  multiUser ? 'multi_user' : 'single'
 
 const safeInvoke = async <T>(
   command: string,
@@ -39,15 +43,31 @@ export const useSettings = () => {
       if (settings.value.dbPath !== currentDbPath) {
         settings.value.dbPath = currentDbPath
       }
+      connectionInfo.value = await safeInvoke<ConnectionInfo>('get_connection_info')
       return
     }
 
     // Подключаемся только если БД не подключена и есть путь в настройках
     if (settings.value.dbPath) {
-      await safeInvoke('connect_database', { dbPath: settings.value.dbPath }, 'Failed to connect to database')
+      await connect(settings.value.dbPath)
     }
   }
 
+  const connect = async (dbPath: string) => {
+    connectionInfo.value = await safeInvoke<ConnectionInfo>(
+      'connect_database',
+      {
+        dbPath,
+        mode: connectionMode(settings.value.multiUser),
+        key: settings.value.dbKey || null,
+        readOnly: settings.value.readOnly
+      },
+      'Failed to connect to database'
+    )
+    connectionInfo.value?.warnings.forEach((warning) => console.warn(warning))
+    return connectionInfo.value
+  }
+
   const saveSettings = () => {
     localStorage.setItem('app-settings', JSON.stringify(settings.value))
   }
@@ -59,7 +79,7 @@ export const useSettings = () => {
     }
     
     if (dbPath) {
-      const connected = await safeInvoke('connect_database', { dbPath }, 'Failed to connect to database')
+      const connected = await connect(dbPath)
       if (!connected) {
         throw new Error('Failed to connect to database')
       }
@@ -69,6 +89,23 @@ export const useSettings = () => {
     saveSettings()
   }
 
+  // Смена режима требует переподключения с другими параметрами журнала
+  const updateMultiUser = async (multiUser: boolean) => {
+    settings.value.multiUser = multiUser
+    saveSettings()
+    if (settings.value.dbPath) {
+      await updateDbPath(settings.value.dbPath)
+    }
+  }
+
+  const updateReadOnly = async (readOnly: boolean) => {
+    settings.value.readOnly = readOnly
+    saveSettings()
+    if (settings.value.dbPath) {
+      await updateDbPath(settings.value.dbPath)
+    }
+  }
+
   watch(
     settings,
     () => {
@@ -80,8 +117,11 @@ export const useSettings = () => {
   return {
     settings,
     loadSettings,
+    connectionInfo,
     saveSettings,
-    updateDbPath
+    updateDbPath,
+    updateMultiUser,
+    updateReadOnly
   }
 }
 
@@ -0,0 +1,81 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import { getErrorMessage } from '../utils/errors'
+
+export interface SmtpSettings {
+  enabled: boolean
+  host: string
+  port: number
+  /** none — без шифрования (например, MailHog), tls — обычно порт 465 */
+  security: 'none' | 'starttls' | 'tls'
+  username: string | null
+  /** Не возвращается с сервера; пустой пароль при сохранении оставляет прежний */
+  password?: string | null
+  from_address: string
+  /** За сколько дней до срока напоминать; 0 — только в день срока и при просрочке */
+  remind_days_before: number
+}
+
+export const useSmtp = () => {
+  const authStore = useAuthStore()
+  const smtpSettings = ref<SmtpSettings | null>(null)
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async <T>(operation: (token: string) => Promise<T>, errorMessage: string): Promise<T | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await operation(authStore.token)
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadSmtpSettings = async () => {
+    if (!authStore.isAdmin) return
+    const result = await run(
+      token => invoke<SmtpSettings>('get_smtp_settings', { token }),
+      'Не удалось загрузить настройки почты'
+    )
+    if (result) smtpSettings.value = { ...result, password: '' }
+  }
+
+  const saveSmtpSettings = async (settings: SmtpSettings): Promise<boolean> => {
+    const result = await run(
+      token => invoke<SmtpSettings>('update_smtp_settings', { settings, token }),
+      'Не удалось сохранить настройки почты'
+    )
+    if (result) smtpSettings.value = { ...result, password: '' }
+    return result !== null
+  }
+
+  /**
+   * Пробное письмо на адрес текущего администратора с сохранёнными настройками
+   */
+  const sendTestEmail = async (): Promise<boolean> => {
+    const result = await run(
+      token => invoke<void>('send_test_email', { token }).then(() => true),
+      'Не удалось отправить пробное письмо'
+    )
+    return result === true
+  }
+
+  return {
+    smtpSettings,
+    isLoading,
+    error,
+    loadSmtpSettings,
+    saveSmtpSettings,
+    sendTestEmail
+  }
+}
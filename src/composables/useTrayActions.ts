@@ -0,0 +1,27 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import { useRouter } from 'vue-router'
+
+/** Действие, выбранное в меню значка в области уведомлений */
+export type TrayAction = 'open_calendar' | 'new_card' | 'show_overdue'
+
+export const useTrayActions = () => {
+  const router = useRouter()
+
+  const handleAction = async (action: TrayAction) => {
+    switch (action) {
+      case 'new_card':
+        await router.push({ name: 'calendar', query: { action: 'new' } })
+        break
+      case 'show_overdue':
+        await router.push({ name: 'calendar', query: { status: 'overdue' } })
+        break
+      default:
+        await router.push({ name: 'calendar' })
+    }
+  }
+
+  const subscribeToTray = async (): Promise<UnlistenFn> =>
+    listen<TrayAction>('tray-action', (event) => handleAction(event.payload))
+
+  return { subscribeToTray }
+}
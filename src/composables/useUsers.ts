@@ -2,10 +2,13 @@ import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
 import { useAuthStore } from '../stores/auth'
 import type { User, UserRole } from '../types/auth'
+import { getErrorMessage } from '../utils/errors'
 
 interface UserResponse {
   id: number
   username: string
+  full_name?: string | null
+  email?: string | null
   role: string
   created_at: string
 }
@@ -14,6 +17,8 @@ function mapUserResponse(user: UserResponse): User {
   return {
     id: user.id,
     username: user.username,
+    fullName: user.full_name ?? undefined,
+    email: user.email ?? undefined,
     role: user.role as UserRole,
     createdAt: user.created_at
   }
@@ -39,7 +44,7 @@ export const useUsers = () => {
       })
       users.value = usersData.map(mapUserResponse)
     } catch (err) {
-      const message = err instanceof Error ? err.message : 'Failed to fetch users'
+      const message = getErrorMessage(err, 'Failed to fetch users')
       error.value = message
       throw new Error(message)
     } finally {
@@ -47,7 +52,7 @@ export const useUsers = () => {
     }
   }
 
-  const createUser = async (username: string, password: string, role: UserRole) => {
+  const createUser = async (username: string, password: string, role: UserRole, fullName?: string, email?: string) => {
     if (!authStore.token) {
       throw new Error('Not authenticated')
     }
@@ -57,19 +62,21 @@ export const useUsers = () => {
     try {
       await invoke<number>('register_user', {
         username,
+        fullName: fullName || null,
+        email: email || null,
         password,
         role,
         token: authStore.token
       })
       await getAllUsers()
     } catch (err) {
-      const message = err instanceof Error ? err.message : 'Failed to create user'
+      const message = getErrorMessage(err, 'Failed to create user')
       error.value = message
       throw new Error(message)
     }
   }
 
-  const updateUser = async (id: number, username: string, role: UserRole) => {
+  const updateUser = async (id: number, username: string, role: UserRole, fullName?: string, email?: string) => {
     if (!authStore.token) {
       throw new Error('Not authenticated')
     }
@@ -80,12 +87,14 @@ export const useUsers = () => {
       await invoke<number>('update_user', {
         id,
         username,
+        fullName: fullName || null,
+        email: email || null,
         role,
         token: authStore.token
       })
       await getAllUsers()
     } catch (err) {
-      const message = err instanceof Error ? err.message : 'Failed to update user'
+      const message = getErrorMessage(err, 'Failed to update user')
       error.value = message
       throw new Error(message)
     }
@@ -105,7 +114,7 @@ export const useUsers = () => {
       })
       await getAllUsers()
     } catch (err) {
-      const message = err instanceof Error ? err.message : 'Failed to delete user'
+      const message = getErrorMessage(err, 'Failed to delete user')
       error.value = message
       throw new Error(message)
     }
@@ -125,7 +134,7 @@ export const useUsers = () => {
         token: authStore.token
       })
     } catch (err) {
-      const message = err instanceof Error ? err.message : 'Failed to change password'
+      const message = getErrorMessage(err, 'Failed to change password')
       error.value = message
       throw new Error(message)
     }
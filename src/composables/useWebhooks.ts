@@ -0,0 +1,143 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+import { useAuthStore } from '../stores/auth'
+import { getErrorMessage } from '../utils/errors'
+
+export type WebhookEvent = 'card.created' | 'card.closed' | 'card.overdue'
+
+export const WEBHOOK_EVENTS: { key: WebhookEvent, label: string }[] = [
+  { key: 'card.created', label: 'Карточка создана' },
+  { key: 'card.closed', label: 'Карточка исполнена' },
+  { key: 'card.overdue', label: 'Карточка просрочена' }
+]
+
+export interface Webhook {
+  id: number
+  url: string
+  events: WebhookEvent[]
+  enabled: boolean
+  created_at: string
+}
+
+export interface WebhookInput {
+  url: string
+  /** При изменении пустой секрет оставляет прежний */
+  secret: string | null
+  events: WebhookEvent[]
+  enabled: boolean
+}
+
+export type DeliveryStatus = 'pending' | 'sending' | 'delivered' | 'failed'
+
+export interface WebhookDelivery {
+  id: number
+  webhook_id: number
+  url: string | null
+  event: WebhookEvent
+  card_id: number
+  payload: string
+  status: DeliveryStatus
+  attempts: number
+  next_attempt_at: string
+  response_status: number | null
+  last_error: string | null
+  created_at: string
+  delivered_at: string | null
+}
+
+export const useWebhooks = () => {
+  const authStore = useAuthStore()
+  const webhooks = ref<Webhook[]>([])
+  const deliveries = ref<WebhookDelivery[]>([])
+  const isLoading = ref(false)
+  const error = ref<string | null>(null)
+
+  const run = async <T>(operation: (token: string) => Promise<T>, errorMessage: string): Promise<T | null> => {
+    if (!authStore.token) {
+      throw new Error('Not authenticated')
+    }
+
+    isLoading.value = true
+    error.value = null
+
+    try {
+      return await operation(authStore.token)
+    } catch (err) {
+      error.value = getErrorMessage(err, errorMessage)
+      return null
+    } finally {
+      isLoading.value = false
+    }
+  }
+
+  const loadWebhooks = async () => {
+    if (!authStore.isAdmin) return
+    const result = await run(
+      token => invoke<Webhook[]>('list_webhooks', { token }),
+      'Не удалось загрузить вебхуки'
+    )
+    if (result) webhooks.value = result
+  }
+
+  const loadDeliveries = async (webhookId: number | null = null) => {
+    if (!authStore.isAdmin) return
+    const result = await run(
+      token => invoke<WebhookDelivery[]>('list_webhook_deliveries', { webhookId, limit: 100, token }),
+      'Не удалось загрузить журнал доставки'
+    )
+    if (result) deliveries.value = result
+  }
+
+  const createWebhook = async (webhook: WebhookInput): Promise<boolean> => {
+    const result = await run(
+      token => invoke<Webhook>('create_webhook', { webhook, token }),
+      'Не удалось добавить вебхук'
+    )
+    if (result) webhooks.value = [...webhooks.value, result]
+    return result !== null
+  }
+
+  const updateWebhook = async (id: number, webhook: WebhookInput): Promise<boolean> => {
+    const result = await run(
+      token => invoke<Webhook>('update_webhook', { id, webhook, token }),
+      'Не удалось сохранить вебхук'
+    )
+    if (result) webhooks.value = webhooks.value.map(w => (w.id === id ? result : w))
+    return result !== null
+  }
+
+  const deleteWebhook = async (id: number) => {
+    const result = await run(
+      token => invoke<number>('delete_webhook', { id, token }),
+      'Не удалось удалить вебхук'
+    )
+    if (result !== null) {
+      webhooks.value = webhooks.value.filter(w => w.id !== id)
+      deliveries.value = deliveries.value.filter(d => d.webhook_id !== id)
+    }
+  }
+
+  /**
+   * Возвращает неудачную доставку в очередь; отправка произойдёт в течение минуты
+   */
+  const retryDelivery = async (id: number) => {
+    await run(
+      token => invoke<void>('retry_webhook_delivery', { id, token }),
+      'Не удалось повторить доставку'
+    )
+    await loadDeliveries()
+  }
+
+  return {
+    webhooks,
+    deliveries,
+    isLoading,
+    error,
+    loadWebhooks,
+    loadDeliveries,
+    createWebhook,
+    updateWebhook,
+    deleteWebhook,
+    retryDelivery
+  }
+}
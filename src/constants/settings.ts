@@ -20,7 +20,9 @@ export const DEFAULT_SETTINGS = {
   theme: 'auto' as const,
   language: 'ru' as const,
   firstDayOfWeek: 1 as const,
-  notifications: true,
-  dbPath: ''
+  dbPath: '',
+  multiUser: false,
+  dbKey: '',
+  readOnly: false
 }
 
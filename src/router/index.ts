@@ -5,6 +5,8 @@ import CalendarPage from '../pages/CalendarPage.vue'
 import SettingsPage from '../pages/SettingsPage.vue'
 import LoginView from '../pages/LoginView.vue'
 import UsersManagementPage from '../pages/UsersManagementPage.vue'
+import TrashPage from '../pages/TrashPage.vue'
+import ArchivePage from '../pages/ArchivePage.vue'
 
 const routes: RouteRecordRaw[] = [
   {
@@ -30,6 +32,18 @@ const routes: RouteRecordRaw[] = [
     name: 'users',
     component: UsersManagementPage,
     meta: { requiresAuth: true, role: ['admin'] }
+  },
+  {
+    path: '/trash',
+    name: 'trash',
+    component: TrashPage,
+    meta: { requiresAuth: true, role: ['admin', 'controller'] }
+  },
+  {
+    path: '/archive',
+    name: 'archive',
+    component: ArchivePage,
+    meta: { requiresAuth: true }
   }
 ]
 
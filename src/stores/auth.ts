@@ -2,10 +2,13 @@ import { defineStore } from 'pinia'
 import { ref, computed } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
 import type { User, UserRole } from '../types/auth'
+import { getErrorMessage } from '../utils/errors'
 
 interface UserResponse {
   id: number
   username: string
+  full_name?: string | null
+  email?: string | null
   role: string
   created_at: string
 }
@@ -14,6 +17,8 @@ function mapUserResponse(user: UserResponse): User {
   return {
     id: user.id,
     username: user.username,
+    fullName: user.full_name ?? undefined,
+    email: user.email ?? undefined,
     role: user.role as UserRole,
     createdAt: user.created_at
   }
@@ -35,7 +40,7 @@ export const useAuthStore = defineStore('auth', () => {
       localStorage.setItem('auth_token', jwt)
       await fetchCurrentUser()
     } catch (error) {
-      throw new Error(error instanceof Error ? error.message : 'Login failed')
+      throw new Error(getErrorMessage(error, 'Login failed'))
     }
   }
 
@@ -43,6 +48,22 @@ export const useAuthStore = defineStore('auth', () => {
     token.value = null
     user.value = null
     localStorage.removeItem('auth_token')
+    // Сохранённая для профиля БД сессия тоже больше не действительна
+    invoke('forget_profile_session').catch(() => {})
+  }
+
+  /**
+   * Сессия, сохранённая для профиля при переключении БД. Без неё нужен повторный вход.
+   */
+  async function adoptSession(sessionToken: string | null): Promise<void> {
+    if (!sessionToken) {
+      logout()
+      return
+    }
+
+    token.value = sessionToken
+    localStorage.setItem('auth_token', sessionToken)
+    await fetchCurrentUser()
   }
 
   async function fetchCurrentUser(): Promise<void> {
@@ -71,6 +92,7 @@ export const useAuthStore = defineStore('auth', () => {
     isController,
     login,
     logout,
+    adoptSession,
     fetchCurrentUser
   }
 })
@@ -3,6 +3,9 @@ export type UserRole = 'admin' | 'user' | 'controller'
 export interface User {
   id: number
   username: string
+  fullName?: string
+  /** Адрес для напоминаний о сроках */
+  email?: string
   role: UserRole
   createdAt: string
 }
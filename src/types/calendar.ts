@@ -18,6 +18,12 @@ export interface ControlCard {
   department?: string
   controller?: string
   controllerUserId?: number
+  formattedNumber?: string
+  numberingSchemeId?: number
+  version?: number
+  updatedAt?: string
+  executionReport?: string
+  completedAt?: string
 }
 
 export interface CalendarEvent {
@@ -4,12 +4,53 @@ export type Language = 'ru' | 'en'
 
 export type FirstDayOfWeek = 0 | 1
 
+export type ConnectionMode = 'single' | 'multi_user'
+
+export interface ConnectionInfo {
+  path: string
+  mode: ConnectionMode
+  journal_mode: string
+  storage: {
+    network: boolean
+    filesystem: string | null
+  }
+  client_id: string | null
+  encrypted: boolean
+  read_only: boolean
+  warnings: string[]
+}
+
+/** Именованная БД рабочего места */
+export interface DatabaseProfile {
+  id: string
+  name: string
+  path: string
+  read_only: boolean
+  mode: ConnectionMode
+  last_used_at: string | null
+}
+
+export interface ProfileList {
+  profiles: DatabaseProfile[]
+  active_id: string | null
+}
+
+export interface ProfileSwitch {
+  profile: DatabaseProfile
+  connection: ConnectionInfo
+  session_token: string | null
+}
+
 export interface AppSettings {
   theme: Theme
   language: Language
   firstDayOfWeek: FirstDayOfWeek
-  notifications: boolean
   dbPath: string
+  multiUser: boolean
+  /** Ключ шифрования БД; хранится только на этом рабочем месте */
+  dbKey: string
+  /** Открывать БД только для чтения */
+  readOnly: boolean
 }
 
 export interface SelectOption<T = string> {
@@ -17,3 +58,30 @@ export interface SelectOption<T = string> {
   label: string
 }
 
+
+export interface DiagnosticReport {
+  path: string
+  file: {
+    exists: boolean
+    size_bytes: number | null
+    readonly: boolean
+    encrypted: boolean
+    directory_writable: boolean
+    journal_files: string[]
+  }
+  open_error: string | null
+  write_locked: boolean
+  lock_holders: { hostname: string; os_username: string; last_seen: string }[]
+  integrity_errors: string[]
+  schema_version: number | null
+  supported_schema_version: number
+  orphaned_references: { table: string; column: string; target: string; count: number }[]
+  row_counts: { table: string; rows: number }[]
+  problems: string[]
+}
+
+export interface RepairReport {
+  backup_path: string
+  actions: { description: string; rows: number }[]
+  report: DiagnosticReport
+}
@@ -0,0 +1,45 @@
+/**
+ * Ошибка, возвращаемая командами бэкенда
+ */
+export interface AppError {
+  code: 'not_connected' | 'unauthorized' | 'forbidden' | 'not_found' | 'validation' | 'conflict' | 'read_only' | 'db'
+  message: string
+  details?: { field?: string, current?: unknown } | null
+}
+
+const ERROR_MESSAGES: Record<AppError['code'], string> = {
+  not_connected: 'База данных не подключена',
+  unauthorized: 'Ошибка авторизации: неверные учётные данные или истёк сеанс',
+  forbidden: 'Недостаточно прав для выполнения операции',
+  not_found: 'Запись не найдена',
+  validation: 'Некорректные данные',
+  conflict: 'Данные были изменены другим пользователем',
+  read_only: 'База данных открыта только для чтения, изменения недоступны',
+  db: 'Ошибка базы данных'
+}
+
+export function isAppError(error: unknown): error is AppError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
+}
+
+/** У этих ошибок сообщение бэкенда постоянное и ничего не добавляет к локализованному */
+const FIXED_MESSAGE_CODES: AppError['code'][] = ['not_connected', 'read_only']
+
+/**
+ * Локализованный текст ошибки с исходным сообщением бэкенда (путь к файлу, ответ почтового
+ * сервера и т.п.). Для ошибок валидации добавляется имя поля.
+ */
+export function getErrorMessage(error: unknown, fallback: string): string {
+  if (isAppError(error)) {
+    const localized = ERROR_MESSAGES[error.code] ?? fallback
+    if (FIXED_MESSAGE_CODES.includes(error.code) || !error.message) {
+      return localized
+    }
+    const field = error.details?.field ? ` (${error.details.field})` : ''
+    return `${localized}${field}: ${error.message}`
+  }
+  if (error instanceof Error) {
+    return error.message
+  }
+  return typeof error === 'string' ? error : fallback
+}